@@ -1,16 +1,31 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable::{self, UpgradeableLoaderState},
+    clock::Clock,
     entrypoint,
     entrypoint::ProgramResult,
     msg,
-    program::{invoke, invoke_signed},
+    log::sol_log_data,
+    program::{invoke, invoke_signed, set_return_data},
     program_error::ProgramError,
+    program_pack::Pack,
+    program_utils::limited_deserialize,
     pubkey::Pubkey,
     rent::Rent,
     system_instruction,
     sysvar::Sysvar,
 };
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    onchain::invoke_transfer_checked,
+    state::{Account as TokenAccount, Mint},
+};
+use thiserror::Error;
 
 // Define program ID
 solana_program::declare_id!("Your_Program_ID_Here");
@@ -18,122 +33,4656 @@ solana_program::declare_id!("Your_Program_ID_Here");
 // Define instruction types
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum DepositInstruction {
-    /// Инициализация аккаунта пользователя
+    /// Инициализация аккаунта пользователя в указанном vault
+    /// Логирует событие `init` (см. `InitEvent`)
     /// 0. `[signer]` Пользователь, который будет владельцем аккаунта
     /// 1. `[writable]` Аккаунт данных пользователя (PDA)
     /// 2. `[]` System program
-    InitializeAccount,
+    InitializeAccount { vault_id: u16 },
 
     /// Внесение депозита
+    /// Если vault ещё не освобождён от ренты, часть первого депозита остаётся
+    /// в vault как резерв ренты и не зачисляется пользователю; при дневном лимите
+    /// старый аккаунт пользователя расширяется за счёт пользователя.
+    /// Зачисленная сумма и новый баланс возвращаются через return data и
+    /// событие `deposit` (см. `DepositEvent`). Если в vault включены
+    /// проценты, накопленные проценты сначала выплачиваются из rewards аккаунта
+    /// (событие `interest`). Сумма меньше минимального депозита vault отклоняется
+    /// 0. `[signer, writable]` Пользователь, который вносит депозит
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[]` Config аккаунт vault (PDA, должен быть инициализирован)
+    /// 5. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты или задан
+    ///    лимит TVL
+    /// 6. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    Deposit { vault_id: u16, amount: u64 },
+
+    /// Вывод средств
+    /// Выведенная сумма и новый баланс возвращаются через return data и
+    /// событие `withdraw` (см. `WithdrawEvent`). Накопленные проценты
+    /// выплачиваются до расчёта суммы, так же как при `Deposit`; комиссия за
+    /// вывод списывается с баланса вместе с суммой и переводится в fee vault.
+    /// Остаток меньше минимального баланса vault допускается только нулевым;
+    /// выведенное за эпоху кластера не может превысить лимит vault
+    /// 0. `[signer]` Пользователь, который выводит средства
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    /// 5. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты, комиссия за
+    ///    вывод или лимит TVL
+    /// 6. `[writable]` Fee vault (PDA), если в vault есть комиссия за вывод или лимит TVL
+    /// 7. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    Withdraw { vault_id: u16, amount: u64 },
+
+    /// Увеличение размера аккаунта пользователя под будущие поля
+    /// 0. `[signer, writable]` Владелец аккаунта, оплачивающий дополнительную ренту
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[]` System program
+    GrowAccount { vault_id: u16, new_size: u32 },
+
+    /// Создание config аккаунта vault; подписант становится администратором,
+    /// bump vault PDA записывается в config. Создание должен подписать
+    /// upgrade authority программы, поэтому у неизменяемой программы новых
+    /// config не бывает. Депозиты в vault без config не принимаются
+    /// 0. `[signer, writable]` Администратор, оплачивающий создание аккаунта
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    /// 3. `[]` ProgramData аккаунт программы (загрузчик BPF upgradeable)
+    /// 4. `[signer]` Upgrade authority программы
+    InitializeConfig { vault_id: u16 },
+
+    /// Независимая приостановка депозитов и выводов
+    /// 0. `[signer]` Администратор vault или держатель роли `Pauser`
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    SetPauseFlags {
+        vault_id: u16,
+        deposits: bool,
+        withdrawals: bool,
+    },
+
+    /// Внесение депозита с линейным графиком разблокировки (vesting)
+    /// Резерв ренты vault, дневной лимит и результат — так же, как при `Deposit`
+    /// 0. `[signer, writable]` Пользователь, который вносит депозит
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[]` Config аккаунт vault (PDA, должен быть инициализирован)
+    /// 5. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты или задан
+    ///    лимит TVL
+    /// 6. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    DepositVested {
+        vault_id: u16,
+        amount: u64,
+        vest_start: i64,
+        vest_end: i64,
+    },
+
+    /// Начисление наград нескольким пользователям из rewards аккаунта (только администратор)
+    /// Сумма начислений переводится из rewards аккаунта в vault, поэтому vault
+    /// остаётся обеспеченным, а в rewards аккаунте остаётся резерв ренты; не
+    /// более `MAX_BATCH_CREDITS` начислений за раз.
+    /// Начисление считается депозитом в `lifetime_deposited`; аккаунты
+    /// пользователей должны быть инициализированы. Если в vault включены
+    /// проценты, сначала выплачиваются накопленные проценты, как при `Deposit`,
+    /// а аккаунт старого размера расширяется за счёт подписанта. Если задан
+    /// лимит TVL, начисления и проценты учитываются в stats аккаунте, и пакет,
+    /// после которого TVL превысил бы лимит, отклоняется. Каждое начисление
+    /// логируется событием `credit` (см. `BalanceEvent`)
+    /// 0. `[signer, writable]` Администратор vault или держатель роли `Distributor`
+    /// 1. `[]` Config аккаунт vault (PDA)
+    /// 2. `[writable]` Rewards аккаунт vault (PDA)
+    /// 3. `[writable]` Vault аккаунт программы (PDA)
+    /// 4. `[]` System program
+    /// 5. `[writable]` Stats аккаунт vault (PDA), читается, только если задан лимит TVL
+    /// 6. `[writable]` Аккаунты данных пользователей (PDA) в порядке `credits` (и далее)
+    BatchCredit {
+        vault_id: u16,
+        credits: Vec<(Pubkey, u64)>,
+    },
+
+    /// Назначение роли (только администратор); config, созданный до появления
+    /// ролей, расширяется за счёт администратора
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    GrantRole {
+        vault_id: u16,
+        role: Role,
+        authority: Pubkey,
+    },
+
+    /// Отзыв роли (только администратор)
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    RevokeRole { vault_id: u16, role: Role },
+
+    /// Возврат пылевого баланса владельцу (только администратор)
+    /// Сначала выплачиваются накопленные проценты; затем баланс должен быть
+    /// меньше `threshold` и порога пыли из `SetDustThreshold` и не содержать
+    /// невыплаченного вестинга. Если владелец включил `SetSweepToTreasury`,
+    /// баланс уходит в fee vault, откуда его забирает `CollectFees` (пустой fee
+    /// vault администратор пополняет до резерва ренты). После возврата аккаунт
+    /// остаётся с нулевым балансом и помечается закрываемым: `CloseAccount` не
+    /// ждёт конца окна депозитов, пока на аккаунт снова не внесут средства.
+    /// Если задан лимит TVL, проценты и возврат учитываются в stats аккаунте.
+    /// Возврат логируется событием `sweep-dust` (см. `BalanceEvent`)
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[]` Config аккаунт vault (PDA)
+    /// 2. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 3. `[writable]` Владелец аккаунта пользователя
+    /// 4. `[writable]` Vault аккаунт программы (PDA)
+    /// 5. `[]` System program
+    /// 6. `[writable]` Stats аккаунт vault (PDA), читается, только если задан лимит TVL
+    /// 7. `[writable]` Rewards аккаунт vault (PDA), читается, только если включены проценты
+    /// 8. `[writable]` Fee vault (PDA), читается, только если владелец включил возврат в него
+    SweepDust { vault_id: u16, threshold: u64 },
+
+    /// Установка дневного лимита депозитов на аккаунт (только администратор);
+    /// 0 снимает лимит, config, созданный до появления лимита, расширяется за
+    /// счёт администратора
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    SetDepositLimit { vault_id: u16, daily_limit: u64 },
+
+    /// Расчёт суммы, которую владелец может вывести прямо сейчас, с учётом
+    /// всех проверок `Withdraw`: паузы выводов, вестинга и блокировок, запроса
+    /// на вывод при заданной задержке, оставшегося лимита эпохи и минимального
+    /// остатка; сумма (u64, little-endian) возвращается через return data,
+    /// аккаунты не изменяются
+    /// 0. `[]` Владелец аккаунта пользователя
+    /// 1. `[]` Аккаунт данных пользователя (PDA)
+    /// 2. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    GetWithdrawable { vault_id: u16 },
+
+    /// Безвозвратный отказ от прав администратора (только администратор);
+    /// администратор и все роли сбрасываются на `Pubkey::default()`, после
+    /// чего ни одна администраторская инструкция больше не выполнится.
+    /// Отказ логируется событием `admin` со старым и новым администратором
+    /// 0. `[signer]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    RenounceAdmin { vault_id: u16 },
+
+    /// Вывод всего, что превышает `target_balance`, так что на аккаунте
+    /// остаётся ровно `target_balance`; сумма считается по балансу на момент
+    /// выполнения. Аккаунты и результат те же, что у `Withdraw`
+    /// 0. `[signer]` Пользователь, который выводит средства
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    /// 5. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты, комиссия за
+    ///    вывод или лимит TVL
+    /// 6. `[writable]` Fee vault (PDA), если в vault есть комиссия за вывод или лимит TVL
+    /// 7. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    WithdrawToRemaining { vault_id: u16, target_balance: u64 },
+
+    /// Инициализация аккаунта пользователя для SPL токена `mint` (SPL Token
+    /// или Token-2022) в указанном vault; token-аккаунт vault (ATA vault PDA)
+    /// создаётся за счёт пользователя, если его ещё нет
+    /// 0. `[signer, writable]` Пользователь, который будет владельцем аккаунта
+    /// 1. `[writable]` Аккаунт данных пользователя для mint (PDA)
+    /// 2. `[]` Mint токена
+    /// 3. `[]` Vault аккаунт программы (PDA), владелец token-аккаунта vault
+    /// 4. `[writable]` Token-аккаунт vault (ATA)
+    /// 5. `[]` System program
+    /// 6. `[]` Token program mint (SPL Token или Token-2022)
+    /// 7. `[]` Associated Token Account program
+    InitializeTokenAccount { vault_id: u16 },
+
+    /// Внесение депозита в SPL токенах; пауза депозитов действует так же, как
+    /// при `Deposit`, дневной лимит (в лампортах) к токенам не применяется.
+    /// Зачисляется фактически полученная vault сумма, то есть за вычетом
+    /// комиссии перевода Token-2022. Для mint с transfer hook после остальных
+    /// аккаунтов передаются программа hook, её validation аккаунт и аккаунты
+    /// из него. Результат — через return data и событие `deposit-token`
     /// 0. `[signer]` Пользователь, который вносит депозит
+    /// 1. `[writable]` Аккаунт данных пользователя для mint (PDA)
+    /// 2. `[writable]` Token-аккаунт пользователя, с которого списываются токены
+    /// 3. `[writable]` Token-аккаунт vault (ATA)
+    /// 4. `[]` Mint токена
+    /// 5. `[]` Token program mint (SPL Token или Token-2022)
+    /// 6. `[]` Config аккаунт vault (PDA, должен быть инициализирован)
+    /// 7. `[]` Дополнительные аккаунты transfer hook mint, если он задан (и далее)
+    DepositToken { vault_id: u16, amount: u64 },
+
+    /// Вывод SPL токенов на любой token-аккаунт того же mint; с баланса
+    /// списывается `amount`, комиссию перевода Token-2022 несёт получатель.
+    /// Отклоняется, пока задана задержка вывода; минимальный остаток и лимит за
+    /// эпоху — как у `Withdraw`. Результат — через return data и событие
+    /// `withdraw-token`
+    /// 0. `[signer]` Пользователь, который выводит средства
+    /// 1. `[writable]` Аккаунт данных пользователя для mint (PDA)
+    /// 2. `[writable]` Token-аккаунт получателя
+    /// 3. `[writable]` Token-аккаунт vault (ATA)
+    /// 4. `[]` Vault аккаунт программы (PDA), владелец token-аккаунта vault
+    /// 5. `[]` Mint токена
+    /// 6. `[]` Token program mint (SPL Token или Token-2022)
+    /// 7. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    /// 8. `[]` Дополнительные аккаунты transfer hook, как у `DepositToken`, и
+    ///    System program, если задан лимит за эпоху, а аккаунт данных старого
+    ///    размера: он расширяется за счёт пользователя (тогда writable) (и далее)
+    WithdrawToken { vault_id: u16, amount: u64 },
+
+    /// Внесение нативных SOL, которые оборачиваются в wSOL на token-аккаунте
+    /// vault, так что баланс ведётся так же, как у любого токена (аккаунт
+    /// пользователя для native mint из `InitializeTokenAccount`); дневной
+    /// лимит, как и у `DepositToken`, не применяется. Результат — через
+    /// return data и событие `deposit-wrapped`
+    /// 0. `[signer, writable]` Пользователь, который вносит депозит
+    /// 1. `[writable]` Аккаунт данных пользователя для native mint (PDA)
+    /// 2. `[writable]` wSOL token-аккаунт vault (ATA)
+    /// 3. `[]` Native mint (wSOL)
+    /// 4. `[]` System program
+    /// 5. `[]` SPL Token program
+    /// 6. `[]` Config аккаунт vault (PDA, должен быть инициализирован)
+    DepositWrapped { vault_id: u16, amount: u64 },
+
+    /// Вывод wSOL баланса нативными SOL: сумма переводится на временный
+    /// token-аккаунт (PDA), который сразу закрывается на пользователя, так что
+    /// пользователь получает `amount` лампортов, а рента временного аккаунта
+    /// возвращается ему же. Задержка вывода, минимальный остаток и лимит за
+    /// эпоху — как у `WithdrawToken`. Результат — через return data и событие
+    /// `withdraw-unwrapped`
+    /// 0. `[signer, writable]` Пользователь, который выводит средства
+    /// 1. `[writable]` Аккаунт данных пользователя для native mint (PDA)
+    /// 2. `[writable]` wSOL token-аккаунт vault (ATA)
+    /// 3. `[]` Vault аккаунт программы (PDA), владелец token-аккаунтов vault
+    /// 4. `[writable]` Временный token-аккаунт для разворачивания (PDA)
+    /// 5. `[]` Native mint (wSOL)
+    /// 6. `[]` System program
+    /// 7. `[]` SPL Token program
+    /// 8. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    WithdrawUnwrapped { vault_id: u16, amount: u64 },
+
+    /// Создание vault аккаунта (системный аккаунт без данных) с резервом ренты
+    /// за счёт подписанта, чтобы первый депозит зачислялся полностью; если
+    /// config уже создан, в него записывается bump vault PDA (config старого
+    /// формата расширяется за счёт подписанта). Повторный вызов, которому
+    /// нечего делать, завершается ошибкой `AccountAlreadyInitialized`
+    /// 0. `[signer, writable]` Плательщик
+    /// 1. `[writable]` Vault аккаунт программы (PDA)
+    /// 2. `[writable]` Config аккаунт vault (PDA, может быть не инициализирован)
+    /// 3. `[]` System program
+    InitializeVault { vault_id: u16 },
+
+    /// Закрытие пустого user аккаунта (SOL или токенов): данные обнуляются,
+    /// лампорты ренты возвращаются владельцу. Баланс должен быть нулевым; если
+    /// у vault есть дневной лимит, окно депозитов должно завершиться, чтобы
+    /// повторное создание аккаунта не сбрасывало лимит, кроме аккаунтов, с
+    /// которых `SweepDust` вернул пыль
+    /// 0. `[signer, writable]` Владелец аккаунта
+    /// 1. `[writable]` User аккаунт программы (PDA)
+    /// 2. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    CloseAccount { vault_id: u16 },
+
+    /// Вывод всего баланса; сумма берётся из баланса на момент выполнения, так
+    /// что транзакции, изменившие баланс раньше, не оставляют остатка.
+    /// Аккаунты и результат те же, что у `Withdraw`
+    /// 0. `[signer]` Пользователь, который выводит средства
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    /// 5. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты, комиссия за
+    ///    вывод или лимит TVL
+    /// 6. `[writable]` Fee vault (PDA), если в vault есть комиссия за вывод или лимит TVL
+    /// 7. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    WithdrawAll { vault_id: u16 },
+
+    /// Первый шаг передачи user аккаунта (SOL или токенов) другому владельцу:
+    /// владелец предлагает `new_owner`, который затем принимает передачу через
+    /// `AcceptOwnership`; `Pubkey::default()` отменяет предложение. Аккаунт
+    /// старого формата расширяется за счёт владельца
+    /// 0. `[signer, writable]` Владелец аккаунта
+    /// 1. `[writable]` User аккаунт программы (PDA)
+    /// 2. `[]` System program
+    TransferOwnership { vault_id: u16, new_owner: Pubkey },
+
+    /// Второй шаг передачи: предложенный владелец принимает её. Баланс, вестинг,
+    /// итоги и окно лимита переносятся в PDA нового владельца (рента за его
+    /// счёт), старый PDA закрывается, его рента возвращается прежнему владельцу.
+    /// Передача логируется событием `ownership` (см. `OwnershipEvent`)
+    /// 0. `[signer, writable]` Новый владелец
+    /// 1. `[writable]` User аккаунт прежнего владельца (PDA)
+    /// 2. `[writable]` User аккаунт нового владельца (PDA, ещё не создан)
+    /// 3. `[writable]` Прежний владелец
+    /// 4. `[]` System program
+    AcceptOwnership { vault_id: u16 },
+
+    /// Назначение делегата, который может выводить с SOL аккаунта владельца
+    /// не более `cap` лампортов в сумме; новый вызов заменяет делегата и
+    /// остаток лимита. Аккаунт старого формата расширяется за счёт владельца
+    /// 0. `[signer, writable]` Владелец аккаунта
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[]` System program
+    SetDelegate { vault_id: u16, delegate: Pubkey, cap: u64 },
+
+    /// Отзыв делегата вместе с неиспользованным остатком лимита
+    /// 0. `[signer]` Владелец аккаунта
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    RevokeDelegate { vault_id: u16 },
+
+    /// Вывод делегатом на свой адрес в пределах остатка лимита; действуют те
+    /// же ограничения, что у `Withdraw` владельца (пауза, вестинг, комиссия,
+    /// лимит за эпоху, который делегат делит с владельцем). Накопленные
+    /// проценты сначала выплачиваются владельцу; вывод логируется событием
+    /// `withdraw` с делегатом в качестве получателя
+    /// 0. `[signer, writable]` Делегат, получающий средства
+    /// 1. `[writable]` Аккаунт данных владельца (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    /// 5. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты, комиссия за
+    ///    вывод или лимит TVL
+    /// 6. `[writable]` Fee vault (PDA), если в vault есть комиссия за вывод или лимит TVL
+    /// 7. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    DelegatedWithdraw { vault_id: u16, amount: u64 },
+
+    /// Внесение депозита с блокировкой вывода до `unlock_ts` (unix timestamp)
+    /// Пока часы кластера не дошли до `unlock_ts`, вывод с аккаунта отклоняется;
+    /// повторная блокировка может только продлить срок. Резерв ренты vault,
+    /// дневной лимит и результат — так же, как при `Deposit`
+    /// 0. `[signer, writable]` Пользователь, который вносит депозит
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[]` Config аккаунт vault (PDA, должен быть инициализирован)
+    /// 5. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты или задан
+    ///    лимит TVL
+    /// 6. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    DepositLocked { vault_id: u16, amount: u64, unlock_ts: i64 },
+
+    /// Настройка уровня блокировки `tier` (только администратор): срок в секундах
+    /// и множитель наград в базисных пунктах (`MULTIPLIER_BPS_ONE` — 1x, меньше
+    /// нельзя); нулевой `duration` отключает уровень. Config, созданный до
+    /// появления уровней, расширяется за счёт администратора
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    SetLockupTier { vault_id: u16, tier: u8, duration: i64, multiplier_bps: u16 },
+
+    /// Внесение депозита, блокируемого на срок уровня `tier` из config vault
+    /// Сумма записывается отдельным траншем со сроком и множителем уровня и не
+    /// может быть выведена до окончания срока; одновременно действует не более
+    /// `MAX_LOCKUP_TRANCHES` траншей. Резерв ренты vault, дневной лимит и
+    /// результат — так же, как при `Deposit`
+    /// 0. `[signer, writable]` Пользователь, который вносит депозит
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[]` Config аккаунт vault (PDA)
+    /// 5. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты или задан
+    ///    лимит TVL
+    /// 6. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    DepositTiered { vault_id: u16, amount: u64, tier: u8 },
+
+    /// Начисление процентов (может вызвать кто угодно): индекс процентов vault
+    /// доводится до текущего времени и сохраняется, каждому переданному
+    /// аккаунту выплачиваются накопленные проценты из rewards аккаунта. Если
+    /// rewards аккаунт не покрывает начисление, выплачивается только то, что
+    /// в нём есть сверх резерва ренты; остальное сгорает. Аккаунты токенов
+    /// отклоняются, аккаунты старого размера пропускаются. Если задан лимит TVL,
+    /// выплаты учитываются в stats аккаунте
+    /// 0. `[writable]` Config аккаунт vault (PDA)
+    /// 1. `[writable]` Rewards аккаунт vault (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[writable]` Stats аккаунт vault (PDA), читается, только если задан лимит TVL
+    /// 5. `[writable]` Аккаунты данных пользователей (PDA) (и далее)
+    AccrueInterest { vault_id: u16 },
+
+    /// Установка годовой процентной ставки в базисных пунктах (только
+    /// администратор). Проценты до этого момента начисляются по прежней ставке;
+    /// первая установка включает проценты в vault, после чего депозиты и выводы
+    /// требуют rewards аккаунт. Config старого размера расширяется за счёт
+    /// администратора
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    SetInterestRate { vault_id: u16, rate_bps: u16 },
+
+    /// Настройка экономики vault одной инструкцией (только администратор):
+    /// годовая процентная ставка (как в `SetInterestRate`), комиссия за вывод
+    /// в базисных пунктах (не больше `MAX_WITHDRAW_FEE_BPS`), которая
+    /// удерживается из выводимой суммы и уходит в fee vault, и минимальный
+    /// депозит в лампортах (0 — без минимума).
+    /// Каждый изменённый параметр логируется событием `param`. При ненулевой
+    /// комиссии администратор пополняет fee vault до резерва ренты
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    /// 3. `[writable]` Fee vault (PDA), только при ненулевой комиссии
+    SetParams { vault_id: u16, interest_rate_bps: u16, withdraw_fee_bps: u16, min_deposit: u64 },
+
+    /// Вывод накопленных комиссий из fee vault на указанный адрес (только
+    /// администратор); в fee vault остаётся резерв ренты. Получатель и сумма
+    /// логируются событием `fees`
+    /// 0. `[signer]` Администратор vault
+    /// 1. `[]` Config аккаунт vault (PDA)
+    /// 2. `[writable]` Fee vault (PDA)
+    /// 3. `[writable]` Получатель комиссий
+    /// 4. `[]` System program
+    CollectFees { vault_id: u16 },
+
+    /// Настройка уровня комиссии `tier` (только администратор): для балансов от
+    /// `min_balance` лампортов при выводе удерживается `fee_bps` (не больше
+    /// `MAX_WITHDRAW_FEE_BPS`) вместо комиссии из `SetParams`; из подходящих
+    /// уровней действует уровень с наибольшим `min_balance`, нулевой
+    /// `min_balance` отключает уровень. При ненулевой комиссии администратор
+    /// пополняет fee vault до резерва ренты
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    /// 3. `[writable]` Fee vault (PDA), только при ненулевой комиссии
+    SetFeeTier { vault_id: u16, tier: u8, min_balance: u64, fee_bps: u16 },
+
+    /// Установка максимального баланса одного аккаунта в лампортах (только
+    /// администратор), например на время бета-запуска; 0 снимает ограничение.
+    /// Депозит, после которого баланс превысил бы максимум, отклоняется, уже
+    /// внесённые средства не затрагиваются. Config старого размера расширяется
+    /// за счёт администратора
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    SetMaxBalancePerUser { vault_id: u16, max_balance: u64 },
+
+    /// Установка лимита TVL vault в лампортах (только администратор); 0 снимает
+    /// лимит. Пока лимит задан, в stats аккаунте ведётся сумма зачисленного на
+    /// балансы SOL депозитами, начислениями и процентами за вычетом списанного
+    /// выводами и возвратами пыли, и депозит или начисление,
+    /// после которого она превысила бы лимит, отклоняется. Stats аккаунт
+    /// создаётся за счёт администратора; при включении лимита счётчик берётся
+    /// из баланса vault сверх резерва ренты. Config старого размера
+    /// расширяется за счёт администратора
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[writable]` Stats аккаунт vault (PDA)
+    /// 3. `[]` Vault аккаунт программы (PDA)
+    /// 4. `[]` System program
+    SetTvlCap { vault_id: u16, cap: u64 },
+
+    /// Установка минимального остатка после частичного вывода в лампортах
+    /// (только администратор), чтобы в vault не оставались пыльные аккаунты;
+    /// вывод всего баланса разрешён всегда, 0 снимает ограничение. Вместе с
+    /// минимальным депозитом из `SetParams`. Config старого размера
+    /// расширяется за счёт администратора
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    SetMinResidualBalance { vault_id: u16, min_balance: u64 },
+
+    /// Запрос вывода `amount` лампортов с SOL аккаунта: сумма и время запроса
+    /// записываются в аккаунт, а сами средства выводятся через `ClaimWithdraw`
+    /// после задержки vault. Новый запрос заменяет прежний и заново запускает
+    /// задержку, нулевой `amount` отменяет запрос. Аккаунт старого формата
+    /// расширяется за счёт владельца
+    /// 0. `[signer, writable]` Владелец аккаунта
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    /// 3. `[]` System program
+    RequestWithdraw { vault_id: u16, amount: u64 },
+
+    /// Вывод запрошенной через `RequestWithdraw` суммы, когда с момента запроса
+    /// прошла задержка vault; задержка берётся из config на момент вывода.
+    /// Проверки, комиссия, результат и аккаунты те же, что у `Withdraw`
+    /// 0. `[signer]` Пользователь, который выводит средства
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    /// 5. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты, комиссия за
+    ///    вывод или лимит TVL
+    /// 6. `[writable]` Fee vault (PDA), если в vault есть комиссия за вывод или лимит TVL
+    /// 7. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    ClaimWithdraw { vault_id: u16 },
+
+    /// Установка задержки вывода в секундах (только администратор): пока она
+    /// задана, SOL выводится только парой `RequestWithdraw` и `ClaimWithdraw`,
+    /// а `Withdraw`, `WithdrawAll`, `WithdrawToRemaining` и `DelegatedWithdraw`
+    /// отклоняются, как и `WithdrawToken` и `WithdrawUnwrapped`, у которых нет
+    /// запроса, что даёт время заметить скомпрометированный ключ; 0
+    /// отключает задержку, больше `MAX_WITHDRAW_COOLDOWN` задать нельзя. Config
+    /// старого размера расширяется за счёт администратора
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    SetWithdrawCooldown { vault_id: u16, cooldown: i64 },
+
+    /// Установка лимита выводов на аккаунт за эпоху кластера в лампортах (только
+    /// администратор); 0 снимает лимит. Учёт начинается заново, когда эпоха
+    /// `Clock` сменилась, комиссия за вывод входит в сумму. Config старого
+    /// размера расширяется за счёт администратора
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    SetWithdrawLimit { vault_id: u16, epoch_limit: u64 },
+
+    /// Депозит в пользу `beneficiary`: средства вносит плательщик, а зачисляются
+    /// они на уже открытый SOL аккаунт получателя, например для выплат или
+    /// подарков. Ограничения vault (минимум, максимальный баланс, дневной лимит
+    /// получателя, лимит TVL) и результат — так же, как при `Deposit`; аккаунт
+    /// старого формата расширяется за счёт плательщика
+    /// 0. `[signer, writable]` Плательщик
+    /// 1. `[writable]` Аккаунт данных получателя (PDA)
+    /// 2. `[writable]` Vault аккаунт программы (PDA)
+    /// 3. `[]` System program
+    /// 4. `[]` Config аккаунт vault (PDA, должен быть инициализирован)
+    /// 5. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты или задан
+    ///    лимит TVL
+    /// 6. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    DepositFor { vault_id: u16, amount: u64, beneficiary: Pubkey },
+
+    /// Вывод средств на произвольный адрес, например адрес депозита на бирже;
+    /// подписывает по-прежнему владелец, а проверки, комиссия и результат те
+    /// же, что у `Withdraw`. Получатель стоит сразу после config, поэтому
+    /// необязательные аккаунты `Withdraw` сдвигаются на одну позицию
+    /// 0. `[signer]` Пользователь, который выводит средства
     /// 1. `[writable]` Аккаунт данных пользователя (PDA)
     /// 2. `[writable]` Vault аккаунт программы (PDA)
     /// 3. `[]` System program
-    Deposit { amount: u64 },
+    /// 4. `[]` Config аккаунт vault (PDA, может быть не инициализирован)
+    /// 5. `[writable]` Получатель средств
+    /// 6. `[writable]` Rewards аккаунт vault (PDA), если в vault включены проценты, комиссия за
+    ///    вывод или лимит TVL
+    /// 7. `[writable]` Fee vault (PDA), если в vault есть комиссия за вывод или лимит TVL
+    /// 8. `[writable]` Stats аккаунт vault (PDA), только если задан лимит TVL
+    WithdrawTo { vault_id: u16, amount: u64 },
+
+    /// `Deposit` с мемо, например идентификатором платежа на бирже, не длиннее
+    /// `MAX_MEMO_LEN` байт. Аккаунты и результат те же, что у `Deposit`; мемо
+    /// пишется в лог и событие `memo`, а если после аккаунтов `Deposit`
+    /// передана программа SPL Memo, ещё и через неё с подписью плательщика
+    DepositWithMemo { vault_id: u16, amount: u64, memo: String },
+
+    /// `Withdraw` с мемо не длиннее `MAX_MEMO_LEN` байт. Аккаунты и результат
+    /// те же, что у `Withdraw`, мемо записывается так же, как при
+    /// `DepositWithMemo`
+    WithdrawWithMemo { vault_id: u16, amount: u64, memo: String },
+
+    /// Установка порога пыли в лампортах (только администратор): `SweepDust`
+    /// возвращает только балансы меньше него, 0 запрещает возврат пыли.
+    /// Config старого размера расширяется за счёт администратора
+    /// 0. `[signer, writable]` Администратор vault
+    /// 1. `[writable]` Config аккаунт vault (PDA)
+    /// 2. `[]` System program
+    SetDustThreshold { vault_id: u16, threshold: u64 },
+
+    /// Согласие владельца SOL аккаунта на возврат пыли в fee vault vault вместо
+    /// своего адреса (`enabled`), например если пыль не стоит комиссии за
+    /// получение. Аккаунт старого формата расширяется за счёт владельца
+    /// 0. `[signer, writable]` Владелец аккаунта
+    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
+    /// 2. `[]` System program
+    SetSweepToTreasury { vault_id: u16, enabled: bool },
+}
+
+// Define the roles an admin can delegate; the admin itself holds every role
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Pauser,
+    Distributor,
+}
+
+// Define the data structure for user account
+#[derive(BorshSerialize, BorshDeserialize, Debug)]
+pub struct UserAccount {
+    pub owner: Pubkey,
+    pub balance: u64,
+    // Linear vesting schedule; vest_total of 0 means nothing is vesting
+    pub vest_start: i64,
+    pub vest_end: i64,
+    pub vest_total: u64,
+    // Monotonic totals; accounts created before these existed track them once grown to LEN
+    pub lifetime_deposited: u64,
+    pub lifetime_withdrawn: u64,
+    // Deposits credited since deposit_window_start, for the vault's daily limit
+    pub deposit_window_start: i64,
+    pub deposited_in_window: u64,
+    // SPL token mint the balance is held in; Pubkey::default() means native SOL,
+    // which is also how accounts created before token support read
+    pub mint: Pubkey,
+    // Owner proposed by TransferOwnership; Pubkey::default() means none is pending
+    pub pending_owner: Pubkey,
+    // Key allowed to withdraw up to delegated_amount more; Pubkey::default() means none
+    pub delegate: Pubkey,
+    pub delegated_amount: u64,
+    // No withdrawals are allowed before this unix timestamp; 0 means no lockup
+    pub unlock_ts: i64,
+    // Amounts deposited under a vault's lockup tiers, each locked on its own
+    pub tranches: [LockupTranche; MAX_LOCKUP_TRANCHES],
+    // The vault's interest index when interest was last paid; 0 means never
+    pub interest_index: u128,
+    // Amount RequestWithdraw asked for and when; a pending_withdrawal of 0 means none
+    pub pending_withdrawal: u64,
+    pub withdraw_requested_at: i64,
+    // Withdrawals debited in withdraw_epoch, for the vault's epoch withdrawal limit
+    pub withdraw_epoch: u64,
+    pub withdrawn_in_epoch: u64,
+    // Whether the owner agreed to SweepDust paying the fee vault instead of them
+    pub sweep_to_treasury: bool,
+    // Set by SweepDust so CloseAccount needn't wait out the deposit window; a deposit clears it
+    pub closeable: bool,
+}
+
+// An amount deposited under a lockup tier, locked until unlock_ts and earning
+// the tier's multiplier meanwhile; an amount of 0 marks a free slot
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LockupTranche {
+    pub amount: u64,
+    pub unlock_ts: i64,
+    pub multiplier_bps: u16,
+}
+
+impl LockupTranche {
+    pub const LEN: usize = 8 + 8 + 2;
+}
+
+// One of a vault's lockup tiers; a duration of 0 means the tier is disabled
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LockupTier {
+    // Seconds a deposit under the tier stays locked
+    pub duration: i64,
+    // Reward multiplier in basis points of MULTIPLIER_BPS_ONE
+    pub multiplier_bps: u16,
+}
+
+impl LockupTier {
+    pub const LEN: usize = 8 + 2;
+}
+
+// One of a vault's withdraw fee tiers; a min_balance of 0 means the tier is disabled
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FeeTier {
+    // Balance, in lamports, from which the tier's fee applies instead
+    pub min_balance: u64,
+    // Share of the withdrawal kept as a fee, in basis points
+    pub fee_bps: u16,
+}
+
+impl FeeTier {
+    pub const LEN: usize = 8 + 2;
+}
+
+// Upper bound on the size a user account can be grown to
+pub const MAX_USER_ACCOUNT_SIZE: usize = 1024;
+
+// Upper bound on the credits in one BatchCredit, keeping it within transaction size
+pub const MAX_BATCH_CREDITS: usize = 10;
+
+// Length of the window the daily deposit limit applies to
+pub const DEPOSIT_WINDOW_SECONDS: i64 = 24 * 60 * 60;
+
+// Lockup tiers a vault config holds, e.g. 30, 90 and 180 days
+pub const LOCKUP_TIER_COUNT: usize = 3;
+
+// Withdraw fee tiers a vault config holds, e.g. a lower fee above 100 SOL
+pub const FEE_TIER_COUNT: usize = 3;
+
+// Lockup tranches a user account can have running at once
+pub const MAX_LOCKUP_TRANCHES: usize = 4;
+
+// Longest memo, in bytes, DepositWithMemo and WithdrawWithMemo accept
+pub const MAX_MEMO_LEN: usize = 128;
+
+// Multiplier, in basis points, that leaves an amount unchanged
+pub const MULTIPLIER_BPS_ONE: u16 = 10_000;
+
+// Highest withdraw fee, in basis points, SetParams and SetFeeTier accept: 10%
+pub const MAX_WITHDRAW_FEE_BPS: u16 = 1_000;
+
+// Longest withdrawal cooldown SetWithdrawCooldown accepts, so funds can't be
+// held back indefinitely: 30 days
+pub const MAX_WITHDRAW_COOLDOWN: i64 = 30 * 24 * 60 * 60;
+
+// Interest index a vault starts from; the index only grows from here
+pub const INTEREST_INDEX_ONE: u128 = 1_000_000_000_000;
+
+// Length of the year interest rates are quoted over
+pub const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+
+impl UserAccount {
+    pub const LEN: usize = 32 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 32 + 32 + 32 + 8 + 8
+        + LockupTranche::LEN * MAX_LOCKUP_TRANCHES
+        + 16
+        + 8
+        + 8
+        + 8
+        + 8
+        + 1
+        + 1;
+
+    // Size of accounts created before lifetime totals existed
+    pub const LEGACY_LEN: usize = 32 + 8 + 8 + 8 + 8;
+
+    // Deserialize from account data, ignoring any zeroed space reserved by GrowAccount;
+    // legacy accounts read as having zero lifetime totals
+    pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        let mut padded = [0u8; Self::LEN];
+        let len = data.len().min(Self::LEN);
+        padded[..len].copy_from_slice(&data[..len]);
+        Ok(Self::deserialize(&mut &padded[..])?)
+    }
+
+    // Load a user account the program created. An uninitialized PDA would unpack
+    // as all zeroes, so anything not owned by the program, shorter than a legacy
+    // account or without a recorded owner is refused
+    pub fn load(
+        user_data_account: &AccountInfo,
+        program_id: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        if user_data_account.owner != program_id
+            || user_data_account.data_len() < Self::LEGACY_LEN
+        {
+            return Err(DepositError::AccountNotInitialized.into());
+        }
+        let user_data = Self::unpack(&user_data_account.data.borrow())?;
+        if user_data.owner == Pubkey::default() {
+            return Err(DepositError::AccountNotInitialized.into());
+        }
+        Ok(user_data)
+    }
+
+    // Write back to account data; a legacy account keeps only the fields it has room for
+    pub fn save(&self, user_data_account: &AccountInfo) -> ProgramResult {
+        let data = self.try_to_vec()?;
+        let mut account_data = user_data_account.data.borrow_mut();
+        let len = account_data.len().min(data.len());
+        account_data[..len].copy_from_slice(&data[..len]);
+        Ok(())
+    }
+
+    // Part of the vesting schedule that has not unlocked yet at `now`
+    pub fn locked_amount(&self, now: i64) -> u64 {
+        if self.vest_total == 0 || now >= self.vest_end {
+            return 0;
+        }
+        if now <= self.vest_start {
+            return self.vest_total;
+        }
+
+        // Widen before multiplying so the proportion can't overflow
+        let elapsed = (now as i128 - self.vest_start as i128) as u128;
+        let duration = (self.vest_end as i128 - self.vest_start as i128) as u128;
+        let vested = self.vest_total as u128 * elapsed / duration;
+        self.vest_total.saturating_sub(vested as u64)
+    }
+
+    // Whether a lockup still blocks every withdrawal at `now`
+    pub fn is_locked(&self, now: i64) -> bool {
+        now < self.unlock_ts
+    }
+
+    // Part of the balance held by tranches that are still locked at `now`
+    pub fn tranche_locked(&self, now: i64) -> u64 {
+        self.tranches
+            .iter()
+            .filter(|tranche| now < tranche.unlock_ts)
+            .fold(0, |locked, tranche| locked.saturating_add(tranche.amount))
+    }
+
+    // Balance with the bonus of every tranche still locked at `now` added, the
+    // weight rewards are shared by
+    pub fn weighted_balance(&self, now: i64) -> u64 {
+        let bonus: u128 = self
+            .tranches
+            .iter()
+            .filter(|tranche| now < tranche.unlock_ts)
+            .map(|tranche| {
+                let bonus_bps = tranche.multiplier_bps.saturating_sub(MULTIPLIER_BPS_ONE);
+                tranche.amount as u128 * bonus_bps as u128 / MULTIPLIER_BPS_ONE as u128
+            })
+            .sum();
+        (self.balance as u128 + bonus).min(u64::MAX as u128) as u64
+    }
+
+    // Balance that can be withdrawn at `now`
+    pub fn withdrawable(&self, now: i64) -> u64 {
+        if self.is_locked(now) {
+            return 0;
+        }
+        self.balance
+            .saturating_sub(self.locked_amount(now))
+            .saturating_sub(self.tranche_locked(now))
+    }
+
+    // Interest earned on the weighted balance since the account last settled
+    // at the vault's `index`; an account that never settled has earned none
+    pub fn accrued_interest(&self, index: u128, now: i64) -> u64 {
+        if self.interest_index == 0 || index <= self.interest_index {
+            return 0;
+        }
+        let growth = index - self.interest_index;
+        let interest = self.weighted_balance(now) as u128 * growth / self.interest_index;
+        interest.min(u64::MAX as u128) as u64
+    }
+
+    // The pending withdrawal, once `cooldown` seconds have passed since it was requested
+    pub fn claimable_withdrawal(&self, cooldown: i64, now: i64) -> Result<u64, ProgramError> {
+        if self.pending_withdrawal == 0 {
+            return Err(DepositError::NoPendingWithdrawal.into());
+        }
+        if now < self.withdraw_requested_at.saturating_add(cooldown) {
+            return Err(DepositError::CooldownNotElapsed.into());
+        }
+        Ok(self.pending_withdrawal)
+    }
+}
+
+// Define the per-vault configuration
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+pub struct VaultConfig {
+    pub admin: Pubkey,
+    pub deposits_paused: bool,
+    pub withdrawals_paused: bool,
+    // Delegated authorities; Pubkey::default() means the role is unassigned
+    pub pauser: Pubkey,
+    pub distributor: Pubkey,
+    // Lamports each account may deposit per DEPOSIT_WINDOW_SECONDS; 0 means unlimited
+    pub daily_deposit_limit: u64,
+    // Bump of the vault PDA; 0 means not recorded yet, for configs created before it was
+    pub vault_bump: u8,
+    // Lockups DepositTiered can choose from, indexed by tier
+    pub lockup_tiers: [LockupTier; LOCKUP_TIER_COUNT],
+    // Annual interest rate in basis points, accrued into interest_index
+    pub interest_rate_bps: u16,
+    // Growth of one lamport since interest was enabled, in INTEREST_INDEX_ONE
+    // units, as of interest_updated_at; 0 means interest was never enabled
+    pub interest_index: u128,
+    pub interest_updated_at: i64,
+    // Share of every SOL withdrawal, in basis points, kept in the fee vault
+    pub withdraw_fee_bps: u16,
+    // Smallest SOL deposit accepted, in lamports; 0 means no minimum
+    pub min_deposit: u64,
+    // Fees replacing withdraw_fee_bps for balances of at least the tier's minimum
+    pub fee_tiers: [FeeTier; FEE_TIER_COUNT],
+    // Largest balance, in lamports, a deposit may bring an account to; 0 means unlimited
+    pub max_balance_per_user: u64,
+    // Largest total value locked deposits may bring the vault to, as tracked in
+    // its stats account; 0 means unlimited and untracked
+    pub tvl_cap: u64,
+    // Smallest non-zero balance a withdrawal may leave, in lamports; 0 means no minimum
+    pub min_residual_balance: u64,
+    // Seconds between RequestWithdraw and ClaimWithdraw; while non-zero SOL leaves
+    // the vault only that way. 0 means withdrawals are immediate
+    pub withdraw_cooldown: i64,
+    // Lamports each account may withdraw per cluster epoch; 0 means unlimited
+    pub epoch_withdraw_limit: u64,
+    // SweepDust only takes balances below this many lamports; 0 means no sweeping
+    pub dust_threshold: u64,
+}
+
+impl VaultConfig {
+    pub const LEN: usize = 32
+        + 1
+        + 1
+        + 32
+        + 32
+        + 8
+        + 1
+        + LockupTier::LEN * LOCKUP_TIER_COUNT
+        + 2
+        + 16
+        + 8
+        + 2
+        + 8
+        + FeeTier::LEN * FEE_TIER_COUNT
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8
+        + 8;
+
+    // Size of configs created before roles existed
+    pub const LEGACY_LEN: usize = 32 + 1 + 1;
+
+    // Load the vault's config, treating a not yet initialized config as all defaults
+    pub fn load(
+        config_account: &AccountInfo,
+        vault_id: u16,
+        program_id: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let (expected_config_account, _) = find_config_address(vault_id, program_id);
+        if expected_config_account != *config_account.key {
+            return Err(DepositError::WrongConfigPda.into());
+        }
+
+        if config_account.data_is_empty() {
+            return Ok(Self::default());
+        }
+
+        if config_account.owner != program_id {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        // Configs created before roles existed are shorter; the missing roles
+        // read as unassigned
+        let data = config_account.data.borrow();
+        let mut padded = [0u8; Self::LEN];
+        let len = data.len().min(Self::LEN);
+        padded[..len].copy_from_slice(&data[..len]);
+        Ok(Self::deserialize(&mut &padded[..])?)
+    }
+
+    // Load the config a deposit is checked against. Until the admin has created
+    // it there is nothing to pause or limit deposits with, so none are taken
+    pub fn load_initialized(
+        config_account: &AccountInfo,
+        vault_id: u16,
+        program_id: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let config = Self::load(config_account, vault_id, program_id)?;
+        if config_account.data_is_empty() {
+            return Err(DepositError::AccountNotInitialized.into());
+        }
+        Ok(config)
+    }
+
+    // Write the config back; a legacy config keeps only the fields that fit,
+    // and it can't hold roles until GrantRole grows it
+    pub fn save(&self, config_account: &AccountInfo) -> ProgramResult {
+        let data = self.try_to_vec()?;
+        let mut account_data = config_account.data.borrow_mut();
+        let len = account_data.len().min(data.len());
+        account_data[..len].copy_from_slice(&data[..len]);
+        Ok(())
+    }
+
+    // The vault PDA, from the bump recorded when the config was created so the
+    // bump search is skipped; configs that never recorded one have it as 0
+    pub fn vault_address(&self, vault_id: u16, program_id: &Pubkey) -> (Pubkey, u8) {
+        if self.vault_bump != 0 {
+            let seeds: &[&[u8]] = &[b"vault", &vault_id.to_le_bytes(), &[self.vault_bump]];
+            if let Ok(vault_account) = Pubkey::create_program_address(seeds, program_id) {
+                return (vault_account, self.vault_bump);
+            }
+        }
+        find_vault_address(vault_id, program_id)
+    }
+
+    pub fn authority(&self, role: Role) -> Pubkey {
+        match role {
+            Role::Pauser => self.pauser,
+            Role::Distributor => self.distributor,
+        }
+    }
+
+    // Whether `key` may act in `role`
+    pub fn is_authorized(&self, role: Role, key: &Pubkey) -> bool {
+        let authority = self.authority(role);
+        *key == self.admin || (authority != Pubkey::default() && *key == authority)
+    }
+
+    // The lockup tier a deposit asks for, if the vault offers it
+    pub fn lockup_tier(&self, tier: u8) -> Result<LockupTier, ProgramError> {
+        match self.lockup_tiers.get(tier as usize) {
+            Some(lockup_tier) if lockup_tier.duration > 0 => Ok(*lockup_tier),
+            _ => Err(DepositError::UnknownLockupTier.into()),
+        }
+    }
+
+    // The interest index accrued at the current rate up to `now`, without
+    // compounding since interest_updated_at
+    pub fn interest_index_at(&self, now: i64) -> Result<u128, ProgramError> {
+        if self.interest_index == 0 || now <= self.interest_updated_at {
+            return Ok(self.interest_index);
+        }
+
+        let elapsed = (now as i128 - self.interest_updated_at as i128) as u128;
+        let growth = self
+            .interest_index
+            .checked_mul(self.interest_rate_bps as u128)
+            .and_then(|growth| growth.checked_mul(elapsed))
+            .ok_or(DepositError::ArithmeticOverflow)?
+            / (MULTIPLIER_BPS_ONE as u128 * SECONDS_PER_YEAR as u128);
+        Ok(self
+            .interest_index
+            .checked_add(growth)
+            .ok_or(DepositError::ArithmeticOverflow)?)
+    }
+
+    // Change the interest rate at `now`; interest up to now accrues at the old
+    // rate, and the first rate set enables interest
+    pub fn set_interest_rate(&mut self, rate_bps: u16, now: i64) -> ProgramResult {
+        self.interest_index = match self.interest_index_at(now)? {
+            0 => INTEREST_INDEX_ONE,
+            index => index,
+        };
+        self.interest_updated_at = now;
+        self.interest_rate_bps = rate_bps;
+        Ok(())
+    }
+
+    // Refuse to leave a dust balance; withdrawing everything is always allowed
+    pub fn check_residual_balance(&self, balance: u64) -> ProgramResult {
+        if balance > 0 && balance < self.min_residual_balance {
+            return Err(DepositError::ResidualBelowMinimum.into());
+        }
+        Ok(())
+    }
+
+    // Refuse withdrawals while they are paused, and any but the claim of a
+    // request while the vault has a cooldown
+    pub fn check_withdrawal(&self, claim: bool) -> ProgramResult {
+        if self.withdrawals_paused {
+            return Err(DepositError::WithdrawalsPaused.into());
+        }
+        if self.withdraw_cooldown > 0 && !claim {
+            return Err(DepositError::WithdrawalRequestRequired.into());
+        }
+        Ok(())
+    }
+
+    // Account state after a withdrawal of `amount`, already debited from
+    // `user_data`, passes the residual minimum and counts against the epoch limit
+    pub fn apply_withdraw_limits(
+        &self,
+        user_data: UserAccount,
+        amount: u64,
+        epoch: u64,
+    ) -> Result<UserAccount, ProgramError> {
+        self.check_residual_balance(user_data.balance)?;
+        if self.epoch_withdraw_limit == 0 {
+            return Ok(user_data);
+        }
+        apply_withdraw_limit(&user_data, amount, self.epoch_withdraw_limit, epoch)
+    }
+
+    // The most `user_data` can withdraw at `now` in `epoch` under every check a
+    // withdrawal makes: the pause, a cooldown's request, vesting and lockups,
+    // the epoch limit and the residual minimum
+    pub fn max_withdrawal(&self, user_data: &UserAccount, now: i64, epoch: u64) -> u64 {
+        if self.withdrawals_paused {
+            return 0;
+        }
+        let mut max = user_data.withdrawable(now);
+        if self.withdraw_cooldown > 0 {
+            let claimable = user_data.claimable_withdrawal(self.withdraw_cooldown, now);
+            max = max.min(claimable.unwrap_or(0));
+        }
+        if self.epoch_withdraw_limit > 0 {
+            let withdrawn = if user_data.withdraw_epoch == epoch {
+                user_data.withdrawn_in_epoch
+            } else {
+                0
+            };
+            max = max.min(self.epoch_withdraw_limit.saturating_sub(withdrawn));
+        }
+        // Only withdrawing everything may leave less than the residual minimum
+        if max < user_data.balance {
+            max = max.min(user_data.balance.saturating_sub(self.min_residual_balance));
+        }
+        max
+    }
+
+    // The fee rate for an account holding `balance`: that of the enabled tier
+    // with the highest minimum the balance reaches, or withdraw_fee_bps if none
+    pub fn withdraw_fee_bps_for(&self, balance: u64) -> u16 {
+        self.fee_tiers
+            .iter()
+            .filter(|fee_tier| fee_tier.min_balance > 0 && balance >= fee_tier.min_balance)
+            .max_by_key(|fee_tier| fee_tier.min_balance)
+            .map_or(self.withdraw_fee_bps, |fee_tier| fee_tier.fee_bps)
+    }
+
+    // Whether any withdrawal can be charged a fee, and so needs the fee vault
+    pub fn charges_withdraw_fee(&self) -> bool {
+        self.withdraw_fee_bps > 0
+            || self
+                .fee_tiers
+                .iter()
+                .any(|fee_tier| fee_tier.min_balance > 0 && fee_tier.fee_bps > 0)
+    }
+
+    // The fee withheld from a withdrawal of `amount` out of `balance`, rounded down
+    pub fn withdraw_fee(&self, amount: u64, balance: u64) -> u64 {
+        let fee_bps = self.withdraw_fee_bps_for(balance);
+        (amount as u128 * fee_bps as u128 / MULTIPLIER_BPS_ONE as u128) as u64
+    }
+}
+
+// Define the per-vault stats, kept up to date only while the vault has a TVL cap
+#[derive(BorshSerialize, BorshDeserialize, Debug, Default)]
+pub struct VaultStats {
+    // Lamports credited by deposits less those debited by withdrawals
+    pub total_value_locked: u64,
+}
+
+impl VaultStats {
+    pub const LEN: usize = 8;
+
+    // Load the vault's stats, which SetTvlCap created along with the cap
+    pub fn load(
+        stats_account: &AccountInfo,
+        vault_id: u16,
+        program_id: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let (expected_stats_account, _) = find_stats_address(vault_id, program_id);
+        if expected_stats_account != *stats_account.key {
+            return Err(DepositError::WrongStatsPda.into());
+        }
+        if stats_account.owner != program_id {
+            return Err(ProgramError::IllegalOwner);
+        }
+        Ok(Self::deserialize(&mut &stats_account.data.borrow()[..])?)
+    }
+
+    pub fn save(&self, stats_account: &AccountInfo) -> ProgramResult {
+        self.serialize(&mut &mut stats_account.data.borrow_mut()[..])?;
+        Ok(())
+    }
+}
+
+// Define the events SOL instructions log as the `Program data:` of the event's name and
+// its borsh encoding; the client decodes them from a transaction's logs
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct InitEvent {
+    pub vault_id: u16,
+    pub owner: Pubkey,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct DepositEvent {
+    pub vault_id: u16,
+    // Whose balance was credited, which under DepositFor isn't the payer
+    pub owner: Pubkey,
+    pub payer: Pubkey,
+    // Lamports credited, less any the vault kept as its rent reserve
+    pub amount: u64,
+    pub balance: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawEvent {
+    pub vault_id: u16,
+    pub owner: Pubkey,
+    // The owner, or the account WithdrawTo paid out to
+    pub destination: Pubkey,
+    // Lamports debited from the balance, `fee` of which went to the fee vault
+    pub amount: u64,
+    pub fee: u64,
+    pub balance: u64,
+}
+
+// Any other change to a balance: token and wrapped SOL transfers, interest,
+// credits and swept dust
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct BalanceEvent {
+    pub vault_id: u16,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub balance: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FeesEvent {
+    pub vault_id: u16,
+    // Any address the admin names can be paid
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MemoEvent {
+    pub vault_id: u16,
+    pub signer: Pubkey,
+    pub memo: String,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ParamEvent {
+    pub vault_id: u16,
+    pub name: String,
+    pub old: u64,
+    pub new: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AdminEvent {
+    pub vault_id: u16,
+    pub old: Pubkey,
+    pub new: Pubkey,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipEvent {
+    pub vault_id: u16,
+    pub old_owner: Pubkey,
+    pub new_owner: Pubkey,
+    // The balance that moved with the account
+    pub balance: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Init(InitEvent),
+    Deposit(DepositEvent),
+    Withdraw(WithdrawEvent),
+    DepositToken(BalanceEvent),
+    WithdrawToken(BalanceEvent),
+    DepositWrapped(BalanceEvent),
+    WithdrawUnwrapped(BalanceEvent),
+    Interest(BalanceEvent),
+    Credit(BalanceEvent),
+    SweepDust(BalanceEvent),
+    Ownership(OwnershipEvent),
+    Fees(FeesEvent),
+    Memo(MemoEvent),
+    Param(ParamEvent),
+    Admin(AdminEvent),
+}
+
+impl Event {
+    pub fn name(&self) -> &'static [u8] {
+        match self {
+            Event::Init(_) => b"init",
+            Event::Deposit(_) => b"deposit",
+            Event::Withdraw(_) => b"withdraw",
+            Event::DepositToken(_) => b"deposit-token",
+            Event::WithdrawToken(_) => b"withdraw-token",
+            Event::DepositWrapped(_) => b"deposit-wrapped",
+            Event::WithdrawUnwrapped(_) => b"withdraw-unwrapped",
+            Event::Interest(_) => b"interest",
+            Event::Credit(_) => b"credit",
+            Event::SweepDust(_) => b"sweep-dust",
+            Event::Ownership(_) => b"ownership",
+            Event::Fees(_) => b"fees",
+            Event::Memo(_) => b"memo",
+            Event::Param(_) => b"param",
+            Event::Admin(_) => b"admin",
+        }
+    }
+
+    pub fn emit(&self) -> ProgramResult {
+        let data = match self {
+            Event::Init(event) => event.try_to_vec()?,
+            Event::Deposit(event) => event.try_to_vec()?,
+            Event::Withdraw(event) => event.try_to_vec()?,
+            Event::DepositToken(event)
+            | Event::WithdrawToken(event)
+            | Event::DepositWrapped(event)
+            | Event::WithdrawUnwrapped(event)
+            | Event::Interest(event)
+            | Event::Credit(event)
+            | Event::SweepDust(event) => event.try_to_vec()?,
+            Event::Ownership(event) => event.try_to_vec()?,
+            Event::Fees(event) => event.try_to_vec()?,
+            Event::Memo(event) => event.try_to_vec()?,
+            Event::Param(event) => event.try_to_vec()?,
+            Event::Admin(event) => event.try_to_vec()?,
+        };
+        sol_log_data(&[self.name(), &data]);
+        Ok(())
+    }
+}
+
+// Define program-specific errors. Codes are returned as ProgramError::Custom
+// and decoded by the client, so variants are only appended, never reordered
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DepositError {
+    #[error("Deposits are paused for this vault")]
+    DepositsPaused = 0,
+    #[error("Withdrawals are paused for this vault")]
+    WithdrawalsPaused = 1,
+    #[error("A vesting schedule is still in progress")]
+    VestingInProgress = 2,
+    #[error("Amount exceeds the vested balance")]
+    AmountStillVesting = 3,
+    #[error("Vesting schedule must end after it starts")]
+    InvalidVestingSchedule = 4,
+    #[error("First deposit does not cover the vault's rent-exempt reserve")]
+    DepositBelowVaultRent = 5,
+    #[error("Batch has more credits than allowed")]
+    BatchTooLarge = 6,
+    #[error("Rewards account cannot cover the batch")]
+    InsufficientRewards = 7,
+    #[error("Balance is not below the dust threshold")]
+    DustAboveThreshold = 8,
+    #[error("Deposit exceeds the daily deposit limit")]
+    DepositLimitExceeded = 9,
+    #[error("Account still holds a balance")]
+    AccountNotEmpty = 10,
+    #[error("Deposits in the current daily window keep the account open")]
+    DepositWindowOpen = 11,
+    #[error("Amount exceeds the delegate's remaining allowance")]
+    DelegateAllowanceExceeded = 12,
+    #[error("User data account is not the expected PDA")]
+    WrongUserPda = 13,
+    #[error("Vault account is not the expected PDA")]
+    WrongVaultPda = 14,
+    #[error("Config account is not the expected PDA")]
+    WrongConfigPda = 15,
+    #[error("Rewards account is not the expected PDA")]
+    WrongRewardsPda = 16,
+    #[error("Token account is not the one the vault uses")]
+    WrongTokenAccount = 17,
+    #[error("Mint does not match the account")]
+    WrongMint = 18,
+    #[error("Account does not belong to the given owner")]
+    WrongOwner = 19,
+    #[error("Signer is not authorized for this account")]
+    Unauthorized = 20,
+    #[error("Account is not initialized")]
+    AccountNotInitialized = 21,
+    #[error("Amount exceeds the recorded balance")]
+    InsufficientBalance = 22,
+    #[error("Arithmetic overflow")]
+    ArithmeticOverflow = 23,
+    #[error("The all-zero key cannot own an account")]
+    ZeroOwner = 24,
+    #[error("Withdrawals are locked until the unlock time")]
+    FundsLocked = 25,
+    #[error("Unlock time must be in the future")]
+    InvalidUnlockTime = 26,
+    #[error("Lockup tier is not offered by this vault")]
+    UnknownLockupTier = 27,
+    #[error("Lockup tier needs a positive duration and a multiplier of at least 1x")]
+    InvalidLockupTier = 28,
+    #[error("Every lockup tranche of the account is still locked")]
+    TooManyTranches = 29,
+    #[error("Interest is not enabled for this vault")]
+    InterestNotEnabled = 30,
+    #[error("Deposit is below the vault's minimum")]
+    DepositBelowMinimum = 31,
+    #[error("Withdraw fee is above MAX_WITHDRAW_FEE_BPS")]
+    InvalidWithdrawFee = 32,
+    #[error("Fee vault account is not the expected PDA")]
+    WrongFeeVaultPda = 33,
+    #[error("The vault has no such fee tier")]
+    UnknownFeeTier = 34,
+    #[error("The deposit would take the balance above the vault's per-user maximum")]
+    MaxBalanceExceeded = 35,
+    #[error("The deposit would push the vault's total value locked above its cap")]
+    CapExceeded = 36,
+    #[error("Stats account is not the expected PDA")]
+    WrongStatsPda = 37,
+    #[error("The withdrawal would leave a balance below the vault's minimum")]
+    ResidualBelowMinimum = 38,
+    #[error("Withdrawals from this vault must be requested and claimed after its cooldown")]
+    WithdrawalRequestRequired = 39,
+    #[error("No withdrawal has been requested")]
+    NoPendingWithdrawal = 40,
+    #[error("The withdrawal cooldown has not elapsed yet")]
+    CooldownNotElapsed = 41,
+    #[error("Withdrawal cooldown must be between 0 and MAX_WITHDRAW_COOLDOWN")]
+    InvalidCooldown = 42,
+    #[error("Withdrawal exceeds the epoch withdrawal limit")]
+    WithdrawLimitExceeded = 43,
+    #[error("Memo is longer than MAX_MEMO_LEN bytes")]
+    MemoTooLong = 44,
+    #[error("ProgramData account is not this program's")]
+    WrongProgramData = 45,
+}
+
+impl From<DepositError> for ProgramError {
+    fn from(e: DepositError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+// Derive the PDA holding a user's data for the given vault
+pub fn find_user_account_address(
+    owner: &Pubkey,
+    vault_id: u16,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"user-account", owner.as_ref(), &vault_id.to_le_bytes()],
+        program_id,
+    )
+}
+
+// Derive the PDA holding a user's balance of one SPL token mint for the given vault
+pub fn find_token_user_account_address(
+    owner: &Pubkey,
+    vault_id: u16,
+    mint: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"user-account", owner.as_ref(), &vault_id.to_le_bytes(), mint.as_ref()],
+        program_id,
+    )
+}
+
+// Derive the PDA of a user account of either kind from the mint it records
+pub fn find_user_data_address(
+    owner: &Pubkey,
+    vault_id: u16,
+    mint: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    if *mint == Pubkey::default() {
+        find_user_account_address(owner, vault_id, program_id)
+    } else {
+        find_token_user_account_address(owner, vault_id, mint, program_id)
+    }
+}
+
+// Derive the token account holding the vault's tokens of `mint`: the vault PDA's ATA
+// under the mint's token program
+pub fn find_vault_token_address(
+    vault_id: u16,
+    mint: &Pubkey,
+    token_program_id: &Pubkey,
+    program_id: &Pubkey,
+) -> Pubkey {
+    let (vault_account, _) = find_vault_address(vault_id, program_id);
+    get_associated_token_address_with_program_id(&vault_account, mint, token_program_id)
+}
+
+// Derive the short-lived token account a WithdrawUnwrapped moves an owner's wSOL
+// through before closing it to them
+pub fn find_unwrap_address(owner: &Pubkey, vault_id: u16, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[b"unwrap", owner.as_ref(), &vault_id.to_le_bytes()],
+        program_id,
+    )
+}
+
+// Derive the PDA holding the lamports of the given vault
+pub fn find_vault_address(vault_id: u16, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"vault", &vault_id.to_le_bytes()], program_id)
+}
+
+// Derive the PDA holding the configuration of the given vault
+pub fn find_config_address(vault_id: u16, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config", &vault_id.to_le_bytes()], program_id)
+}
+
+// Derive the PDA holding the lamports reserved for reward credits
+pub fn find_rewards_address(vault_id: u16, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"rewards", &vault_id.to_le_bytes()], program_id)
+}
+
+// Derive the PDA withdraw fees accumulate in until the admin collects them
+pub fn find_fee_vault_address(vault_id: u16, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"fee-vault", &vault_id.to_le_bytes()], program_id)
+}
+
+// Derive the PDA holding the vault's stats
+pub fn find_stats_address(vault_id: u16, program_id: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"stats", &vault_id.to_le_bytes()], program_id)
+}
+
+// Program entrypoint
+entrypoint!(process_instruction);
+
+// Process instruction function
+pub fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    instruction_data: &[u8],
+) -> ProgramResult {
+    // try_from_slice fails unless every byte is consumed, so instructions with
+    // trailing garbage are rejected rather than partially decoded
+    let instruction = DepositInstruction::try_from_slice(instruction_data)?;
+
+    match instruction {
+        DepositInstruction::InitializeAccount { vault_id } => {
+            process_initialize_account(program_id, accounts, vault_id)
+        }
+        DepositInstruction::Deposit { vault_id, amount } => process_deposit(
+            program_id,
+            accounts,
+            vault_id,
+            amount,
+            DepositTerms::Unrestricted,
+            None,
+        ),
+        DepositInstruction::Withdraw { vault_id, amount } => process_withdraw(
+            program_id,
+            accounts,
+            vault_id,
+            WithdrawAmount::Exact(amount),
+            Payout::Owner,
+        ),
+        DepositInstruction::GrowAccount { vault_id, new_size } => {
+            process_grow_account(program_id, accounts, vault_id, new_size as usize)
+        }
+        DepositInstruction::InitializeConfig { vault_id } => {
+            process_initialize_config(program_id, accounts, vault_id)
+        }
+        DepositInstruction::SetPauseFlags {
+            vault_id,
+            deposits,
+            withdrawals,
+        } => process_set_pause_flags(program_id, accounts, vault_id, deposits, withdrawals),
+        DepositInstruction::DepositVested {
+            vault_id,
+            amount,
+            vest_start,
+            vest_end,
+        } => process_deposit(
+            program_id,
+            accounts,
+            vault_id,
+            amount,
+            DepositTerms::Vested(vest_start, vest_end),
+            None,
+        ),
+        DepositInstruction::BatchCredit { vault_id, credits } => {
+            process_batch_credit(program_id, accounts, vault_id, &credits)
+        }
+        DepositInstruction::GrantRole {
+            vault_id,
+            role,
+            authority,
+        } => process_set_role(program_id, accounts, vault_id, role, authority),
+        DepositInstruction::RevokeRole { vault_id, role } => {
+            process_set_role(program_id, accounts, vault_id, role, Pubkey::default())
+        }
+        DepositInstruction::SweepDust { vault_id, threshold } => {
+            process_sweep_dust(program_id, accounts, vault_id, threshold)
+        }
+        DepositInstruction::SetDepositLimit { vault_id, daily_limit } => {
+            process_set_deposit_limit(program_id, accounts, vault_id, daily_limit)
+        }
+        DepositInstruction::GetWithdrawable { vault_id } => {
+            process_get_withdrawable(program_id, accounts, vault_id)
+        }
+        DepositInstruction::RenounceAdmin { vault_id } => {
+            process_renounce_admin(program_id, accounts, vault_id)
+        }
+        DepositInstruction::WithdrawToRemaining {
+            vault_id,
+            target_balance,
+        } => process_withdraw(
+            program_id,
+            accounts,
+            vault_id,
+            WithdrawAmount::LeaveRemaining(target_balance),
+            Payout::Owner,
+        ),
+        DepositInstruction::InitializeTokenAccount { vault_id } => {
+            process_initialize_token_account(program_id, accounts, vault_id)
+        }
+        DepositInstruction::DepositToken { vault_id, amount } => {
+            process_deposit_token(program_id, accounts, vault_id, amount)
+        }
+        DepositInstruction::WithdrawToken { vault_id, amount } => {
+            process_withdraw_token(program_id, accounts, vault_id, amount)
+        }
+        DepositInstruction::DepositWrapped { vault_id, amount } => {
+            process_deposit_wrapped(program_id, accounts, vault_id, amount)
+        }
+        DepositInstruction::WithdrawUnwrapped { vault_id, amount } => {
+            process_withdraw_unwrapped(program_id, accounts, vault_id, amount)
+        }
+        DepositInstruction::InitializeVault { vault_id } => {
+            process_initialize_vault(program_id, accounts, vault_id)
+        }
+        DepositInstruction::CloseAccount { vault_id } => {
+            process_close_account(program_id, accounts, vault_id)
+        }
+        DepositInstruction::WithdrawAll { vault_id } => process_withdraw(
+            program_id,
+            accounts,
+            vault_id,
+            WithdrawAmount::LeaveRemaining(0),
+            Payout::Owner,
+        ),
+        DepositInstruction::TransferOwnership { vault_id, new_owner } => {
+            process_transfer_ownership(program_id, accounts, vault_id, new_owner)
+        }
+        DepositInstruction::AcceptOwnership { vault_id } => {
+            process_accept_ownership(program_id, accounts, vault_id)
+        }
+        DepositInstruction::SetDelegate { vault_id, delegate, cap } => {
+            process_set_delegate(program_id, accounts, vault_id, delegate, cap)
+        }
+        DepositInstruction::RevokeDelegate { vault_id } => {
+            process_set_delegate(program_id, accounts, vault_id, Pubkey::default(), 0)
+        }
+        DepositInstruction::DelegatedWithdraw { vault_id, amount } => {
+            process_delegated_withdraw(program_id, accounts, vault_id, amount)
+        }
+        DepositInstruction::DepositLocked {
+            vault_id,
+            amount,
+            unlock_ts,
+        } => process_deposit(
+            program_id,
+            accounts,
+            vault_id,
+            amount,
+            DepositTerms::LockedUntil(unlock_ts),
+            None,
+        ),
+        DepositInstruction::SetLockupTier {
+            vault_id,
+            tier,
+            duration,
+            multiplier_bps,
+        } => process_set_lockup_tier(
+            program_id,
+            accounts,
+            vault_id,
+            tier,
+            duration,
+            multiplier_bps,
+        ),
+        DepositInstruction::DepositTiered {
+            vault_id,
+            amount,
+            tier,
+        } => process_deposit(
+            program_id,
+            accounts,
+            vault_id,
+            amount,
+            DepositTerms::Tier(tier),
+            None,
+        ),
+        DepositInstruction::AccrueInterest { vault_id } => {
+            process_accrue_interest(program_id, accounts, vault_id)
+        }
+        DepositInstruction::SetInterestRate { vault_id, rate_bps } => {
+            process_set_interest_rate(program_id, accounts, vault_id, rate_bps)
+        }
+        DepositInstruction::SetParams {
+            vault_id,
+            interest_rate_bps,
+            withdraw_fee_bps,
+            min_deposit,
+        } => process_set_params(
+            program_id,
+            accounts,
+            vault_id,
+            interest_rate_bps,
+            withdraw_fee_bps,
+            min_deposit,
+        ),
+        DepositInstruction::CollectFees { vault_id } => {
+            process_collect_fees(program_id, accounts, vault_id)
+        }
+        DepositInstruction::SetFeeTier {
+            vault_id,
+            tier,
+            min_balance,
+            fee_bps,
+        } => process_set_fee_tier(program_id, accounts, vault_id, tier, min_balance, fee_bps),
+        DepositInstruction::SetMaxBalancePerUser { vault_id, max_balance } => {
+            process_set_max_balance_per_user(program_id, accounts, vault_id, max_balance)
+        }
+        DepositInstruction::SetTvlCap { vault_id, cap } => {
+            process_set_tvl_cap(program_id, accounts, vault_id, cap)
+        }
+        DepositInstruction::SetMinResidualBalance { vault_id, min_balance } => {
+            process_set_min_residual_balance(program_id, accounts, vault_id, min_balance)
+        }
+        DepositInstruction::RequestWithdraw { vault_id, amount } => {
+            process_request_withdraw(program_id, accounts, vault_id, amount)
+        }
+        DepositInstruction::ClaimWithdraw { vault_id } => process_withdraw(
+            program_id,
+            accounts,
+            vault_id,
+            WithdrawAmount::Pending,
+            Payout::Owner,
+        ),
+        DepositInstruction::SetWithdrawCooldown { vault_id, cooldown } => {
+            process_set_withdraw_cooldown(program_id, accounts, vault_id, cooldown)
+        }
+        DepositInstruction::SetWithdrawLimit { vault_id, epoch_limit } => {
+            process_set_withdraw_limit(program_id, accounts, vault_id, epoch_limit)
+        }
+        DepositInstruction::DepositFor {
+            vault_id,
+            amount,
+            beneficiary,
+        } => process_deposit(
+            program_id,
+            accounts,
+            vault_id,
+            amount,
+            DepositTerms::Unrestricted,
+            Some(beneficiary),
+        ),
+        DepositInstruction::WithdrawTo { vault_id, amount } => process_withdraw(
+            program_id,
+            accounts,
+            vault_id,
+            WithdrawAmount::Exact(amount),
+            Payout::Destination,
+        ),
+        DepositInstruction::DepositWithMemo {
+            vault_id,
+            amount,
+            memo,
+        } => {
+            process_deposit(
+                program_id,
+                accounts,
+                vault_id,
+                amount,
+                DepositTerms::Unrestricted,
+                None,
+            )?;
+            process_memo(accounts, vault_id, &memo)
+        }
+        DepositInstruction::WithdrawWithMemo {
+            vault_id,
+            amount,
+            memo,
+        } => {
+            process_withdraw(
+                program_id,
+                accounts,
+                vault_id,
+                WithdrawAmount::Exact(amount),
+                Payout::Owner,
+            )?;
+            process_memo(accounts, vault_id, &memo)
+        }
+        DepositInstruction::SetDustThreshold { vault_id, threshold } => {
+            process_set_dust_threshold(program_id, accounts, vault_id, threshold)
+        }
+        DepositInstruction::SetSweepToTreasury { vault_id, enabled } => {
+            process_set_sweep_to_treasury(program_id, accounts, vault_id, enabled)
+        }
+    }
+}
+
+// Initialize account function
+fn process_initialize_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // An all-zero owner would be indistinguishable from zeroed account data
+    if *user_account.key == Pubkey::default() {
+        return Err(DepositError::ZeroOwner.into());
+    }
+
+    // Derive the PDA for user data account
+    let (expected_user_data_account, bump_seed) =
+        find_user_account_address(user_account.key, vault_id, program_id);
+
+    // Verify the user data account is the expected PDA
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    create_user_data_account(
+        program_id,
+        user_account,
+        user_data_account,
+        system_program,
+        &[
+            b"user-account",
+            user_account.key.as_ref(),
+            &vault_id.to_le_bytes(),
+            &[bump_seed],
+        ],
+        Pubkey::default(),
+    )?;
+
+    Event::Init(InitEvent { vault_id, owner: *user_account.key }).emit()
+}
+
+// Create a user data PDA at the signer's expense and record its owner and mint;
+// `seeds` are the PDA's signer seeds including the bump
+fn create_user_data_account<'a>(
+    program_id: &Pubkey,
+    user_account: &AccountInfo<'a>,
+    user_data_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    seeds: &[&[u8]],
+    mint: Pubkey,
+) -> ProgramResult {
+    // Calculate the size of the user data account
+    let user_data_size = UserAccount::LEN;
+
+    // Calculate the rent required for the account
+    let rent = Rent::get()?;
+    let rent_lamports = rent.minimum_balance(user_data_size);
+
+    // Create the user data account
+    invoke_signed(
+        &system_instruction::create_account(
+            user_account.key,
+            user_data_account.key,
+            rent_lamports,
+            user_data_size as u64,
+            program_id,
+        ),
+        &[
+            user_account.clone(),
+            user_data_account.clone(),
+            system_program.clone(),
+        ],
+        &[seeds],
+    )?;
+
+    // Initialize the user data account
+    let user_data = UserAccount {
+        owner: *user_account.key,
+        balance: 0,
+        vest_start: 0,
+        vest_end: 0,
+        vest_total: 0,
+        lifetime_deposited: 0,
+        lifetime_withdrawn: 0,
+        deposit_window_start: 0,
+        deposited_in_window: 0,
+        mint,
+        pending_owner: Pubkey::default(),
+        delegate: Pubkey::default(),
+        delegated_amount: 0,
+        unlock_ts: 0,
+        tranches: [LockupTranche::default(); MAX_LOCKUP_TRANCHES],
+        interest_index: 0,
+        pending_withdrawal: 0,
+        withdraw_requested_at: 0,
+        withdraw_epoch: 0,
+        withdrawn_in_epoch: 0,
+        sweep_to_treasury: false,
+        closeable: false,
+    };
+
+    // Serialize the data and store it in the account
+    user_data.save(user_data_account)
+}
+
+// Grow a user account created by an older version to the current size, at the owner's expense
+fn grow_user_data_account<'a>(
+    user_account: &AccountInfo<'a>,
+    user_data_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    if user_data_account.data_len() >= UserAccount::LEN {
+        return Ok(());
+    }
+
+    let rent = Rent::get()?;
+    let rent_shortfall = rent
+        .minimum_balance(UserAccount::LEN)
+        .saturating_sub(user_data_account.lamports());
+
+    if rent_shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(user_account.key, user_data_account.key, rent_shortfall),
+            &[
+                user_account.clone(),
+                user_data_account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+
+    user_data_account.realloc(UserAccount::LEN, true)
+}
+
+// Return a user account's lamports to `destination` and hand the emptied account
+// back to the system program, so it can't be revived with stale data in this transaction
+fn close_user_data_account(
+    user_data_account: &AccountInfo,
+    destination: &AccountInfo,
+) -> Result<u64, ProgramError> {
+    let rent_lamports = user_data_account.lamports();
+    **destination.lamports.borrow_mut() = destination
+        .lamports()
+        .checked_add(rent_lamports)
+        .ok_or(DepositError::ArithmeticOverflow)?;
+    **user_data_account.lamports.borrow_mut() = 0;
+    user_data_account.data.borrow_mut().fill(0);
+    user_data_account.realloc(0, false)?;
+    user_data_account.assign(&solana_program::system_program::id());
+    Ok(rent_lamports)
+}
+
+// What a deposit restricts about withdrawing the amount it credits
+enum DepositTerms {
+    Unrestricted,
+    // Unlocks linearly from the first timestamp to the second
+    Vested(i64, i64),
+    // Nothing in the account can be withdrawn before the timestamp
+    LockedUntil(i64),
+    // Locked in a tranche of its own for the duration of the vault's lockup tier
+    Tier(u8),
+}
+
+// Deposit function; a beneficiary is credited in place of the depositor, who
+// still pays for everything
+fn process_deposit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    amount: u64,
+    terms: DepositTerms,
+    beneficiary: Option<Pubkey>,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Derive the PDA for user data account of whoever is credited
+    let owner = beneficiary.unwrap_or(*user_account.key);
+    let (expected_user_data_account, _) = find_user_account_address(&owner, vault_id, program_id);
+
+    // Verify the user data account is the expected PDA
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    // Verify the vault account is correct
+    let (expected_vault_account, _) = find_vault_address(vault_id, program_id);
+
+    if expected_vault_account != *vault_account.key {
+        return Err(DepositError::WrongVaultPda.into());
+    }
+
+    // Refuse deposits while they are paused, and ones below the vault's minimum
+    let config = VaultConfig::load_initialized(config_account, vault_id, program_id)?;
+    if config.deposits_paused {
+        return Err(DepositError::DepositsPaused.into());
+    }
+    if amount < config.min_deposit {
+        return Err(DepositError::DepositBelowMinimum.into());
+    }
+
+    // Until the vault is rent-exempt, deposits top up its reserve first; that
+    // part stays in the vault and is not credited to the user
+    let rent = Rent::get()?;
+    let vault_rent_shortfall = rent
+        .minimum_balance(vault_account.data_len())
+        .saturating_sub(vault_account.lamports());
+    if amount < vault_rent_shortfall {
+        return Err(DepositError::DepositBelowVaultRent.into());
+    }
+    let credited = amount - vault_rent_shortfall;
+
+    // The rewards account pays interest, and precedes the stats account
+    let now = Clock::get()?.unix_timestamp;
+    // Only an account its owner opened can be credited, for them or anyone else
+    let mut user_data = UserAccount::load(user_data_account, program_id)?;
+    let interest_index = config.interest_index_at(now)?;
+    let rewards_account = if interest_index > 0 || config.tvl_cap > 0 {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+
+    // Pay the interest earned so far before the balance changes
+    let mut paid = 0;
+    if let Some(rewards_account) = rewards_account.filter(|_| interest_index > 0) {
+        let owed = user_data.accrued_interest(interest_index, now);
+        paid = pay_interest(
+            rewards_account,
+            vault_account,
+            system_program,
+            vault_id,
+            program_id,
+            owed,
+        )?;
+        user_data = apply_interest(&user_data, interest_index, paid)?;
+
+        // An older, shorter account couldn't record the index
+        grow_user_data_account(user_account, user_data_account, system_program)?;
+        emit_balance_change(Event::Interest, vault_id, &owner, paid, user_data.balance)?;
+    }
+
+    // Compute the new account state before moving any lamports
+    let vesting = match terms {
+        DepositTerms::Vested(vest_start, vest_end) => Some((vest_start, vest_end)),
+        _ => None,
+    };
+    user_data = apply_deposit(&user_data, credited, vesting, now)?;
+
+    // Interest paid just above counts too, as exposure the wallet already has
+    if config.max_balance_per_user > 0 && user_data.balance > config.max_balance_per_user {
+        return Err(DepositError::MaxBalanceExceeded.into());
+    }
+
+    if config.tvl_cap > 0 {
+        let stats_account = next_account_info(account_info_iter)?;
+        let added = credited.checked_add(paid).ok_or(DepositError::ArithmeticOverflow)?;
+        record_tvl_deposit(stats_account, vault_id, program_id, added, config.tvl_cap)?;
+    }
+
+    if config.daily_deposit_limit > 0 {
+        user_data = apply_deposit_limit(&user_data, credited, config.daily_deposit_limit, now)?;
+
+        // An older, shorter account couldn't record the window
+        grow_user_data_account(user_account, user_data_account, system_program)?;
+    }
+
+    match terms {
+        DepositTerms::LockedUntil(unlock_ts) => {
+            user_data = apply_lockup(&user_data, unlock_ts, now)?;
+        }
+        DepositTerms::Tier(tier) => {
+            user_data = apply_tranche(&user_data, &config.lockup_tier(tier)?, credited, now)?;
+        }
+        DepositTerms::Unrestricted | DepositTerms::Vested(..) => {}
+    }
+    if matches!(terms, DepositTerms::LockedUntil(_) | DepositTerms::Tier(_)) {
+        // An older, shorter account couldn't record the lockup
+        grow_user_data_account(user_account, user_data_account, system_program)?;
+    }
+
+    // Transfer SOL from user to vault
+    invoke(
+        &system_instruction::transfer(user_account.key, vault_account.key, amount),
+        &[
+            user_account.clone(),
+            vault_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
+
+    // Update user account balance
+    user_data.save(user_data_account)?;
+    set_balance_return_data(credited, user_data.balance);
+    Event::Deposit(DepositEvent {
+        vault_id,
+        owner,
+        payer: *user_account.key,
+        amount: credited,
+        balance: user_data.balance,
+    })
+    .emit()?;
+
+    if let Some((vest_start, vest_end)) = vesting {
+        msg!("Vesting {} lamports from {} to {}", credited, vest_start, vest_end);
+    }
+
+    match terms {
+        DepositTerms::LockedUntil(_) => msg!("Withdrawals locked until {}", user_data.unlock_ts),
+        DepositTerms::Tier(tier) => {
+            let lockup_tier = config.lockup_tier(tier)?;
+            msg!(
+                "Locked {} lamports in tier {} for {} seconds at {} bps",
+                credited,
+                tier,
+                lockup_tier.duration,
+                lockup_tier.multiplier_bps
+            );
+        }
+        DepositTerms::Unrestricted | DepositTerms::Vested(..) => {}
+    }
+
+    if vault_rent_shortfall > 0 {
+        msg!("Retained {} lamports as the vault's rent reserve", vault_rent_shortfall);
+    }
+    Ok(())
+}
+
+// Report a balance change two ways. A calling program reads the return data
+// (see `set_balance_return_data`). Off-chain consumers read the `Event`, which
+// survives however deeply the call was nested. A caller should prefer the
+// return data: logs can be truncated and aren't visible on chain. SOL deposits
+// and withdrawals log their own `Event` variants instead.
+fn emit_balance_change(
+    event: fn(BalanceEvent) -> Event,
+    vault_id: u16,
+    owner: &Pubkey,
+    amount: u64,
+    balance: u64,
+) -> ProgramResult {
+    set_balance_return_data(amount, balance);
+    event(BalanceEvent { vault_id, owner: *owner, amount, balance }).emit()
+}
+
+// Return the amount moved and the new balance as u64 little-endian; a calling
+// program must read them before its own next CPI replaces them
+fn set_balance_return_data(amount: u64, balance: u64) {
+    let mut data = [0u8; 16];
+    data[..8].copy_from_slice(&amount.to_le_bytes());
+    data[8..].copy_from_slice(&balance.to_le_bytes());
+    set_return_data(&data);
+}
+
+// Account state after crediting a deposit at `now`, optionally starting a
+// vesting schedule over the credited amount
+pub fn apply_deposit(
+    user_data: &UserAccount,
+    credited: u64,
+    vesting: Option<(i64, i64)>,
+    now: i64,
+) -> Result<UserAccount, ProgramError> {
+    let balance = user_data
+        .balance
+        .checked_add(credited)
+        .ok_or(DepositError::ArithmeticOverflow)?;
+    let lifetime_deposited = user_data
+        .lifetime_deposited
+        .checked_add(credited)
+        .ok_or(DepositError::ArithmeticOverflow)?;
+
+    // Only one schedule can run at a time
+    let (vest_start, vest_end, vest_total) = match vesting {
+        Some((vest_start, vest_end)) => {
+            if vest_end <= vest_start {
+                return Err(DepositError::InvalidVestingSchedule.into());
+            }
+            if user_data.locked_amount(now) > 0 {
+                return Err(DepositError::VestingInProgress.into());
+            }
+            (vest_start, vest_end, credited)
+        }
+        None => (user_data.vest_start, user_data.vest_end, user_data.vest_total),
+    };
+
+    Ok(UserAccount {
+        balance,
+        lifetime_deposited,
+        vest_start,
+        vest_end,
+        vest_total,
+        closeable: false,
+        ..*user_data
+    })
+}
+
+// Account state after counting a deposit against the daily limit at `now`; the
+// window restarts once DEPOSIT_WINDOW_SECONDS have passed since it opened
+pub fn apply_deposit_limit(
+    user_data: &UserAccount,
+    credited: u64,
+    daily_limit: u64,
+    now: i64,
+) -> Result<UserAccount, ProgramError> {
+    let window_expired =
+        now.saturating_sub(user_data.deposit_window_start) >= DEPOSIT_WINDOW_SECONDS;
+    let (deposit_window_start, already_deposited) = if window_expired {
+        (now, 0)
+    } else {
+        (user_data.deposit_window_start, user_data.deposited_in_window)
+    };
+
+    let deposited_in_window = already_deposited
+        .checked_add(credited)
+        .ok_or(DepositError::ArithmeticOverflow)?;
+    if deposited_in_window > daily_limit {
+        return Err(DepositError::DepositLimitExceeded.into());
+    }
+
+    Ok(UserAccount {
+        deposit_window_start,
+        deposited_in_window,
+        ..*user_data
+    })
+}
+
+// Account state after counting `amount` withdrawn in `epoch` towards the epoch limit,
+// starting the count afresh once the epoch has moved on
+pub fn apply_withdraw_limit(
+    user_data: &UserAccount,
+    amount: u64,
+    epoch_limit: u64,
+    epoch: u64,
+) -> Result<UserAccount, ProgramError> {
+    let already_withdrawn = if user_data.withdraw_epoch == epoch {
+        user_data.withdrawn_in_epoch
+    } else {
+        0
+    };
+
+    let withdrawn_in_epoch = already_withdrawn
+        .checked_add(amount)
+        .ok_or(DepositError::ArithmeticOverflow)?;
+    if withdrawn_in_epoch > epoch_limit {
+        return Err(DepositError::WithdrawLimitExceeded.into());
+    }
+
+    Ok(UserAccount {
+        withdraw_epoch: epoch,
+        withdrawn_in_epoch,
+        ..*user_data
+    })
+}
+
+// Account state after locking withdrawals until `unlock_ts`; an existing lockup
+// can be extended but never shortened
+pub fn apply_lockup(
+    user_data: &UserAccount,
+    unlock_ts: i64,
+    now: i64,
+) -> Result<UserAccount, ProgramError> {
+    if unlock_ts <= now {
+        return Err(DepositError::InvalidUnlockTime.into());
+    }
+
+    Ok(UserAccount {
+        unlock_ts: unlock_ts.max(user_data.unlock_ts),
+        ..*user_data
+    })
+}
+
+// Account state after locking `amount` into a tranche of `tier` at `now`; the
+// slot of a tranche whose lockup has ended is reused
+pub fn apply_tranche(
+    user_data: &UserAccount,
+    tier: &LockupTier,
+    amount: u64,
+    now: i64,
+) -> Result<UserAccount, ProgramError> {
+    let unlock_ts = now
+        .checked_add(tier.duration)
+        .ok_or(DepositError::ArithmeticOverflow)?;
+
+    let mut tranches = user_data.tranches;
+    let slot = tranches
+        .iter_mut()
+        .find(|tranche| tranche.amount == 0 || now >= tranche.unlock_ts)
+        .ok_or(DepositError::TooManyTranches)?;
+    *slot = LockupTranche {
+        amount,
+        unlock_ts,
+        multiplier_bps: tier.multiplier_bps,
+    };
+
+    Ok(UserAccount {
+        tranches,
+        ..*user_data
+    })
+}
+
+// Account state after being paid `paid` lamports of interest up to the vault's
+// `index`; interest the rewards account couldn't cover is not owed again
+pub fn apply_interest(
+    user_data: &UserAccount,
+    index: u128,
+    paid: u64,
+) -> Result<UserAccount, ProgramError> {
+    let balance = user_data
+        .balance
+        .checked_add(paid)
+        .ok_or(DepositError::ArithmeticOverflow)?;
+
+    Ok(UserAccount {
+        balance,
+        interest_index: index,
+        ..*user_data
+    })
+}
+
+// Account state after withdrawing `amount` at `now`
+pub fn apply_withdraw(
+    user_data: &UserAccount,
+    amount: u64,
+    now: i64,
+) -> Result<UserAccount, ProgramError> {
+    // Verify user has enough balance
+    if user_data.balance < amount {
+        return Err(DepositError::InsufficientBalance.into());
+    }
+
+    // Nothing leaves the account while a lockup is running, nor out of a locked tranche
+    let unlocked = user_data.balance.saturating_sub(user_data.tranche_locked(now));
+    if user_data.is_locked(now) || amount > unlocked {
+        return Err(DepositError::FundsLocked.into());
+    }
+
+    // Verify the amount has already vested
+    if amount > user_data.withdrawable(now) {
+        return Err(DepositError::AmountStillVesting.into());
+    }
+
+    let lifetime_withdrawn = user_data
+        .lifetime_withdrawn
+        .checked_add(amount)
+        .ok_or(DepositError::ArithmeticOverflow)?;
+
+    Ok(UserAccount {
+        balance: user_data.balance - amount,
+        lifetime_withdrawn,
+        ..*user_data
+    })
+}
+
+// Account state after the delegate withdraws `amount` at `now`, spending its allowance
+pub fn apply_delegated_withdraw(
+    user_data: &UserAccount,
+    amount: u64,
+    now: i64,
+) -> Result<UserAccount, ProgramError> {
+    let delegated_amount = user_data
+        .delegated_amount
+        .checked_sub(amount)
+        .ok_or(DepositError::DelegateAllowanceExceeded)?;
+
+    Ok(UserAccount {
+        delegated_amount,
+        ..apply_withdraw(user_data, amount, now)?
+    })
+}
+
+// How much a withdraw takes out of the balance
+enum WithdrawAmount {
+    Exact(u64),
+    // Everything above the given balance, computed from the balance on chain
+    LeaveRemaining(u64),
+    // The amount RequestWithdraw recorded, once the vault's cooldown has passed
+    Pending,
+}
+
+// Where a withdraw pays out to
+enum Payout {
+    Owner,
+    // The account following the config, which the owner picked
+    Destination,
+}
+
+// Withdraw function
+fn process_withdraw(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    amount: WithdrawAmount,
+    payout: Payout,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let destination_account = match payout {
+        Payout::Owner => user_account,
+        Payout::Destination => next_account_info(account_info_iter)?,
+    };
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Derive the PDA for user data account
+    let (expected_user_data_account, _) =
+        find_user_account_address(user_account.key, vault_id, program_id);
+
+    // Verify the user data account is the expected PDA
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    // Verify the vault account is correct
+    let config = VaultConfig::load(config_account, vault_id, program_id)?;
+    let (expected_vault_account, vault_bump) = config.vault_address(vault_id, program_id);
+
+    if expected_vault_account != *vault_account.key {
+        return Err(DepositError::WrongVaultPda.into());
+    }
+
+    let claim = matches!(amount, WithdrawAmount::Pending);
+    config.check_withdrawal(claim)?;
+
+    // Never pay out of data whose owner was left zeroed
+    let mut user_data = UserAccount::load(user_data_account, program_id)?;
+
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let interest_index = config.interest_index_at(now)?;
+    let (rewards_account, fee_vault_account, stats_account) =
+        next_withdraw_accounts(account_info_iter, &config, interest_index)?;
+
+    // Pay the interest earned so far, so that withdrawing everything includes it
+    let mut paid = 0;
+    if let Some(rewards_account) = rewards_account.filter(|_| interest_index > 0) {
+        let owed = user_data.accrued_interest(interest_index, now);
+        paid = pay_interest(
+            rewards_account,
+            vault_account,
+            system_program,
+            vault_id,
+            program_id,
+            owed,
+        )?;
+        user_data = apply_interest(&user_data, interest_index, paid)?;
+
+        // An older, shorter account couldn't record the index
+        grow_user_data_account(user_account, user_data_account, system_program)?;
+        emit_balance_change(Event::Interest, vault_id, user_account.key, paid, user_data.balance)?;
+    }
+
+    let amount = match amount {
+        WithdrawAmount::Exact(amount) => amount,
+        WithdrawAmount::LeaveRemaining(target_balance) => user_data
+            .balance
+            .checked_sub(target_balance)
+            .ok_or(DepositError::InsufficientBalance)?,
+        WithdrawAmount::Pending => {
+            user_data.claimable_withdrawal(config.withdraw_cooldown, now)?
+        }
+    };
+
+    // The fee tier is picked by the balance held before the withdrawal
+    let fee = config.withdraw_fee(amount, user_data.balance);
+
+    // Update user account balance. This must stay before the transfer: the
+    // balance is already debited if anything the CPI reaches ever re-enters
+    // this program, and a failed transfer reverts the debit with it.
+    let user_data = apply_withdraw(&user_data, amount, now)?;
+    let mut user_data = config.apply_withdraw_limits(user_data, amount, clock.epoch)?;
+    if config.epoch_withdraw_limit > 0 {
+        // An older, shorter account couldn't record the epoch
+        grow_user_data_account(user_account, user_data_account, system_program)?;
+    }
+    if claim {
+        user_data.pending_withdrawal = 0;
+        user_data.withdraw_requested_at = 0;
+    }
+    user_data.save(user_data_account)?;
+    if let Some(stats_account) = stats_account {
+        record_tvl_change(stats_account, vault_id, program_id, paid, amount)?;
+    }
+
+    // Transfer SOL from vault to user, less the fee debited along with it
+    invoke_signed(
+        &system_instruction::transfer(vault_account.key, destination_account.key, amount - fee),
+        &[
+            vault_account.clone(),
+            destination_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[b"vault", &vault_id.to_le_bytes(), &[vault_bump]]],
+    )?;
+    if let Some(fee_vault_account) = fee_vault_account {
+        collect_withdraw_fee(
+            vault_account,
+            fee_vault_account,
+            system_program,
+            vault_id,
+            vault_bump,
+            program_id,
+            fee,
+        )?;
+    }
+
+    // After the transfers, whose CPIs would otherwise clear the return data
+    set_balance_return_data(amount, user_data.balance);
+    Event::Withdraw(WithdrawEvent {
+        vault_id,
+        owner: *user_account.key,
+        destination: *destination_account.key,
+        amount,
+        fee,
+        balance: user_data.balance,
+    })
+    .emit()
+}
+
+// The rewards, fee vault and stats accounts a withdrawal takes after its fixed
+// ones, each passed only when the vault's settings call for it
+type WithdrawAccounts<'a, 'b> =
+    (Option<&'a AccountInfo<'b>>, Option<&'a AccountInfo<'b>>, Option<&'a AccountInfo<'b>>);
+
+// Interest is paid out of the rewards account and withdraw fees go into the fee
+// vault, which always follows it, as the stats account follows both
+fn next_withdraw_accounts<'a, 'b>(
+    account_info_iter: &mut std::slice::Iter<'a, AccountInfo<'b>>,
+    config: &VaultConfig,
+    interest_index: u128,
+) -> Result<WithdrawAccounts<'a, 'b>, ProgramError> {
+    let tracks_tvl = config.tvl_cap > 0;
+    let rewards_account = if interest_index > 0 || config.charges_withdraw_fee() || tracks_tvl {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+    let fee_vault_account = if config.charges_withdraw_fee() || tracks_tvl {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+    let stats_account = if tracks_tvl {
+        Some(next_account_info(account_info_iter)?)
+    } else {
+        None
+    };
+    Ok((rewards_account, fee_vault_account, stats_account))
+}
+
+// Load the config of a vault for an instruction only its admin may send; an
+// uninitialized config has no admin to authorize it
+fn load_admin_config(
+    program_id: &Pubkey,
+    vault_id: u16,
+    admin: &Pubkey,
+    config_account: &AccountInfo,
+) -> Result<VaultConfig, ProgramError> {
+    if config_account.data_is_empty() {
+        return Err(DepositError::AccountNotInitialized.into());
+    }
+    let config = VaultConfig::load(config_account, vault_id, program_id)?;
+    if config.admin != *admin {
+        return Err(DepositError::Unauthorized.into());
+    }
+    Ok(config)
+}
+
+// Grow account function
+fn process_grow_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    new_size: usize,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Derive the PDA for user data account
+    let (expected_user_data_account, _) =
+        find_user_account_address(user_account.key, vault_id, program_id);
+
+    // Verify the user data account is the expected PDA
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    // Verify the signer owns the account
+    let user_data = UserAccount::load(user_data_account, program_id)?;
+    if user_data.owner != *user_account.key {
+        return Err(DepositError::Unauthorized.into());
+    }
+
+    // Only allow growing, and never past the cap
+    if new_size < user_data_account.data_len() || new_size > MAX_USER_ACCOUNT_SIZE {
+        return Err(ProgramError::InvalidRealloc);
+    }
+
+    // Top up the rent for the additional space from the owner
+    let rent = Rent::get()?;
+    let rent_shortfall = rent
+        .minimum_balance(new_size)
+        .saturating_sub(user_data_account.lamports());
+
+    if rent_shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(user_account.key, user_data_account.key, rent_shortfall),
+            &[
+                user_account.clone(),
+                user_data_account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+
+    // Resize the account, zero-initializing the new bytes
+    user_data_account.realloc(new_size, true)?;
+
+    msg!("User account grown to {} bytes", new_size);
+    Ok(())
+}
+
+// Initialize config function
+fn process_initialize_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let program_data_account = next_account_info(account_info_iter)?;
+    let authority_account = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Only whoever can upgrade the program decides who administers a vault;
+    // otherwise the first caller would take over any vault without a config
+    check_upgrade_authority(program_id, program_data_account, authority_account)?;
+
+    // Verify the config account is the expected PDA
+    let (expected_config_account, bump_seed) = find_config_address(vault_id, program_id);
+    if expected_config_account != *config_account.key {
+        return Err(DepositError::WrongConfigPda.into());
+    }
+
+    // Create the config account
+    let rent = Rent::get()?;
+    invoke_signed(
+        &system_instruction::create_account(
+            admin_account.key,
+            config_account.key,
+            rent.minimum_balance(VaultConfig::LEN),
+            VaultConfig::LEN as u64,
+            program_id,
+        ),
+        &[
+            admin_account.clone(),
+            config_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[b"config", &vault_id.to_le_bytes(), &[bump_seed]]],
+    )?;
+
+    // Record the signer as the vault's admin
+    let (_, vault_bump) = find_vault_address(vault_id, program_id);
+    let config = VaultConfig {
+        admin: *admin_account.key,
+        vault_bump,
+        ..VaultConfig::default()
+    };
+    config.serialize(&mut &mut config_account.data.borrow_mut()[..])?;
+
+    msg!("Config initialized for vault {}", vault_id);
+    Ok(())
+}
+
+// Verify `authority_account` signed and is the upgrade authority recorded in the
+// program's ProgramData account
+fn check_upgrade_authority(
+    program_id: &Pubkey,
+    program_data_account: &AccountInfo,
+    authority_account: &AccountInfo,
+) -> ProgramResult {
+    if !authority_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let (expected_program_data_account, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    if expected_program_data_account != *program_data_account.key
+        || *program_data_account.owner != bpf_loader_upgradeable::id()
+    {
+        return Err(DepositError::WrongProgramData.into());
+    }
+
+    // Only the metadata ahead of the program's bytes is needed
+    let metadata_len = UpgradeableLoaderState::size_of_programdata_metadata();
+    let data = program_data_account.data.borrow();
+    let state = data
+        .get(..metadata_len)
+        .and_then(|metadata| limited_deserialize(metadata, metadata_len as u64).ok());
+    match state {
+        Some(UpgradeableLoaderState::ProgramData {
+            upgrade_authority_address: Some(upgrade_authority),
+            ..
+        }) if upgrade_authority == *authority_account.key => Ok(()),
+        _ => Err(DepositError::Unauthorized.into()),
+    }
+}
+
+// Set pause flags function
+fn process_set_pause_flags(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    deposits: bool,
+    withdrawals: bool,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Load the config; an uninitialized config has no admin to authorize this
+    if config_account.data_is_empty() {
+        return Err(DepositError::AccountNotInitialized.into());
+    }
+    let mut config = VaultConfig::load(config_account, vault_id, program_id)?;
+
+    // Verify the signer may pause the vault
+    if !config.is_authorized(Role::Pauser, admin_account.key) {
+        return Err(DepositError::Unauthorized.into());
+    }
+
+    config.deposits_paused = deposits;
+    config.withdrawals_paused = withdrawals;
+    config.save(config_account)?;
+
+    msg!(
+        "Vault {} pause flags set: deposits {}, withdrawals {}",
+        vault_id,
+        deposits,
+        withdrawals
+    );
+    Ok(())
+}
+
+// Batch credit function
+fn process_batch_credit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    credits: &[(Pubkey, u64)],
+) -> ProgramResult {
+    // Reject an oversized batch before touching any account
+    if credits.len() > MAX_BATCH_CREDITS {
+        return Err(DepositError::BatchTooLarge.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let rewards_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let stats_account = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Load the config; an uninitialized config has no admin to authorize this
+    if config_account.data_is_empty() {
+        return Err(DepositError::AccountNotInitialized.into());
+    }
+    let config = VaultConfig::load(config_account, vault_id, program_id)?;
+
+    // Verify the signer may distribute rewards
+    if !config.is_authorized(Role::Distributor, admin_account.key) {
+        return Err(DepositError::Unauthorized.into());
+    }
+
+    // Verify the rewards and vault accounts are correct
+    let (expected_rewards_account, rewards_bump) = find_rewards_address(vault_id, program_id);
+    if expected_rewards_account != *rewards_account.key {
+        return Err(DepositError::WrongRewardsPda.into());
+    }
+
+    let (expected_vault_account, _) = find_vault_address(vault_id, program_id);
+    if expected_vault_account != *vault_account.key {
+        return Err(DepositError::WrongVaultPda.into());
+    }
+
+    // Verify the rewards account covers the whole batch above its rent reserve,
+    // as pay_interest leaves it
+    let total = credits
+        .iter()
+        .try_fold(0u64, |total, (_, amount)| total.checked_add(*amount))
+        .ok_or(DepositError::ArithmeticOverflow)?;
+    let spare = rewards_account
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(rewards_account.data_len()));
+    if spare < total {
+        return Err(DepositError::InsufficientRewards.into());
+    }
+
+    // Move the credited lamports into the vault so it stays fully backed. This
+    // comes first so that the interest paid below can't spend them.
+    invoke_signed(
+        &system_instruction::transfer(rewards_account.key, vault_account.key, total),
+        &[
+            rewards_account.clone(),
+            vault_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[b"rewards", &vault_id.to_le_bytes(), &[rewards_bump]]],
+    )?;
+
+    // Credit each user; their data accounts follow in the order of `credits`
+    let now = Clock::get()?.unix_timestamp;
+    let interest_index = config.interest_index_at(now)?;
+    let mut total_interest = 0u64;
+    for (owner, amount) in credits {
+        let user_data_account = next_account_info(account_info_iter)?;
+
+        // Verify the user data account is the owner's PDA
+        let (expected_user_data_account, _) =
+            find_user_account_address(owner, vault_id, program_id);
+        if expected_user_data_account != *user_data_account.key {
+            return Err(DepositError::WrongUserPda.into());
+        }
+
+        // A credit needs an account to land in
+        let mut user_data = UserAccount::load(user_data_account, program_id)?;
+
+        // Pay the interest earned so far before the balance changes, as Deposit
+        // does, so the credit only earns from now on
+        if interest_index > 0 {
+            let owed = user_data.accrued_interest(interest_index, now);
+            let paid = pay_interest(
+                rewards_account,
+                vault_account,
+                system_program,
+                vault_id,
+                program_id,
+                owed,
+            )?;
+            user_data = apply_interest(&user_data, interest_index, paid)?;
+            total_interest = total_interest.saturating_add(paid);
+
+            // The signer pays to grow an older, shorter account that couldn't record the index
+            grow_user_data_account(admin_account, user_data_account, system_program)?;
+            emit_balance_change(Event::Interest, vault_id, owner, paid, user_data.balance)?;
+        }
+
+        // A credit is a deposit for every total
+        let user_data = apply_deposit(&user_data, *amount, None, now)?;
+        user_data.save(user_data_account)?;
+        Event::Credit(BalanceEvent {
+            vault_id,
+            owner: *owner,
+            amount: *amount,
+            balance: user_data.balance,
+        })
+        .emit()?;
+    }
+
+    // Credits count against the cap as deposits do, together with the interest
+    if config.tvl_cap > 0 {
+        let added = total.checked_add(total_interest).ok_or(DepositError::ArithmeticOverflow)?;
+        record_tvl_deposit(stats_account, vault_id, program_id, added, config.tvl_cap)?;
+    }
+
+    msg!("Credited {} lamports of rewards to {} users", total, credits.len());
+    Ok(())
+}
+
+// Move up to `owed` lamports of interest from the rewards account into the vault,
+// returning how much was moved. The rewards account keeps its rent reserve, and
+// whatever it can't cover is forfeited, so an empty rewards account never
+// blocks a deposit or withdrawal.
+fn pay_interest<'a>(
+    rewards_account: &AccountInfo<'a>,
+    vault_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    vault_id: u16,
+    program_id: &Pubkey,
+    owed: u64,
+) -> Result<u64, ProgramError> {
+    let (expected_rewards_account, rewards_bump) = find_rewards_address(vault_id, program_id);
+    if expected_rewards_account != *rewards_account.key {
+        return Err(DepositError::WrongRewardsPda.into());
+    }
+
+    let spare = rewards_account
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(rewards_account.data_len()));
+    let paid = owed.min(spare);
+    if paid < owed {
+        msg!("Forfeited {} lamports of interest the rewards account can't cover", owed - paid);
+    }
+    if paid == 0 {
+        return Ok(0);
+    }
+
+    invoke_signed(
+        &system_instruction::transfer(rewards_account.key, vault_account.key, paid),
+        &[
+            rewards_account.clone(),
+            vault_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[b"rewards", &vault_id.to_le_bytes(), &[rewards_bump]]],
+    )?;
+    Ok(paid)
+}
+
+// Move a withdraw fee out of the vault into the fee vault, where it waits for
+// CollectFees
+fn collect_withdraw_fee<'a>(
+    vault_account: &AccountInfo<'a>,
+    fee_vault_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    vault_id: u16,
+    vault_bump: u8,
+    program_id: &Pubkey,
+    fee: u64,
+) -> ProgramResult {
+    let (expected_fee_vault_account, _) = find_fee_vault_address(vault_id, program_id);
+    if expected_fee_vault_account != *fee_vault_account.key {
+        return Err(DepositError::WrongFeeVaultPda.into());
+    }
+    if fee == 0 {
+        return Ok(());
+    }
+
+    invoke_signed(
+        &system_instruction::transfer(vault_account.key, fee_vault_account.key, fee),
+        &[
+            vault_account.clone(),
+            fee_vault_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[b"vault", &vault_id.to_le_bytes(), &[vault_bump]]],
+    )
+}
+
+// Grant or revoke role function; revoking assigns Pubkey::default()
+fn process_set_role(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    role: Role,
+    authority: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Only the admin itself hands out roles
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    // Grow a legacy config so it can hold roles
+    grow_config(admin_account, config_account, system_program)?;
+
+    match role {
+        Role::Pauser => config.pauser = authority,
+        Role::Distributor => config.distributor = authority,
+    }
+    config.save(config_account)?;
+
+    msg!("Vault {} role {:?} assigned to {}", vault_id, role, authority);
+    Ok(())
+}
+
+// Sweep dust function
+fn process_sweep_dust(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    threshold: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let owner_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let stats_account = next_account_info(account_info_iter)?;
+    let rewards_account = next_account_info(account_info_iter)?;
+    let fee_vault_account = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    // Verify the user data account is the owner's PDA, so dust only goes back to its owner
+    let (expected_user_data_account, _) =
+        find_user_account_address(owner_account.key, vault_id, program_id);
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    let (expected_vault_account, vault_bump) = config.vault_address(vault_id, program_id);
+    if expected_vault_account != *vault_account.key {
+        return Err(DepositError::WrongVaultPda.into());
+    }
+
+    let mut user_data = UserAccount::load(user_data_account, program_id)?;
+
+    // Pay the interest earned so far, so the dust is judged by the whole balance
+    let now = Clock::get()?.unix_timestamp;
+    let interest_index = config.interest_index_at(now)?;
+    let mut paid = 0;
+    if interest_index > 0 {
+        let owed = user_data.accrued_interest(interest_index, now);
+        paid = pay_interest(
+            rewards_account,
+            vault_account,
+            system_program,
+            vault_id,
+            program_id,
+            owed,
+        )?;
+        user_data = apply_interest(&user_data, interest_index, paid)?;
+
+        // The admin pays to grow an older, shorter account that couldn't record the index
+        grow_user_data_account(admin_account, user_data_account, system_program)?;
+        emit_balance_change(Event::Interest, vault_id, owner_account.key, paid, user_data.balance)?;
+    }
+
+    // The vault's own ceiling bounds whatever threshold the admin passes
+    if user_data.balance >= threshold.min(config.dust_threshold) {
+        return Err(DepositError::DustAboveThreshold.into());
+    }
+
+    // Sweeping must not release funds that are still vesting
+    let amount = user_data.balance;
+    let mut user_data = apply_withdraw(&user_data, amount, now)?;
+
+    // The admin pays to grow an older, shorter account that couldn't record the flag
+    grow_user_data_account(admin_account, user_data_account, system_program)?;
+    user_data.closeable = true;
+    user_data.save(user_data_account)?;
+    if config.tvl_cap > 0 {
+        record_tvl_change(stats_account, vault_id, program_id, paid, amount)?;
+    }
+
+    // Only an owner who opted in gives their dust to the fee vault
+    let destination_account = if user_data.sweep_to_treasury {
+        fund_fee_vault(admin_account, fee_vault_account, system_program, vault_id, program_id)?;
+        fee_vault_account
+    } else {
+        owner_account
+    };
+
+    invoke_signed(
+        &system_instruction::transfer(vault_account.key, destination_account.key, amount),
+        &[
+            vault_account.clone(),
+            destination_account.clone(),
+            system_program.clone(),
+        ],
+        &[&[b"vault", &vault_id.to_le_bytes(), &[vault_bump]]],
+    )?;
+
+    msg!("Swept {} lamports of dust to {}", amount, destination_account.key);
+    Event::SweepDust(BalanceEvent {
+        vault_id,
+        owner: *owner_account.key,
+        amount,
+        balance: user_data.balance,
+    })
+    .emit()
+}
+
+// Grow a config created by an older version to the current size, with the admin
+// (or whoever signs for it) paying the rent
+fn grow_config<'a>(
+    admin_account: &AccountInfo<'a>,
+    config_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    if config_account.data_len() >= VaultConfig::LEN {
+        return Ok(());
+    }
+
+    let rent = Rent::get()?;
+    let rent_shortfall = rent
+        .minimum_balance(VaultConfig::LEN)
+        .saturating_sub(config_account.lamports());
+
+    if rent_shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(admin_account.key, config_account.key, rent_shortfall),
+            &[
+                admin_account.clone(),
+                config_account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+
+    config_account.realloc(VaultConfig::LEN, true)
+}
+
+// Set deposit limit function
+fn process_set_deposit_limit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    daily_limit: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    // Grow a legacy config so it can hold the limit
+    grow_config(admin_account, config_account, system_program)?;
+
+    emit_param_change(vault_id, "daily_deposit_limit", config.daily_deposit_limit, daily_limit)?;
+    config.daily_deposit_limit = daily_limit;
+    config.save(config_account)?;
+
+    msg!("Vault {} daily deposit limit set to {} lamports", vault_id, daily_limit);
+    Ok(())
+}
+
+// Set withdraw limit function
+fn process_set_withdraw_limit(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    epoch_limit: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    // Grow a legacy config so it can hold the limit
+    grow_config(admin_account, config_account, system_program)?;
+
+    // Withdrawals already counted this epoch count against the new limit
+    emit_param_change(vault_id, "epoch_withdraw_limit", config.epoch_withdraw_limit, epoch_limit)?;
+    config.epoch_withdraw_limit = epoch_limit;
+    config.save(config_account)?;
+
+    msg!("Vault {} epoch withdraw limit set to {} lamports", vault_id, epoch_limit);
+    Ok(())
+}
+
+// Set the ceiling below which SweepDust may take a balance
+fn process_set_dust_threshold(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    threshold: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    // Grow a legacy config so it can hold the threshold
+    grow_config(admin_account, config_account, system_program)?;
+
+    emit_param_change(vault_id, "dust_threshold", config.dust_threshold, threshold)?;
+    config.dust_threshold = threshold;
+    config.save(config_account)?;
+
+    msg!("Vault {} dust threshold set to {} lamports", vault_id, threshold);
+    Ok(())
+}
+
+// Record whether the owner lets SweepDust pay their dust into the fee vault
+fn process_set_sweep_to_treasury(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    enabled: bool,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Verify the user data account is the signer's SOL account
+    let (expected_user_data_account, _) =
+        find_user_account_address(user_account.key, vault_id, program_id);
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    let mut user_data = UserAccount::load(user_data_account, program_id)?;
+    if user_data.owner != *user_account.key {
+        return Err(DepositError::Unauthorized.into());
+    }
+
+    // An older, shorter account couldn't record the flag
+    grow_user_data_account(user_account, user_data_account, system_program)?;
+    user_data.sweep_to_treasury = enabled;
+    user_data.save(user_data_account)?;
+
+    let state = if enabled { "enabled" } else { "disabled" };
+    msg!("Dust sweeping to the fee vault {} for {}", state, user_account.key);
+    Ok(())
+}
+
+// Set max balance per user function
+fn process_set_max_balance_per_user(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    max_balance: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    // Grow a legacy config so it can hold the maximum
+    grow_config(admin_account, config_account, system_program)?;
+
+    emit_param_change(vault_id, "max_balance_per_user", config.max_balance_per_user, max_balance)?;
+    config.max_balance_per_user = max_balance;
+    config.save(config_account)?;
+
+    msg!("Vault {} max balance per user set to {} lamports", vault_id, max_balance);
+    Ok(())
+}
+
+// Set TVL cap function
+fn process_set_tvl_cap(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    cap: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let stats_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    let (expected_stats_account, stats_bump) = find_stats_address(vault_id, program_id);
+    if expected_stats_account != *stats_account.key {
+        return Err(DepositError::WrongStatsPda.into());
+    }
+    let (expected_vault_account, _) = find_vault_address(vault_id, program_id);
+    if expected_vault_account != *vault_account.key {
+        return Err(DepositError::WrongVaultPda.into());
+    }
+
+    // Grow a legacy config so it can hold the cap
+    grow_config(admin_account, config_account, system_program)?;
+
+    // Create the stats account the first time a cap is set
+    let rent = Rent::get()?;
+    if stats_account.data_is_empty() {
+        invoke_signed(
+            &system_instruction::create_account(
+                admin_account.key,
+                stats_account.key,
+                rent.minimum_balance(VaultStats::LEN),
+                VaultStats::LEN as u64,
+                program_id,
+            ),
+            &[
+                admin_account.clone(),
+                stats_account.clone(),
+                system_program.clone(),
+            ],
+            &[&[b"stats", &vault_id.to_le_bytes(), &[stats_bump]]],
+        )?;
+    }
+
+    // Deposits aren't tracked without a cap, so enabling one starts from what
+    // the vault holds for its users
+    if config.tvl_cap == 0 && cap > 0 {
+        let stats = VaultStats {
+            total_value_locked: vault_account
+                .lamports()
+                .saturating_sub(rent.minimum_balance(vault_account.data_len())),
+        };
+        stats.save(stats_account)?;
+    }
+
+    emit_param_change(vault_id, "tvl_cap", config.tvl_cap, cap)?;
+    config.tvl_cap = cap;
+    config.save(config_account)?;
+
+    msg!("Vault {} TVL cap set to {} lamports", vault_id, cap);
+    Ok(())
+}
+
+// Set min residual balance function
+fn process_set_min_residual_balance(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    min_balance: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    // Grow a legacy config so it can hold the minimum
+    grow_config(admin_account, config_account, system_program)?;
+
+    // Balances already below it stay until their next withdrawal
+    emit_param_change(vault_id, "min_residual_balance", config.min_residual_balance, min_balance)?;
+    config.min_residual_balance = min_balance;
+    config.save(config_account)?;
+
+    msg!("Vault {} min residual balance set to {} lamports", vault_id, min_balance);
+    Ok(())
+}
+
+// Set withdraw cooldown function
+fn process_set_withdraw_cooldown(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    cooldown: i64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    if !(0..=MAX_WITHDRAW_COOLDOWN).contains(&cooldown) {
+        return Err(DepositError::InvalidCooldown.into());
+    }
+
+    // Grow a legacy config so it can hold the cooldown
+    grow_config(admin_account, config_account, system_program)?;
+
+    // Requests already made become claimable by the new cooldown
+    emit_param_change(
+        vault_id,
+        "withdraw_cooldown",
+        config.withdraw_cooldown as u64,
+        cooldown as u64,
+    )?;
+    config.withdraw_cooldown = cooldown;
+    config.save(config_account)?;
+
+    msg!("Vault {} withdraw cooldown set to {} seconds", vault_id, cooldown);
+    Ok(())
+}
+
+// Count a deposit credited to a user towards the vault's TVL cap
+fn record_tvl_deposit(
+    stats_account: &AccountInfo,
+    vault_id: u16,
+    program_id: &Pubkey,
+    credited: u64,
+    cap: u64,
+) -> ProgramResult {
+    let mut stats = VaultStats::load(stats_account, vault_id, program_id)?;
+    stats.total_value_locked = stats
+        .total_value_locked
+        .checked_add(credited)
+        .ok_or(DepositError::ArithmeticOverflow)?;
+    if stats.total_value_locked > cap {
+        return Err(DepositError::CapExceeded.into());
+    }
+    stats.save(stats_account)
+}
+
+// Add the interest paid into balances to the vault's TVL and take a debit off
+// it. Interest already owed can't be refused, so unlike a deposit it isn't held
+// to the cap.
+fn record_tvl_change(
+    stats_account: &AccountInfo,
+    vault_id: u16,
+    program_id: &Pubkey,
+    interest: u64,
+    debited: u64,
+) -> ProgramResult {
+    let mut stats = VaultStats::load(stats_account, vault_id, program_id)?;
+    stats.total_value_locked = stats
+        .total_value_locked
+        .saturating_add(interest)
+        .saturating_sub(debited);
+    stats.save(stats_account)
+}
+
+// Set lockup tier function
+fn process_set_lockup_tier(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    tier: u8,
+    duration: i64,
+    multiplier_bps: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    // A tier either is disabled or locks for some time and pays at least 1x
+    if tier as usize >= LOCKUP_TIER_COUNT {
+        return Err(DepositError::UnknownLockupTier.into());
+    }
+    let lockup_tier = match duration {
+        0 => LockupTier::default(),
+        duration if duration > 0 && multiplier_bps >= MULTIPLIER_BPS_ONE => LockupTier {
+            duration,
+            multiplier_bps,
+        },
+        _ => return Err(DepositError::InvalidLockupTier.into()),
+    };
+
+    // Grow a legacy config so it can hold the tiers
+    grow_config(admin_account, config_account, system_program)?;
+
+    // Tranches already deposited keep the terms they were locked under
+    config.lockup_tiers[tier as usize] = lockup_tier;
+    config.save(config_account)?;
+
+    if lockup_tier.duration == 0 {
+        msg!("Vault {} lockup tier {} disabled", vault_id, tier);
+    } else {
+        msg!(
+            "Vault {} lockup tier {} set to {} seconds at {} bps",
+            vault_id,
+            tier,
+            lockup_tier.duration,
+            lockup_tier.multiplier_bps
+        );
+    }
+    Ok(())
+}
+
+// Accrue interest function
+fn process_accrue_interest(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let config_account = next_account_info(account_info_iter)?;
+    let rewards_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let stats_account = next_account_info(account_info_iter)?;
+
+    let mut config = VaultConfig::load(config_account, vault_id, program_id)?;
+    if config.interest_index == 0 {
+        return Err(DepositError::InterestNotEnabled.into());
+    }
+
+    let (expected_vault_account, _) = find_vault_address(vault_id, program_id);
+    if expected_vault_account != *vault_account.key {
+        return Err(DepositError::WrongVaultPda.into());
+    }
+
+    // Bring the index up to date; compounding happens each time it is saved
+    let now = Clock::get()?.unix_timestamp;
+    config.interest_index = config.interest_index_at(now)?;
+    config.interest_updated_at = now;
+    config.save(config_account)?;
+
+    // Settle each user account that follows
+    let mut total_paid = 0u64;
+    let mut settled = 0usize;
+    for user_data_account in account_info_iter {
+        if user_data_account.owner != program_id {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        let user_data = UserAccount::load(user_data_account, program_id)?;
+        let (expected_user_data_account, _) =
+            find_user_data_address(&user_data.owner, vault_id, &user_data.mint, program_id);
+        if expected_user_data_account != *user_data_account.key {
+            return Err(DepositError::WrongUserPda.into());
+        }
+
+        // Token balances aren't backed by lamports, so they earn no interest
+        if user_data.mint != Pubkey::default() {
+            return Err(DepositError::WrongMint.into());
+        }
+
+        // Without a payer here, an older, shorter account can't be grown to
+        // record the index; its next deposit or withdrawal settles it instead
+        if user_data_account.data_len() < UserAccount::LEN {
+            msg!("Skipped {}: account must grow before it can accrue", user_data_account.key);
+            continue;
+        }
+
+        let owed = user_data.accrued_interest(config.interest_index, now);
+        let paid = pay_interest(
+            rewards_account,
+            vault_account,
+            system_program,
+            vault_id,
+            program_id,
+            owed,
+        )?;
+        let user_data = apply_interest(&user_data, config.interest_index, paid)?;
+        user_data.save(user_data_account)?;
+
+        emit_balance_change(Event::Interest, vault_id, &user_data.owner, paid, user_data.balance)?;
+        total_paid = total_paid.saturating_add(paid);
+        settled += 1;
+    }
+    if config.tvl_cap > 0 {
+        record_tvl_change(stats_account, vault_id, program_id, total_paid, 0)?;
+    }
+
+    msg!("Vault {} interest index is {}", vault_id, config.interest_index);
+    msg!("Paid {} lamports of interest to {} accounts", total_paid, settled);
+    Ok(())
+}
+
+// Set interest rate function
+fn process_set_interest_rate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    rate_bps: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    // Grow a legacy config so it can hold the index
+    grow_config(admin_account, config_account, system_program)?;
+
+    emit_param_change(
+        vault_id,
+        "interest_rate_bps",
+        config.interest_rate_bps as u64,
+        rate_bps as u64,
+    )?;
+    config.set_interest_rate(rate_bps, Clock::get()?.unix_timestamp)?;
+    config.save(config_account)?;
+
+    msg!("Vault {} interest rate set to {} bps a year", vault_id, rate_bps);
+    Ok(())
+}
+
+// Set params function
+fn process_set_params(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    interest_rate_bps: u16,
+    withdraw_fee_bps: u16,
+    min_deposit: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    if withdraw_fee_bps > MAX_WITHDRAW_FEE_BPS {
+        return Err(DepositError::InvalidWithdrawFee.into());
+    }
+
+    // Grow a legacy config so it can hold the params
+    grow_config(admin_account, config_account, system_program)?;
+
+    // Leaving the rate as it is doesn't enable interest on a vault without it
+    if interest_rate_bps != config.interest_rate_bps {
+        emit_param_change(
+            vault_id,
+            "interest_rate_bps",
+            config.interest_rate_bps as u64,
+            interest_rate_bps as u64,
+        )?;
+        config.set_interest_rate(interest_rate_bps, Clock::get()?.unix_timestamp)?;
+    }
+    if withdraw_fee_bps != config.withdraw_fee_bps {
+        emit_param_change(
+            vault_id,
+            "withdraw_fee_bps",
+            config.withdraw_fee_bps as u64,
+            withdraw_fee_bps as u64,
+        )?;
+        config.withdraw_fee_bps = withdraw_fee_bps;
+    }
+    if min_deposit != config.min_deposit {
+        emit_param_change(vault_id, "min_deposit", config.min_deposit, min_deposit)?;
+        config.min_deposit = min_deposit;
+    }
+    config.save(config_account)?;
+
+    if withdraw_fee_bps > 0 {
+        let fee_vault_account = next_account_info(account_info_iter)?;
+        fund_fee_vault(admin_account, fee_vault_account, system_program, vault_id, program_id)?;
+    }
+
+    msg!("Vault {} params updated", vault_id);
+    Ok(())
+}
+
+// Set fee tier function
+fn process_set_fee_tier(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    tier: u8,
+    min_balance: u64,
+    fee_bps: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    if tier as usize >= FEE_TIER_COUNT {
+        return Err(DepositError::UnknownFeeTier.into());
+    }
+    if fee_bps > MAX_WITHDRAW_FEE_BPS {
+        return Err(DepositError::InvalidWithdrawFee.into());
+    }
+    let fee_tier = match min_balance {
+        0 => FeeTier::default(),
+        min_balance => FeeTier { min_balance, fee_bps },
+    };
+
+    // Grow a legacy config so it can hold the tiers
+    grow_config(admin_account, config_account, system_program)?;
+
+    config.fee_tiers[tier as usize] = fee_tier;
+    config.save(config_account)?;
+
+    if fee_tier.fee_bps > 0 {
+        let fee_vault_account = next_account_info(account_info_iter)?;
+        fund_fee_vault(admin_account, fee_vault_account, system_program, vault_id, program_id)?;
+    }
+
+    if fee_tier.min_balance == 0 {
+        msg!("Vault {} fee tier {} disabled", vault_id, tier);
+    } else {
+        msg!(
+            "Vault {} fee tier {} set to {} bps from {} lamports",
+            vault_id,
+            tier,
+            fee_tier.fee_bps,
+            fee_tier.min_balance
+        );
+    }
+    Ok(())
+}
+
+// Fees smaller than the rent reserve can't go into an empty fee vault, so the
+// admin tops it up when first setting a fee
+fn fund_fee_vault<'a>(
+    admin_account: &AccountInfo<'a>,
+    fee_vault_account: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    vault_id: u16,
+    program_id: &Pubkey,
+) -> ProgramResult {
+    let (expected_fee_vault_account, _) = find_fee_vault_address(vault_id, program_id);
+    if expected_fee_vault_account != *fee_vault_account.key {
+        return Err(DepositError::WrongFeeVaultPda.into());
+    }
+
+    let shortfall = Rent::get()?
+        .minimum_balance(0)
+        .saturating_sub(fee_vault_account.lamports());
+    if shortfall == 0 {
+        return Ok(());
+    }
+    invoke(
+        &system_instruction::transfer(admin_account.key, fee_vault_account.key, shortfall),
+        &[
+            admin_account.clone(),
+            fee_vault_account.clone(),
+            system_program.clone(),
+        ],
+    )
+}
+
+// Collect fees function
+fn process_collect_fees(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let fee_vault_account = next_account_info(account_info_iter)?;
+    let destination_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    let (expected_fee_vault_account, fee_vault_bump) =
+        find_fee_vault_address(vault_id, program_id);
+    if expected_fee_vault_account != *fee_vault_account.key {
+        return Err(DepositError::WrongFeeVaultPda.into());
+    }
+
+    // Leave the rent reserve so that later fees of any size can still land
+    let collected = fee_vault_account
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(0));
+    if collected > 0 {
+        invoke_signed(
+            &system_instruction::transfer(
+                fee_vault_account.key,
+                destination_account.key,
+                collected,
+            ),
+            &[
+                fee_vault_account.clone(),
+                destination_account.clone(),
+                system_program.clone(),
+            ],
+            &[&[b"fee-vault", &vault_id.to_le_bytes(), &[fee_vault_bump]]],
+        )?;
+    }
+
+    msg!("Collected {} lamports of fees to {}", collected, destination_account.key);
+    Event::Fees(FeesEvent {
+        vault_id,
+        destination: *destination_account.key,
+        amount: collected,
+    })
+    .emit()
+}
+
+// Record the memo of a deposit or withdrawal the first account signed as an
+// `Event`, and through the SPL Memo program when it was passed along
+fn process_memo(accounts: &[AccountInfo], vault_id: u16, memo: &str) -> ProgramResult {
+    if memo.len() > MAX_MEMO_LEN {
+        return Err(DepositError::MemoTooLong.into());
+    }
+    let signer = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+    Event::Memo(MemoEvent {
+        vault_id,
+        signer: *signer.key,
+        memo: memo.to_string(),
+    })
+    .emit()?;
+    msg!("Memo: {}", memo);
+
+    if let Some(memo_program) = accounts.iter().find(|account| *account.key == spl_memo::id()) {
+        invoke(
+            &spl_memo::build_memo(memo.as_bytes(), &[signer.key]),
+            &[signer.clone(), memo_program.clone()],
+        )?;
+    }
+    Ok(())
+}
+
+// Log a changed vault parameter with its old and new values
+fn emit_param_change(vault_id: u16, name: &str, old: u64, new: u64) -> ProgramResult {
+    msg!("{} changed from {} to {}", name, old, new);
+    Event::Param(ParamEvent { vault_id, name: name.to_string(), old, new }).emit()
+}
+
+// Log a changed admin
+fn emit_admin_change(vault_id: u16, old: &Pubkey, new: &Pubkey) -> ProgramResult {
+    Event::Admin(AdminEvent { vault_id, old: *old, new: *new }).emit()
+}
+
+// Get withdrawable function
+fn process_get_withdrawable(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let owner_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+
+    // Verify the user data account is the owner's PDA
+    let (expected_user_data_account, _) =
+        find_user_account_address(owner_account.key, vault_id, program_id);
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    // Apply the same restrictions Withdraw does
+    let user_data = UserAccount::load(user_data_account, program_id)?;
+    let config = VaultConfig::load(config_account, vault_id, program_id)?;
+    let clock = Clock::get()?;
+    let withdrawable = config.max_withdrawal(&user_data, clock.unix_timestamp, clock.epoch);
+
+    set_return_data(&withdrawable.to_le_bytes());
+    msg!("Withdrawable: {} lamports", withdrawable);
+    Ok(())
+}
+
+// Renounce admin function
+fn process_renounce_admin(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let admin_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+
+    // Verify the admin is a signer
+    if !admin_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_admin_config(program_id, vault_id, admin_account.key, config_account)?;
+
+    // Nobody can sign as the default pubkey, and the config can't be
+    // initialized again, so this can never be undone. Delegated roles go too,
+    // since only the admin could have revoked them.
+    config.admin = Pubkey::default();
+    config.pauser = Pubkey::default();
+    config.distributor = Pubkey::default();
+    config.save(config_account)?;
+
+    emit_admin_change(vault_id, admin_account.key, &config.admin)?;
+    msg!("Vault {} admin {} renounced", vault_id, admin_account.key);
+    Ok(())
+}
+
+// Verify `token_program` is SPL Token or Token-2022 and `mint_account` one of its
+// mints; returns the mint's decimals for transfer_checked
+fn load_mint(mint_account: &AccountInfo, token_program: &AccountInfo) -> Result<u8, ProgramError> {
+    if *token_program.key != spl_token::id() && *token_program.key != spl_token_2022::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if mint_account.owner != token_program.key {
+        return Err(ProgramError::IllegalOwner);
+    }
+    let data = mint_account.data.borrow();
+    Ok(StateWithExtensions::<Mint>::unpack(&data)?.base.decimals)
+}
+
+// Token amount held by a token account of either token program
+fn token_amount(token_account: &AccountInfo) -> Result<u64, ProgramError> {
+    let data = token_account.data.borrow();
+    Ok(StateWithExtensions::<TokenAccount>::unpack(&data)?.base.amount)
+}
+
+// Load the signer's balance of `mint`, verifying the PDA and that it was initialized
+// for that mint; a SOL user account can never pass for a token one
+fn load_token_user_data(
+    program_id: &Pubkey,
+    user_account: &AccountInfo,
+    user_data_account: &AccountInfo,
+    vault_id: u16,
+    mint: &Pubkey,
+) -> Result<UserAccount, ProgramError> {
+    let (expected_user_data_account, _) =
+        find_token_user_account_address(user_account.key, vault_id, mint, program_id);
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    let user_data = UserAccount::load(user_data_account, program_id)?;
+    if user_data.owner != *user_account.key {
+        return Err(DepositError::WrongOwner.into());
+    }
+    if user_data.mint != *mint {
+        return Err(DepositError::WrongMint.into());
+    }
+    Ok(user_data)
+}
+
+// Initialize token account function
+fn process_initialize_token_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let mint_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let vault_token_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let associated_token_program = next_account_info(account_info_iter)?;
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // An all-zero owner would be indistinguishable from zeroed account data
+    if *user_account.key == Pubkey::default() {
+        return Err(DepositError::ZeroOwner.into());
+    }
+
+    load_mint(mint_account, token_program)?;
+
+    // Derive the PDA for the user's balance of this mint
+    let (expected_user_data_account, bump_seed) =
+        find_token_user_account_address(user_account.key, vault_id, mint_account.key, program_id);
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    // Verify the vault and its token account are correct
+    let (expected_vault_account, _) = find_vault_address(vault_id, program_id);
+    if expected_vault_account != *vault_account.key {
+        return Err(DepositError::WrongVaultPda.into());
+    }
+    let expected_vault_token_account =
+        find_vault_token_address(vault_id, mint_account.key, token_program.key, program_id);
+    if expected_vault_token_account != *vault_token_account.key {
+        return Err(DepositError::WrongTokenAccount.into());
+    }
+
+    create_user_data_account(
+        program_id,
+        user_account,
+        user_data_account,
+        system_program,
+        &[
+            b"user-account",
+            user_account.key.as_ref(),
+            &vault_id.to_le_bytes(),
+            mint_account.key.as_ref(),
+            &[bump_seed],
+        ],
+        *mint_account.key,
+    )?;
+
+    // The first user of a mint pays for the vault's token account; later ones reuse it
+    invoke(
+        &create_associated_token_account_idempotent(
+            user_account.key,
+            vault_account.key,
+            mint_account.key,
+            token_program.key,
+        ),
+        &[
+            user_account.clone(),
+            vault_token_account.clone(),
+            vault_account.clone(),
+            mint_account.clone(),
+            system_program.clone(),
+            token_program.clone(),
+            associated_token_program.clone(),
+        ],
+    )?;
+
+    msg!("Token account initialized for mint {}", mint_account.key);
+    Ok(())
+}
+
+// Deposit token function
+fn process_deposit_token(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let source_token_account = next_account_info(account_info_iter)?;
+    let vault_token_account = next_account_info(account_info_iter)?;
+    let mint_account = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let decimals = load_mint(mint_account, token_program)?;
+    let user_data = load_token_user_data(
+        program_id,
+        user_account,
+        user_data_account,
+        vault_id,
+        mint_account.key,
+    )?;
+
+    // Verify the vault token account is correct
+    let expected_vault_token_account =
+        find_vault_token_address(vault_id, mint_account.key, token_program.key, program_id);
+    if expected_vault_token_account != *vault_token_account.key {
+        return Err(DepositError::WrongTokenAccount.into());
+    }
+
+    // Refuse deposits while they are paused
+    if VaultConfig::load_initialized(config_account, vault_id, program_id)?.deposits_paused {
+        return Err(DepositError::DepositsPaused.into());
+    }
+
+    // Transfer tokens from the user to the vault; a Token-2022 mint with a
+    // transfer hook gets its extra accounts from the remaining accounts
+    let vault_before = token_amount(vault_token_account)?;
+    invoke_transfer_checked(
+        token_program.key,
+        source_token_account.clone(),
+        mint_account.clone(),
+        vault_token_account.clone(),
+        user_account.clone(),
+        account_info_iter.as_slice(),
+        amount,
+        decimals,
+        &[],
+    )?;
+
+    // Credit what the vault actually received, which a transfer fee makes less than `amount`
+    let received = token_amount(vault_token_account)?
+        .checked_sub(vault_before)
+        .ok_or(DepositError::ArithmeticOverflow)?;
+    let user_data = apply_deposit(&user_data, received, None, Clock::get()?.unix_timestamp)?;
+
+    // Update user account balance
+    user_data.save(user_data_account)?;
+    emit_balance_change(
+        Event::DepositToken,
+        vault_id,
+        user_account.key,
+        received,
+        user_data.balance,
+    )?;
+
+    if received < amount {
+        msg!("Transfer fee withheld {} tokens", amount - received);
+    }
+    msg!("Deposited {} tokens of mint {}", received, mint_account.key);
+    Ok(())
+}
+
+// Withdraw token function
+fn process_withdraw_token(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let destination_token_account = next_account_info(account_info_iter)?;
+    let vault_token_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let mint_account = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let decimals = load_mint(mint_account, token_program)?;
+    let user_data = load_token_user_data(
+        program_id,
+        user_account,
+        user_data_account,
+        vault_id,
+        mint_account.key,
+    )?;
+
+    // Verify the vault and its token account are correct
+    let config = VaultConfig::load(config_account, vault_id, program_id)?;
+    let (expected_vault_account, vault_bump) = config.vault_address(vault_id, program_id);
+    if expected_vault_account != *vault_account.key {
+        return Err(DepositError::WrongVaultPda.into());
+    }
+    let expected_vault_token_account =
+        find_vault_token_address(vault_id, mint_account.key, token_program.key, program_id);
+    if expected_vault_token_account != *vault_token_account.key {
+        return Err(DepositError::WrongTokenAccount.into());
+    }
+
+    // Token balances can't be requested, so a cooldown closes this way out
+    config.check_withdrawal(false)?;
+
+    // Debit before the transfer, as in process_withdraw
+    let clock = Clock::get()?;
+    let user_data = apply_withdraw(&user_data, amount, clock.unix_timestamp)?;
+    let user_data = config.apply_withdraw_limits(user_data, amount, clock.epoch)?;
+    if config.epoch_withdraw_limit > 0 && user_data_account.data_len() < UserAccount::LEN {
+        // An older, shorter account couldn't record the epoch; the owner grows it
+        // through the system program passed among the trailing accounts
+        let system_program = account_info_iter
+            .as_slice()
+            .iter()
+            .find(|account| *account.key == solana_program::system_program::id())
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        grow_user_data_account(user_account, user_data_account, system_program)?;
+    }
+    user_data.save(user_data_account)?;
+
+    // Transfer tokens from the vault to the destination, signed by the vault PDA;
+    // any transfer fee comes out of what the destination receives
+    invoke_transfer_checked(
+        token_program.key,
+        vault_token_account.clone(),
+        mint_account.clone(),
+        destination_token_account.clone(),
+        vault_account.clone(),
+        account_info_iter.as_slice(),
+        amount,
+        decimals,
+        &[&[b"vault", &vault_id.to_le_bytes(), &[vault_bump]]],
+    )?;
+
+    // After the transfer, whose CPI would otherwise clear the return data
+    emit_balance_change(
+        Event::WithdrawToken,
+        vault_id,
+        user_account.key,
+        amount,
+        user_data.balance,
+    )?;
+    msg!("Withdrawn {} tokens of mint {}", amount, mint_account.key);
+    Ok(())
+}
+
+// Verify the accounts every wSOL instruction shares: the classic token program
+// and its native mint, the owner's native-mint balance and the vault's wSOL account
+fn load_wrapped_user_data(
+    program_id: &Pubkey,
+    user_account: &AccountInfo,
+    user_data_account: &AccountInfo,
+    vault_token_account: &AccountInfo,
+    mint_account: &AccountInfo,
+    token_program: &AccountInfo,
+    vault_id: u16,
+) -> Result<UserAccount, ProgramError> {
+    if *token_program.key != spl_token::id() {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if *mint_account.key != spl_token::native_mint::id() {
+        return Err(DepositError::WrongMint.into());
+    }
+
+    let user_data = load_token_user_data(
+        program_id,
+        user_account,
+        user_data_account,
+        vault_id,
+        mint_account.key,
+    )?;
+
+    let expected_vault_token_account =
+        find_vault_token_address(vault_id, mint_account.key, token_program.key, program_id);
+    if expected_vault_token_account != *vault_token_account.key {
+        return Err(DepositError::WrongTokenAccount.into());
+    }
+    Ok(user_data)
+}
+
+// Deposit wrapped function
+fn process_deposit_wrapped(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let vault_token_account = next_account_info(account_info_iter)?;
+    let mint_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let user_data = load_wrapped_user_data(
+        program_id,
+        user_account,
+        user_data_account,
+        vault_token_account,
+        mint_account,
+        token_program,
+        vault_id,
+    )?;
+
+    // Refuse deposits while they are paused
+    if VaultConfig::load_initialized(config_account, vault_id, program_id)?.deposits_paused {
+        return Err(DepositError::DepositsPaused.into());
+    }
+
+    let user_data = apply_deposit(&user_data, amount, None, Clock::get()?.unix_timestamp)?;
+
+    // Wrap: move the lamports onto the vault's wSOL account, then have the
+    // token program count them into its token amount
+    invoke(
+        &system_instruction::transfer(user_account.key, vault_token_account.key, amount),
+        &[
+            user_account.clone(),
+            vault_token_account.clone(),
+            system_program.clone(),
+        ],
+    )?;
+    invoke(
+        &spl_token::instruction::sync_native(token_program.key, vault_token_account.key)?,
+        &[vault_token_account.clone(), token_program.clone()],
+    )?;
 
-    /// Вывод средств
-    /// 0. `[signer]` Пользователь, который выводит средства
-    /// 1. `[writable]` Аккаунт данных пользователя (PDA)
-    /// 2. `[writable]` Vault аккаунт программы (PDA)
-    /// 3. `[]` System program
-    Withdraw { amount: u64 },
-}
+    // Update user account balance
+    user_data.save(user_data_account)?;
+    emit_balance_change(
+        Event::DepositWrapped,
+        vault_id,
+        user_account.key,
+        amount,
+        user_data.balance,
+    )?;
 
-// Define the data structure for user account
-#[derive(BorshSerialize, BorshDeserialize, Debug)]
-pub struct UserAccount {
-    pub owner: Pubkey,
-    pub balance: u64,
+    msg!("Wrapped and deposited {} lamports", amount);
+    Ok(())
 }
 
-// Program entrypoint
-entrypoint!(process_instruction);
-
-// Process instruction function
-pub fn process_instruction(
+// Withdraw unwrapped function
+fn process_withdraw_unwrapped(
     program_id: &Pubkey,
     accounts: &[AccountInfo],
-    instruction_data: &[u8],
+    vault_id: u16,
+    amount: u64,
 ) -> ProgramResult {
-    let instruction = DepositInstruction::try_from_slice(instruction_data)?;
-
-    match instruction {
-        DepositInstruction::InitializeAccount => process_initialize_account(program_id, accounts),
-        DepositInstruction::Deposit { amount } => process_deposit(program_id, accounts, amount),
-        DepositInstruction::Withdraw { amount } => process_withdraw(program_id, accounts, amount),
-    }
-}
-
-// Initialize account function
-fn process_initialize_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get the accounts
     let user_account = next_account_info(account_info_iter)?;
     let user_data_account = next_account_info(account_info_iter)?;
+    let vault_token_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let unwrap_account = next_account_info(account_info_iter)?;
+    let mint_account = next_account_info(account_info_iter)?;
     let system_program = next_account_info(account_info_iter)?;
+    let token_program = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
 
     // Verify the user is a signer
     if !user_account.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
 
-    // Derive the PDA for user data account
-    let (expected_user_data_account, bump_seed) = Pubkey::find_program_address(
-        &[b"user-account", user_account.key.as_ref()],
+    let user_data = load_wrapped_user_data(
         program_id,
-    );
+        user_account,
+        user_data_account,
+        vault_token_account,
+        mint_account,
+        token_program,
+        vault_id,
+    )?;
 
-    // Verify the user data account is the expected PDA
-    if expected_user_data_account != *user_data_account.key {
-        return Err(ProgramError::InvalidAccountData);
+    // Verify the vault and the unwrap account are correct
+    let config = VaultConfig::load(config_account, vault_id, program_id)?;
+    let (expected_vault_account, vault_bump) = config.vault_address(vault_id, program_id);
+    if expected_vault_account != *vault_account.key {
+        return Err(DepositError::WrongVaultPda.into());
+    }
+    let (expected_unwrap_account, unwrap_bump) =
+        find_unwrap_address(user_account.key, vault_id, program_id);
+    if expected_unwrap_account != *unwrap_account.key {
+        return Err(DepositError::WrongTokenAccount.into());
     }
 
-    // Calculate the size of the user data account
-    let user_data_size = std::mem::size_of::<UserAccount>();
+    // wSOL balances can't be requested, so a cooldown closes this way out
+    config.check_withdrawal(false)?;
 
-    // Calculate the rent required for the account
+    // Debit before the transfer, as in process_withdraw
+    let clock = Clock::get()?;
+    let user_data = apply_withdraw(&user_data, amount, clock.unix_timestamp)?;
+    let user_data = config.apply_withdraw_limits(user_data, amount, clock.epoch)?;
+    if config.epoch_withdraw_limit > 0 {
+        // An older, shorter account couldn't record the epoch
+        grow_user_data_account(user_account, user_data_account, system_program)?;
+    }
+    user_data.save(user_data_account)?;
+
+    // Create the unwrap account at the user's expense; closing it below refunds the rent.
+    // Its address is known ahead, so as with the vault in process_initialize_vault it is
+    // topped up rather than created, which would fail once someone sent it dust
     let rent = Rent::get()?;
-    let rent_lamports = rent.minimum_balance(user_data_size);
+    let rent_shortfall = rent
+        .minimum_balance(spl_token::state::Account::LEN)
+        .saturating_sub(unwrap_account.lamports());
+    if rent_shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(user_account.key, unwrap_account.key, rent_shortfall),
+            &[
+                user_account.clone(),
+                unwrap_account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+    }
+    let unwrap_seeds: &[&[u8]] = &[
+        b"unwrap",
+        user_account.key.as_ref(),
+        &vault_id.to_le_bytes(),
+        &[unwrap_bump],
+    ];
+    invoke_signed(
+        &system_instruction::allocate(unwrap_account.key, spl_token::state::Account::LEN as u64),
+        &[unwrap_account.clone(), system_program.clone()],
+        &[unwrap_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(unwrap_account.key, token_program.key),
+        &[unwrap_account.clone(), system_program.clone()],
+        &[unwrap_seeds],
+    )?;
+    invoke(
+        &spl_token::instruction::initialize_account3(
+            token_program.key,
+            unwrap_account.key,
+            mint_account.key,
+            vault_account.key,
+        )?,
+        &[unwrap_account.clone(), mint_account.clone()],
+    )?;
 
-    // Create the user data account
+    // Move the wSOL over and close the account, paying its lamports to the user
+    let vault_seeds: &[&[u8]] = &[b"vault", &vault_id.to_le_bytes(), &[vault_bump]];
     invoke_signed(
-        &system_instruction::create_account(
+        &spl_token::instruction::transfer(
+            token_program.key,
+            vault_token_account.key,
+            unwrap_account.key,
+            vault_account.key,
+            &[],
+            amount,
+        )?,
+        &[
+            vault_token_account.clone(),
+            unwrap_account.clone(),
+            vault_account.clone(),
+            token_program.clone(),
+        ],
+        &[vault_seeds],
+    )?;
+    invoke_signed(
+        &spl_token::instruction::close_account(
+            token_program.key,
+            unwrap_account.key,
             user_account.key,
-            user_data_account.key,
-            rent_lamports,
-            user_data_size as u64,
-            program_id,
-        ),
+            vault_account.key,
+            &[],
+        )?,
         &[
+            unwrap_account.clone(),
             user_account.clone(),
-            user_data_account.clone(),
-            system_program.clone(),
+            vault_account.clone(),
+            token_program.clone(),
         ],
-        &[&[b"user-account", user_account.key.as_ref(), &[bump_seed]]],
+        &[vault_seeds],
     )?;
 
-    // Initialize the user data account
-    let user_data = UserAccount {
-        owner: *user_account.key,
-        balance: 0,
-    };
+    // After the transfers, whose CPIs would otherwise clear the return data
+    emit_balance_change(
+        Event::WithdrawUnwrapped,
+        vault_id,
+        user_account.key,
+        amount,
+        user_data.balance,
+    )?;
+    msg!("Unwrapped and withdrawn {} lamports", amount);
+    Ok(())
+}
 
-    // Serialize the data and store it in the account
-    user_data.serialize(&mut &mut user_data_account.data.borrow_mut()[..])?;
+// Initialize vault function
+fn process_initialize_vault(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let payer_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the payer is a signer
+    if !payer_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Verify the vault account is correct and still a plain system account
+    let (expected_vault_account, vault_bump) = find_vault_address(vault_id, program_id);
+    if expected_vault_account != *vault_account.key {
+        return Err(DepositError::WrongVaultPda.into());
+    }
+    if *vault_account.owner != solana_program::system_program::id() {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let mut config = VaultConfig::load(config_account, vault_id, program_id)?;
+    let record_bump = !config_account.data_is_empty() && config.vault_bump != vault_bump;
+
+    // A system transfer creates the vault even if someone already sent it dust,
+    // where create_account would fail
+    let rent = Rent::get()?;
+    let rent_shortfall = rent
+        .minimum_balance(vault_account.data_len())
+        .saturating_sub(vault_account.lamports());
+    if rent_shortfall == 0 && !record_bump {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    if rent_shortfall > 0 {
+        invoke(
+            &system_instruction::transfer(payer_account.key, vault_account.key, rent_shortfall),
+            &[
+                payer_account.clone(),
+                vault_account.clone(),
+                system_program.clone(),
+            ],
+        )?;
+        msg!("Vault {} funded with {} lamports", vault_id, rent_shortfall);
+    }
 
-    msg!("User account initialized");
+    if record_bump {
+        grow_config(payer_account, config_account, system_program)?;
+        config.vault_bump = vault_bump;
+        config.save(config_account)?;
+        msg!("Vault {} bump {} recorded", vault_id, vault_bump);
+    }
     Ok(())
 }
 
-// Deposit function
-fn process_deposit(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+// Close account function
+fn process_close_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Verify the signer owns the account
+    let user_data = UserAccount::load(user_data_account, program_id)?;
+    if user_data.owner != *user_account.key {
+        return Err(DepositError::Unauthorized.into());
+    }
+
+    // Verify the user data account is the PDA for the mint it records
+    let (expected_user_data_account, _) =
+        find_user_data_address(user_account.key, vault_id, &user_data.mint, program_id);
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    if user_data.balance != 0 {
+        return Err(DepositError::AccountNotEmpty.into());
+    }
+
+    // A fresh account starts a fresh window, so closing would reset the daily limit
+    let config = VaultConfig::load(config_account, vault_id, program_id)?;
+    let window_expired = Clock::get()?
+        .unix_timestamp
+        .saturating_sub(user_data.deposit_window_start)
+        >= DEPOSIT_WINDOW_SECONDS;
+    // An account SweepDust emptied was closed out by the admin, not to dodge the limit
+    let window_open = user_data.deposited_in_window > 0 && !window_expired;
+    if config.daily_deposit_limit > 0 && window_open && !user_data.closeable {
+        return Err(DepositError::DepositWindowOpen.into());
+    }
+
+    let rent_lamports = close_user_data_account(user_data_account, user_account)?;
+    msg!("User account closed, {} lamports returned", rent_lamports);
+    Ok(())
+}
+
+// Transfer ownership function; records the proposal for the new owner to accept
+fn process_transfer_ownership(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    new_owner: Pubkey,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
     // Get the accounts
     let user_account = next_account_info(account_info_iter)?;
     let user_data_account = next_account_info(account_info_iter)?;
-    let vault_account = next_account_info(account_info_iter)?;
     let system_program = next_account_info(account_info_iter)?;
 
     // Verify the user is a signer
@@ -141,54 +4690,190 @@ fn process_deposit(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -
         return Err(ProgramError::MissingRequiredSignature);
     }
 
-    // Derive the PDA for user data account
-    let (expected_user_data_account, _) = Pubkey::find_program_address(
-        &[b"user-account", user_account.key.as_ref()],
-        program_id,
-    );
+    // Verify the signer owns the account
+    let mut user_data = UserAccount::load(user_data_account, program_id)?;
+    if user_data.owner != *user_account.key {
+        return Err(DepositError::Unauthorized.into());
+    }
 
-    // Verify the user data account is the expected PDA
+    // Verify the user data account is the PDA for the mint it records
+    let (expected_user_data_account, _) =
+        find_user_data_address(user_account.key, vault_id, &user_data.mint, program_id);
     if expected_user_data_account != *user_data_account.key {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(DepositError::WrongUserPda.into());
     }
 
-    // Verify the vault account is correct
-    let (expected_vault_account, _) = Pubkey::find_program_address(
-        &[b"vault"],
-        program_id,
-    );
+    // The account would have to move onto itself
+    if new_owner == *user_account.key {
+        return Err(ProgramError::InvalidArgument);
+    }
 
-    if expected_vault_account != *vault_account.key {
-        return Err(ProgramError::InvalidAccountData);
+    // An older, shorter account has no room to record the proposal
+    grow_user_data_account(user_account, user_data_account, system_program)?;
+    user_data.pending_owner = new_owner;
+    user_data.save(user_data_account)?;
+
+    if new_owner == Pubkey::default() {
+        msg!("Ownership transfer cancelled");
+    } else {
+        msg!("Ownership transfer to {} proposed", new_owner);
     }
+    Ok(())
+}
 
-    // Transfer SOL from user to vault
-    invoke(
-        &system_instruction::transfer(user_account.key, vault_account.key, amount),
-        &[
-            user_account.clone(),
-            vault_account.clone(),
-            system_program.clone(),
-        ],
+// Accept ownership function; moves the account to the new owner's PDA
+fn process_accept_ownership(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let new_owner_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let new_user_data_account = next_account_info(account_info_iter)?;
+    let owner_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+
+    // Verify the new owner is a signer
+    if !new_owner_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Only the proposed owner may accept, and the rent goes back to the current one
+    let user_data = UserAccount::load(user_data_account, program_id)?;
+    if user_data.pending_owner == Pubkey::default()
+        || user_data.pending_owner != *new_owner_account.key
+    {
+        return Err(DepositError::Unauthorized.into());
+    }
+    if user_data.owner != *owner_account.key {
+        return Err(DepositError::WrongOwner.into());
+    }
+
+    // Verify both user data accounts are the PDAs for the mint the account records
+    let (expected_user_data_account, _) =
+        find_user_data_address(owner_account.key, vault_id, &user_data.mint, program_id);
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+    let (expected_new_user_data_account, bump_seed) =
+        find_user_data_address(new_owner_account.key, vault_id, &user_data.mint, program_id);
+    if expected_new_user_data_account != *new_user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    // The new owner can't already hold an account of this kind in the vault
+    if !new_user_data_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    let vault_seed = vault_id.to_le_bytes();
+    let bump = [bump_seed];
+    let mut seeds: Vec<&[u8]> = vec![b"user-account", new_owner_account.key.as_ref(), &vault_seed];
+    if user_data.mint != Pubkey::default() {
+        seeds.push(user_data.mint.as_ref());
+    }
+    seeds.push(&bump);
+    create_user_data_account(
+        program_id,
+        new_owner_account,
+        new_user_data_account,
+        system_program,
+        &seeds,
+        user_data.mint,
     )?;
 
-    // Update user account balance
-    let mut user_data = UserAccount::try_from_slice(&user_data_account.data.borrow())?;
-    user_data.balance += amount;
-    user_data.serialize(&mut &mut user_data_account.data.borrow_mut()[..])?;
+    // Everything but the owner carries over, so the daily limit, vesting and any
+    // lockup still apply; the previous owner's delegate has no claim on the new owner's
+    // funds, and their withdrawal request is left for the new owner to make afresh
+    let moved = UserAccount {
+        owner: *new_owner_account.key,
+        pending_owner: Pubkey::default(),
+        delegate: Pubkey::default(),
+        delegated_amount: 0,
+        pending_withdrawal: 0,
+        withdraw_requested_at: 0,
+        ..user_data
+    };
+    moved.save(new_user_data_account)?;
+    close_user_data_account(user_data_account, owner_account)?;
+
+    msg!("Ownership transferred from {} to {}", owner_account.key, new_owner_account.key);
+    Event::Ownership(OwnershipEvent {
+        vault_id,
+        old_owner: *owner_account.key,
+        new_owner: *new_owner_account.key,
+        balance: moved.balance,
+    })
+    .emit()
+}
+
+// Set delegate function; RevokeDelegate sets no delegate with no allowance
+fn process_set_delegate(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    delegate: Pubkey,
+    cap: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let user_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+
+    // Verify the user is a signer
+    if !user_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
 
-    msg!("Deposited {} lamports", amount);
+    // Derive the PDA for user data account
+    let (expected_user_data_account, _) =
+        find_user_account_address(user_account.key, vault_id, program_id);
+
+    // Verify the user data account is the expected PDA
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    // Verify the signer owns the account
+    let mut user_data = UserAccount::load(user_data_account, program_id)?;
+    if user_data.owner != *user_account.key {
+        return Err(DepositError::Unauthorized.into());
+    }
+
+    // An older, shorter account has no room to record a delegate; nothing to grow for a revoke
+    if delegate != Pubkey::default() {
+        let system_program = next_account_info(account_info_iter)?;
+        grow_user_data_account(user_account, user_data_account, system_program)?;
+    }
+    user_data.delegate = delegate;
+    user_data.delegated_amount = cap;
+    user_data.save(user_data_account)?;
+
+    if delegate == Pubkey::default() {
+        msg!("Delegate revoked");
+    } else {
+        msg!("Delegate {} may withdraw up to {} lamports", delegate, cap);
+    }
     Ok(())
 }
 
-// Withdraw function
-fn process_withdraw(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+// Request withdraw function; a request for 0 cancels the pending one
+fn process_request_withdraw(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    amount: u64,
+) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    
+
     // Get the accounts
     let user_account = next_account_info(account_info_iter)?;
     let user_data_account = next_account_info(account_info_iter)?;
-    let vault_account = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
     let system_program = next_account_info(account_info_iter)?;
 
     // Verify the user is a signer
@@ -197,47 +4882,165 @@ fn process_withdraw(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64)
     }
 
     // Derive the PDA for user data account
-    let (expected_user_data_account, _) = Pubkey::find_program_address(
-        &[b"user-account", user_account.key.as_ref()],
-        program_id,
-    );
+    let (expected_user_data_account, _) =
+        find_user_account_address(user_account.key, vault_id, program_id);
 
     // Verify the user data account is the expected PDA
     if expected_user_data_account != *user_data_account.key {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(DepositError::WrongUserPda.into());
     }
 
-    // Verify the vault account is correct
-    let (expected_vault_account, vault_bump) = Pubkey::find_program_address(
-        &[b"vault"],
-        program_id,
-    );
+    // Verify the signer owns the account
+    let mut user_data = UserAccount::load(user_data_account, program_id)?;
+    if user_data.owner != *user_account.key {
+        return Err(DepositError::Unauthorized.into());
+    }
+
+    // Lockups, vesting and the residual minimum are checked when the request is
+    // claimed; only an amount that can never be paid is refused up front
+    if amount > user_data.balance {
+        return Err(DepositError::InsufficientBalance.into());
+    }
+    let config = VaultConfig::load(config_account, vault_id, program_id)?;
+
+    // An older, shorter account has no room to record the request
+    grow_user_data_account(user_account, user_data_account, system_program)?;
+    let now = Clock::get()?.unix_timestamp;
+    user_data.pending_withdrawal = amount;
+    user_data.withdraw_requested_at = if amount > 0 { now } else { 0 };
+    user_data.save(user_data_account)?;
+
+    if amount == 0 {
+        msg!("Withdrawal request cancelled");
+    } else {
+        msg!(
+            "Requested a withdrawal of {} lamports, claimable from {}",
+            amount,
+            now.saturating_add(config.withdraw_cooldown)
+        );
+    }
+    Ok(())
+}
+
+// Delegated withdraw function
+fn process_delegated_withdraw(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    vault_id: u16,
+    amount: u64,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    // Get the accounts
+    let delegate_account = next_account_info(account_info_iter)?;
+    let user_data_account = next_account_info(account_info_iter)?;
+    let vault_account = next_account_info(account_info_iter)?;
+    let system_program = next_account_info(account_info_iter)?;
+    let config_account = next_account_info(account_info_iter)?;
+
+    // Verify the delegate is a signer
+    if !delegate_account.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Verify the user data account is the PDA of the owner it records
+    let user_data = UserAccount::load(user_data_account, program_id)?;
+    let (expected_user_data_account, _) =
+        find_user_account_address(&user_data.owner, vault_id, program_id);
+    if expected_user_data_account != *user_data_account.key {
+        return Err(DepositError::WrongUserPda.into());
+    }
+
+    // Verify the signer is the owner's delegate
+    if user_data.delegate == Pubkey::default() || user_data.delegate != *delegate_account.key {
+        return Err(DepositError::Unauthorized.into());
+    }
 
+    // Verify the vault account is correct
+    let config = VaultConfig::load(config_account, vault_id, program_id)?;
+    let (expected_vault_account, vault_bump) = config.vault_address(vault_id, program_id);
     if expected_vault_account != *vault_account.key {
-        return Err(ProgramError::InvalidAccountData);
+        return Err(DepositError::WrongVaultPda.into());
     }
 
-    // Verify user has enough balance
-    let mut user_data = UserAccount::try_from_slice(&user_data_account.data.borrow())?;
-    if user_data.balance < amount {
-        return Err(ProgramError::InsufficientFunds);
+    // A cooldown leaves the owner's requests as the only way out, so a delegate
+    // can't get around it
+    config.check_withdrawal(false)?;
+
+    // The trailing accounts follow the same rules as for Withdraw
+    let clock = Clock::get()?;
+    let now = clock.unix_timestamp;
+    let interest_index = config.interest_index_at(now)?;
+    let (rewards_account, fee_vault_account, stats_account) =
+        next_withdraw_accounts(account_info_iter, &config, interest_index)?;
+
+    // Pay the owner the interest earned so far, as Withdraw does, so the fee tier
+    // and residual balance see the same balance whoever withdraws
+    let mut user_data = user_data;
+    let mut paid = 0;
+    if let Some(rewards_account) = rewards_account.filter(|_| interest_index > 0) {
+        let owed = user_data.accrued_interest(interest_index, now);
+        paid = pay_interest(
+            rewards_account,
+            vault_account,
+            system_program,
+            vault_id,
+            program_id,
+            owed,
+        )?;
+        user_data = apply_interest(&user_data, interest_index, paid)?;
+
+        // The delegate pays to grow an older, shorter account that couldn't record the index
+        grow_user_data_account(delegate_account, user_data_account, system_program)?;
+        emit_balance_change(Event::Interest, vault_id, &user_data.owner, paid, user_data.balance)?;
     }
 
-    // Update user account balance
-    user_data.balance -= amount;
-    user_data.serialize(&mut &mut user_data_account.data.borrow_mut()[..])?;
+    // The fee applies as it does to the owner, so delegating can't avoid it
+    let fee = config.withdraw_fee(amount, user_data.balance);
+
+    // Debit before the transfer, as Withdraw does
+    let user_data = apply_delegated_withdraw(&user_data, amount, now)?;
+    let user_data = config.apply_withdraw_limits(user_data, amount, clock.epoch)?;
+    if config.epoch_withdraw_limit > 0 {
+        // The delegate pays to grow an older, shorter account that couldn't record the epoch
+        grow_user_data_account(delegate_account, user_data_account, system_program)?;
+    }
+    user_data.save(user_data_account)?;
+    if let Some(stats_account) = stats_account {
+        record_tvl_change(stats_account, vault_id, program_id, paid, amount)?;
+    }
 
-    // Transfer SOL from vault to user
     invoke_signed(
-        &system_instruction::transfer(vault_account.key, user_account.key, amount),
+        &system_instruction::transfer(vault_account.key, delegate_account.key, amount - fee),
         &[
             vault_account.clone(),
-            user_account.clone(),
+            delegate_account.clone(),
             system_program.clone(),
         ],
-        &[&[b"vault", &[vault_bump]]],
+        &[&[b"vault", &vault_id.to_le_bytes(), &[vault_bump]]],
     )?;
+    if let Some(fee_vault_account) = fee_vault_account {
+        collect_withdraw_fee(
+            vault_account,
+            fee_vault_account,
+            system_program,
+            vault_id,
+            vault_bump,
+            program_id,
+            fee,
+        )?;
+    }
 
-    msg!("Withdrawn {} lamports", amount);
-    Ok(())
+    // After the transfers, whose CPIs would otherwise clear the return data
+    set_balance_return_data(amount, user_data.balance);
+    msg!("Delegate withdrew {} lamports, {} left", amount, user_data.delegated_amount);
+    Event::Withdraw(WithdrawEvent {
+        vault_id,
+        owner: user_data.owner,
+        destination: *delegate_account.key,
+        amount,
+        fee,
+        balance: user_data.balance,
+    })
+    .emit()
 }
@@ -3,37 +3,471 @@ mod tests {
     use super::*;
     use borsh::{BorshDeserialize, BorshSerialize};
     use solana_program::{
+        account_info::AccountInfo,
+        entrypoint::ProgramResult,
         instruction::{AccountMeta, Instruction},
+        program::{get_return_data, invoke, set_return_data},
+        program_error::ProgramError,
+        program_pack::Pack,
+        program_stubs::{self, SyscallStubs},
         pubkey::Pubkey,
         rent::Rent,
-        system_program,
+        system_instruction, system_program,
     };
-    use solana_program_test::{processor, ProgramTest};
+    use solana_program_test::{processor, BanksClient, ProgramTest};
     use solana_sdk::{
         account::Account,
+        clock::Clock,
+        instruction::InstructionError,
         signature::{Keypair, Signer},
-        transaction::Transaction,
+        signer::keypair::keypair_from_seed,
+        transaction::{Transaction, TransactionError},
+    };
+    use spl_tlv_account_resolution::{account::ExtraAccountMeta, state::ExtraAccountMetaList};
+    use spl_token_2022::extension::{
+        transfer_fee::instruction::initialize_transfer_fee_config, transfer_hook, ExtensionType,
+    };
+    use spl_transfer_hook_interface::{
+        get_extra_account_metas_address,
+        instruction::{ExecuteInstruction, TransferHookInstruction},
     };
     use std::str::FromStr;
+    use std::sync::{Mutex, Once, OnceLock};
 
     // Define the data structure for user account
     #[derive(BorshSerialize, BorshDeserialize, Debug)]
     pub struct UserAccount {
         pub owner: Pubkey,
         pub balance: u64,
+        pub vest_start: i64,
+        pub vest_end: i64,
+        pub vest_total: u64,
+        pub lifetime_deposited: u64,
+        pub lifetime_withdrawn: u64,
+        pub deposit_window_start: i64,
+        pub deposited_in_window: u64,
+        pub mint: Pubkey,
+        pub pending_owner: Pubkey,
+        pub delegate: Pubkey,
+        pub delegated_amount: u64,
+        pub unlock_ts: i64,
+        pub tranches: [LockupTranche; MAX_LOCKUP_TRANCHES],
+        pub interest_index: u128,
+        pub pending_withdrawal: u64,
+        pub withdraw_requested_at: i64,
+        pub withdraw_epoch: u64,
+        pub withdrawn_in_epoch: u64,
+        pub sweep_to_treasury: bool,
+        pub closeable: bool,
+    }
+
+    #[derive(BorshSerialize, BorshDeserialize, Debug, Default, Clone, Copy)]
+    pub struct LockupTranche {
+        pub amount: u64,
+        pub unlock_ts: i64,
+        pub multiplier_bps: u16,
     }
 
     // Define instruction types
     #[derive(BorshSerialize, BorshDeserialize, Debug)]
     pub enum DepositInstruction {
-        InitializeAccount,
-        Deposit { amount: u64 },
-        Withdraw { amount: u64 },
+        InitializeAccount { vault_id: u16 },
+        Deposit { vault_id: u16, amount: u64 },
+        Withdraw { vault_id: u16, amount: u64 },
+        GrowAccount { vault_id: u16, new_size: u32 },
+        InitializeConfig { vault_id: u16 },
+        SetPauseFlags { vault_id: u16, deposits: bool, withdrawals: bool },
+        DepositVested { vault_id: u16, amount: u64, vest_start: i64, vest_end: i64 },
+        BatchCredit { vault_id: u16, credits: Vec<(Pubkey, u64)> },
+        GrantRole { vault_id: u16, role: Role, authority: Pubkey },
+        RevokeRole { vault_id: u16, role: Role },
+        SweepDust { vault_id: u16, threshold: u64 },
+        SetDepositLimit { vault_id: u16, daily_limit: u64 },
+        GetWithdrawable { vault_id: u16 },
+        RenounceAdmin { vault_id: u16 },
+        WithdrawToRemaining { vault_id: u16, target_balance: u64 },
+        InitializeTokenAccount { vault_id: u16 },
+        DepositToken { vault_id: u16, amount: u64 },
+        WithdrawToken { vault_id: u16, amount: u64 },
+        DepositWrapped { vault_id: u16, amount: u64 },
+        WithdrawUnwrapped { vault_id: u16, amount: u64 },
+        InitializeVault { vault_id: u16 },
+        CloseAccount { vault_id: u16 },
+        WithdrawAll { vault_id: u16 },
+        TransferOwnership { vault_id: u16, new_owner: Pubkey },
+        AcceptOwnership { vault_id: u16 },
+        SetDelegate { vault_id: u16, delegate: Pubkey, cap: u64 },
+        RevokeDelegate { vault_id: u16 },
+        DelegatedWithdraw { vault_id: u16, amount: u64 },
+        DepositLocked { vault_id: u16, amount: u64, unlock_ts: i64 },
+        SetLockupTier { vault_id: u16, tier: u8, duration: i64, multiplier_bps: u16 },
+        DepositTiered { vault_id: u16, amount: u64, tier: u8 },
+        AccrueInterest { vault_id: u16 },
+        SetInterestRate { vault_id: u16, rate_bps: u16 },
+        SetParams {
+            vault_id: u16,
+            interest_rate_bps: u16,
+            withdraw_fee_bps: u16,
+            min_deposit: u64,
+        },
+        CollectFees { vault_id: u16 },
+        SetFeeTier { vault_id: u16, tier: u8, min_balance: u64, fee_bps: u16 },
+        SetMaxBalancePerUser { vault_id: u16, max_balance: u64 },
+        SetTvlCap { vault_id: u16, cap: u64 },
+        SetMinResidualBalance { vault_id: u16, min_balance: u64 },
+        RequestWithdraw { vault_id: u16, amount: u64 },
+        ClaimWithdraw { vault_id: u16 },
+        SetWithdrawCooldown { vault_id: u16, cooldown: i64 },
+        SetWithdrawLimit { vault_id: u16, epoch_limit: u64 },
+        DepositFor { vault_id: u16, amount: u64, beneficiary: Pubkey },
+        WithdrawTo { vault_id: u16, amount: u64 },
+        DepositWithMemo { vault_id: u16, amount: u64, memo: String },
+        WithdrawWithMemo { vault_id: u16, amount: u64, memo: String },
+        SetDustThreshold { vault_id: u16, threshold: u64 },
+        SetSweepToTreasury { vault_id: u16, enabled: bool },
     }
 
     // Assume your program ID
     const PROGRAM_ID: &str = "Your_Program_ID_Here";
 
+    // Pre-fund a vault with its rent-exempt reserve so deposits are credited in full
+    fn add_funded_vault(program_test: &mut ProgramTest, program_id: &Pubkey, vault_id: u16) {
+        let (vault_account, _) = find_vault_address(vault_id, program_id);
+        program_test.add_account(
+            vault_account,
+            Account {
+                lamports: Rent::default().minimum_balance(0),
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    // Stand-in for the program's upgrade authority, which must co-sign InitializeConfig
+    fn upgrade_authority() -> Keypair {
+        keypair_from_seed(&[7u8; 32]).unwrap()
+    }
+
+    // Record upgrade_authority() in the program's ProgramData account, as a deploy
+    // through the upgradeable loader would
+    fn add_program_data(program_test: &mut ProgramTest, program_id: &Pubkey) {
+        let (program_data_account, _) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        // Bincode layout of UpgradeableLoaderState::ProgramData: tag, slot, Some(authority)
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(upgrade_authority().pubkey().as_ref());
+        program_test.add_account(
+            program_data_account,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: bpf_loader_upgradeable::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    // Pre-create an initialized config with no admin, for tests that only need
+    // deposits to be accepted
+    fn add_config(program_test: &mut ProgramTest, program_id: &Pubkey, vault_id: u16) {
+        let (config_account, _) = find_config_address(vault_id, program_id);
+        let data = super::VaultConfig::default().try_to_vec().unwrap();
+        program_test.add_account(
+            config_account,
+            Account {
+                lamports: Rent::default().minimum_balance(data.len()),
+                data,
+                owner: *program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+    }
+
+    // Build the instruction that sets up a vault's config with `admin` as its admin;
+    // the transaction must also be signed by upgrade_authority()
+    fn initialize_config_instruction(
+        program_id: Pubkey,
+        admin: Pubkey,
+        vault_id: u16,
+    ) -> Instruction {
+        let (config_account, _) = find_config_address(vault_id, &program_id);
+        let (program_data_account, _) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(admin, true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(program_data_account, false),
+                AccountMeta::new_readonly(upgrade_authority().pubkey(), true),
+            ],
+            data: DepositInstruction::InitializeConfig { vault_id }.try_to_vec().unwrap(),
+        }
+    }
+
+    // The vault must keep its owner and never become executable, whatever the
+    // program's CPIs do to it
+    async fn assert_vault_invariants(
+        banks_client: &mut BanksClient,
+        vault_account: Pubkey,
+        expected_owner: Pubkey,
+    ) {
+        let vault = banks_client.get_account(vault_account).await.unwrap().unwrap();
+        assert_eq!(vault.owner, expected_owner);
+        assert!(!vault.executable);
+    }
+
+    // Every sol_log_data call made by any test once record_events() has run, since
+    // program-test's stubs print it rather than adding `Program data:` to the logs
+    static LOGGED_DATA: Mutex<Vec<Vec<Vec<u8>>>> = Mutex::new(Vec::new());
+    static PROGRAM_TEST_STUBS: OnceLock<Box<dyn SyscallStubs>> = OnceLock::new();
+
+    // Record sol_log_data and forward everything program-test's stubs implement
+    // to them
+    struct RecordingStubs;
+
+    impl RecordingStubs {
+        // Tests running in parallel may call in before the stubs being replaced are
+        // stored, so wait for them
+        fn inner(&self) -> &'static dyn SyscallStubs {
+            loop {
+                if let Some(stubs) = PROGRAM_TEST_STUBS.get() {
+                    return stubs.as_ref();
+                }
+                std::thread::yield_now();
+            }
+        }
+    }
+
+    impl SyscallStubs for RecordingStubs {
+        fn sol_log(&self, message: &str) {
+            self.inner().sol_log(message)
+        }
+        fn sol_log_data(&self, fields: &[&[u8]]) {
+            let recorded = fields.iter().map(|field| field.to_vec()).collect();
+            LOGGED_DATA.lock().unwrap().push(recorded);
+            self.inner().sol_log_data(fields)
+        }
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            self.inner().sol_invoke_signed(instruction, account_infos, signers_seeds)
+        }
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            self.inner().sol_get_clock_sysvar(var_addr)
+        }
+        fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+            self.inner().sol_get_epoch_schedule_sysvar(var_addr)
+        }
+        fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+            self.inner().sol_get_epoch_rewards_sysvar(var_addr)
+        }
+        fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+            self.inner().sol_get_fees_sysvar(var_addr)
+        }
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            self.inner().sol_get_rent_sysvar(var_addr)
+        }
+        fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+            self.inner().sol_get_last_restart_slot(var_addr)
+        }
+        fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+            self.inner().sol_get_return_data()
+        }
+        fn sol_set_return_data(&self, data: &[u8]) {
+            self.inner().sol_set_return_data(data)
+        }
+        fn sol_get_stack_height(&self) -> u64 {
+            self.inner().sol_get_stack_height()
+        }
+    }
+
+    // Start recording events. program-test installs its stubs on the first start,
+    // so this must run once a program test has started
+    fn record_events() {
+        static RECORDING: Once = Once::new();
+        RECORDING.call_once(|| {
+            let stubs = program_stubs::set_syscall_stubs(Box::new(RecordingStubs));
+            assert!(PROGRAM_TEST_STUBS.set(stubs).is_ok());
+        });
+    }
+
+    // Take the recorded sol_log_data calls `is_ours` picks out, leaving other tests' calls
+    fn take_logged_data(is_ours: impl Fn(&[Vec<u8>]) -> bool) -> Vec<Vec<Vec<u8>>> {
+        let mut logged = LOGGED_DATA.lock().unwrap();
+        let mut taken = Vec::new();
+        logged.retain(|fields| {
+            if is_ours(fields) {
+                taken.push(fields.clone());
+                false
+            } else {
+                true
+            }
+        });
+        taken
+    }
+
+    // Decode a recorded sol_log_data call into the event it logged
+    fn decode_event(fields: &[Vec<u8>]) -> Option<Event> {
+        let [name, data] = fields else {
+            return None;
+        };
+        let balance = || BalanceEvent::try_from_slice(data).ok();
+        match name.as_slice() {
+            b"init" => InitEvent::try_from_slice(data).ok().map(Event::Init),
+            b"deposit" => DepositEvent::try_from_slice(data).ok().map(Event::Deposit),
+            b"withdraw" => WithdrawEvent::try_from_slice(data).ok().map(Event::Withdraw),
+            b"deposit-token" => balance().map(Event::DepositToken),
+            b"withdraw-token" => balance().map(Event::WithdrawToken),
+            b"deposit-wrapped" => balance().map(Event::DepositWrapped),
+            b"withdraw-unwrapped" => balance().map(Event::WithdrawUnwrapped),
+            b"interest" => balance().map(Event::Interest),
+            b"credit" => balance().map(Event::Credit),
+            b"sweep-dust" => balance().map(Event::SweepDust),
+            b"ownership" => OwnershipEvent::try_from_slice(data).ok().map(Event::Ownership),
+            b"fees" => FeesEvent::try_from_slice(data).ok().map(Event::Fees),
+            b"memo" => MemoEvent::try_from_slice(data).ok().map(Event::Memo),
+            b"param" => ParamEvent::try_from_slice(data).ok().map(Event::Param),
+            b"admin" => AdminEvent::try_from_slice(data).ok().map(Event::Admin),
+            _ => None,
+        }
+    }
+
+    // Take the recorded events `is_ours` picks out, leaving other tests' events
+    fn take_events(is_ours: impl Fn(&Event) -> bool) -> Vec<Event> {
+        take_logged_data(|fields| decode_event(fields).is_some_and(|event| is_ours(&event)))
+            .iter()
+            .filter_map(|fields| decode_event(fields))
+            .collect()
+    }
+
+    // Take the events recorded for `vault_id`, which every event's encoding starts with
+    fn take_vault_events(vault_id: u16) -> Vec<Event> {
+        take_logged_data(|fields| {
+            decode_event(fields).is_some() && fields[1].starts_with(&vault_id.to_le_bytes())
+        })
+        .iter()
+        .filter_map(|fields| decode_event(fields))
+        .collect()
+    }
+
+    // Take the withdraw events recorded for `owner`'s balance
+    fn take_withdraw_events(owner: &Pubkey) -> Vec<WithdrawEvent> {
+        take_events(|event| matches!(event, Event::Withdraw(event) if event.owner == *owner))
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::Withdraw(event) => Some(event),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Build the instruction that initializes an owner's user account
+    fn initialize_instruction(program_id: Pubkey, owner: Pubkey, vault_id: u16) -> Instruction {
+        let (user_data_account, _) = find_user_account_address(&owner, vault_id, &program_id);
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::InitializeAccount { vault_id }.try_to_vec().unwrap(),
+        }
+    }
+
+    // Build an admin's SweepDust of `owner`'s account, up to the vault's own threshold
+    fn sweep_dust_instruction(
+        program_id: Pubkey,
+        admin: Pubkey,
+        owner: Pubkey,
+        vault_id: u16,
+    ) -> Instruction {
+        let (user_data_account, _) = find_user_account_address(&owner, vault_id, &program_id);
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(admin, true),
+                AccountMeta::new_readonly(find_config_address(vault_id, &program_id).0, false),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(owner, false),
+                AccountMeta::new(find_vault_address(vault_id, &program_id).0, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(find_stats_address(vault_id, &program_id).0, false),
+                AccountMeta::new(find_rewards_address(vault_id, &program_id).0, false),
+                AccountMeta::new(find_fee_vault_address(vault_id, &program_id).0, false),
+            ],
+            data: DepositInstruction::SweepDust { vault_id, threshold: u64::MAX }
+                .try_to_vec()
+                .unwrap(),
+        }
+    }
+
+    fn set_dust_threshold_instruction(
+        program_id: Pubkey,
+        admin: Pubkey,
+        vault_id: u16,
+        threshold: u64,
+    ) -> Instruction {
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(admin, true),
+                AccountMeta::new(find_config_address(vault_id, &program_id).0, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::SetDustThreshold { vault_id, threshold }
+                .try_to_vec()
+                .unwrap(),
+        }
+    }
+
+    // Build a deposit or withdraw instruction, which share the same accounts
+    fn transfer_instruction(
+        program_id: Pubkey,
+        owner: Pubkey,
+        instruction: DepositInstruction,
+    ) -> Instruction {
+        let vault_id = match instruction {
+            DepositInstruction::Deposit { vault_id, .. }
+            | DepositInstruction::Withdraw { vault_id, .. }
+            | DepositInstruction::WithdrawToRemaining { vault_id, .. }
+            | DepositInstruction::WithdrawAll { vault_id }
+            | DepositInstruction::ClaimWithdraw { vault_id }
+            | DepositInstruction::WithdrawTo { vault_id, .. }
+            | DepositInstruction::DepositWithMemo { vault_id, .. }
+            | DepositInstruction::WithdrawWithMemo { vault_id, .. }
+            | DepositInstruction::DepositVested { vault_id, .. }
+            | DepositInstruction::DepositLocked { vault_id, .. }
+            | DepositInstruction::DepositTiered { vault_id, .. } => vault_id,
+            _ => panic!("not a deposit or withdraw instruction"),
+        };
+        let (user_data_account, _) = find_user_account_address(&owner, vault_id, &program_id);
+        let (vault_account, _) = find_vault_address(vault_id, &program_id);
+        let (config_account, _) = find_config_address(vault_id, &program_id);
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(config_account, false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        }
+    }
+
     // Test initialize account
     #[tokio::test]
     async fn test_initialize_account() {
@@ -49,10 +483,7 @@ mod tests {
         let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
 
         // Derive user data account
-        let (user_data_account, _) = Pubkey::find_program_address(
-            &[b"user-account", payer.pubkey().as_ref()],
-            &program_id,
-        );
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
 
         // Create instruction
         let instruction = Instruction {
@@ -62,7 +493,7 @@ mod tests {
                 AccountMeta::new(user_data_account, false),
                 AccountMeta::new_readonly(system_program::id(), false),
             ],
-            data: DepositInstruction::InitializeAccount.try_to_vec().unwrap(),
+            data: DepositInstruction::InitializeAccount { vault_id: 0 }.try_to_vec().unwrap(),
         };
 
         // Create transaction
@@ -93,13 +524,15 @@ mod tests {
             program_id,
             processor!(process_instruction),
         );
+        add_config(&mut program_test, &program_id, 0);
 
-        // Add vault account
-        let (vault_account, vault_bump) = Pubkey::find_program_address(&[b"vault"], &program_id);
+        // Add vault account, already holding its rent-exempt reserve
+        let (vault_account, vault_bump) = find_vault_address(0, &program_id);
+        let vault_reserve = Rent::default().minimum_balance(0);
         program_test.add_account(
             vault_account,
             Account {
-                lamports: 0,
+                lamports: vault_reserve,
                 data: vec![],
                 owner: program_id,
                 executable: false,
@@ -111,10 +544,7 @@ mod tests {
         let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
 
         // Derive user data account
-        let (user_data_account, _) = Pubkey::find_program_address(
-            &[b"user-account", payer.pubkey().as_ref()],
-            &program_id,
-        );
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
 
         // First initialize the account
         let init_instruction = Instruction {
@@ -124,7 +554,7 @@ mod tests {
                 AccountMeta::new(user_data_account, false),
                 AccountMeta::new_readonly(system_program::id(), false),
             ],
-            data: DepositInstruction::InitializeAccount.try_to_vec().unwrap(),
+            data: DepositInstruction::InitializeAccount { vault_id: 0 }.try_to_vec().unwrap(),
         };
 
         let init_transaction = Transaction::new_signed_with_payer(
@@ -145,8 +575,9 @@ mod tests {
                 AccountMeta::new(user_data_account, false),
                 AccountMeta::new(vault_account, false),
                 AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(find_config_address(0, &program_id).0, false),
             ],
-            data: DepositInstruction::Deposit { amount }.try_to_vec().unwrap(),
+            data: DepositInstruction::Deposit { vault_id: 0, amount }.try_to_vec().unwrap(),
         };
 
         let deposit_transaction = Transaction::new_signed_with_payer(
@@ -165,7 +596,8 @@ mod tests {
 
         // Verify vault received the lamports
         let vault = banks_client.get_account(vault_account).await.unwrap().unwrap();
-        assert_eq!(vault.lamports, amount);
+        assert_eq!(vault.lamports, vault_reserve + amount);
+        assert_vault_invariants(&mut banks_client, vault_account, program_id).await;
     }
 
     // Test withdraw
@@ -178,13 +610,7683 @@ mod tests {
             program_id,
             processor!(process_instruction),
         );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        // Derive accounts
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let vault_reserve = Rent::default().minimum_balance(0);
+
+        // Initialize the account and deposit in one transaction; the vault is a
+        // system account, which is what withdraw signs for
+        let amount = 10_000_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(payer.pubkey(), true),
+                        AccountMeta::new(user_data_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                    ],
+                    data: DepositInstruction::InitializeAccount { vault_id: 0 }.try_to_vec().unwrap(),
+                },
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(payer.pubkey(), true),
+                        AccountMeta::new(user_data_account, false),
+                        AccountMeta::new(vault_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                        AccountMeta::new_readonly(find_config_address(0, &program_id).0, false),
+                    ],
+                    data: DepositInstruction::Deposit { vault_id: 0, amount }.try_to_vec().unwrap(),
+                },
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+
+        banks_client.process_transaction(transaction).await.unwrap();
+        assert_vault_invariants(&mut banks_client, vault_account, system_program::id()).await;
+
+        // Withdraw half of the deposit
+        let withdraw_amount = amount / 2;
+        let withdraw_instruction = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(find_config_address(0, &program_id).0, false),
+            ],
+            data: DepositInstruction::Withdraw { vault_id: 0, amount: withdraw_amount }
+                .try_to_vec()
+                .unwrap(),
+        };
+
+        let withdraw_transaction = Transaction::new_signed_with_payer(
+            &[withdraw_instruction],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+
+        banks_client.process_transaction(withdraw_transaction).await.unwrap();
+
+        // Verify the balance was reduced
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, amount - withdraw_amount);
+
+        // Verify the vault paid out the lamports
+        let vault = banks_client.get_account(vault_account).await.unwrap().unwrap();
+        assert_eq!(vault.lamports, vault_reserve + amount - withdraw_amount);
+        assert_vault_invariants(&mut banks_client, vault_account, system_program::id()).await;
+    }
+
+    // Test that vaults with different ids keep their funds apart
+    #[tokio::test]
+    async fn test_vault_isolation() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        for vault_id in [1, 2] {
+            add_config(&mut program_test, &program_id, vault_id);
+            add_funded_vault(&mut program_test, &program_id, vault_id);
+        }
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        // Initialize a user account in vaults 1 and 2 and deposit different amounts
+        let deposits = [(1u16, 3_000_000u64), (2u16, 5_000_000u64)];
+        let mut instructions = Vec::new();
+        for (vault_id, amount) in deposits {
+            let (user_data_account, _) =
+                find_user_account_address(&payer.pubkey(), vault_id, &program_id);
+            let (vault_account, _) = find_vault_address(vault_id, &program_id);
+            instructions.push(Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new(user_data_account, false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+                data: DepositInstruction::InitializeAccount { vault_id }.try_to_vec().unwrap(),
+            });
+            instructions.push(Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new(user_data_account, false),
+                    AccountMeta::new(vault_account, false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(find_config_address(vault_id, &program_id).0, false),
+                ],
+                data: DepositInstruction::Deposit { vault_id, amount }.try_to_vec().unwrap(),
+            });
+        }
+
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Each vault and user account only reflects its own deposit
+        for (vault_id, amount) in deposits {
+            let (user_data_account, _) =
+                find_user_account_address(&payer.pubkey(), vault_id, &program_id);
+            let (vault_account, _) = find_vault_address(vault_id, &program_id);
+
+            let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+            let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+            assert_eq!(user_data.balance, amount);
+
+            let vault = banks_client.get_account(vault_account).await.unwrap().unwrap();
+            assert_eq!(vault.lamports, Rent::default().minimum_balance(0) + amount);
+        }
+
+        // Withdrawing vault 1's balance through vault 2's accounts must fail
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 1, &program_id);
+        let (vault_account, _) = find_vault_address(2, &program_id);
+        let withdraw_instruction = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(find_config_address(2, &program_id).0, false),
+            ],
+            data: DepositInstruction::Withdraw { vault_id: 1, amount: 3_000_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+
+        let withdraw_transaction = Transaction::new_signed_with_payer(
+            &[withdraw_instruction],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+
+        assert!(banks_client.process_transaction(withdraw_transaction).await.is_err());
+    }
+
+    // Test that deposit and withdraw move lamports without creating or destroying any
+    #[tokio::test]
+    async fn test_lamport_conservation() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
 
         // Start program
         let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
 
         // Derive accounts
-        let (user_data_account, _) = Pubkey::find_program_address(
-            &[b"user-account", payer.pubkey().as_ref()],
-            &program_id,
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+
+        // Initialize the account; its rent is part of the system being tracked
+        let init_transaction = Transaction::new_signed_with_payer(
+            &[initialize_instruction(program_id, payer.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(init_transaction).await.unwrap();
+
+        let amount = 10_000_000;
+        let steps = [
+            DepositInstruction::Deposit { vault_id: 0, amount },
+            DepositInstruction::Withdraw { vault_id: 0, amount: amount / 4 },
+        ];
+
+        for step in steps {
+            let transaction = Transaction::new_signed_with_payer(
+                &[transfer_instruction(program_id, payer.pubkey(), step)],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            );
+            let fee = banks_client
+                .get_fee_for_message(transaction.message.clone())
+                .await
+                .unwrap()
+                .unwrap();
+
+            // Sum every account the program can touch before and after
+            let mut before = 0;
+            for account in [payer.pubkey(), user_data_account, vault_account] {
+                before += banks_client.get_balance(account).await.unwrap();
+            }
+
+            banks_client.process_transaction(transaction).await.unwrap();
+
+            let mut after = 0;
+            for account in [payer.pubkey(), user_data_account, vault_account] {
+                after += banks_client.get_balance(account).await.unwrap();
+            }
+
+            // Only the transaction fee may leave the system
+            assert_eq!(after + fee, before);
+        }
+
+        // The vault holds exactly what the user is credited with on top of its reserve
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, amount - amount / 4);
+        assert_eq!(
+            banks_client.get_balance(vault_account).await.unwrap(),
+            Rent::default().minimum_balance(0) + user_data.balance
+        );
+    }
+
+    // Test that many init, deposit and withdraw cycles for fresh owners leak no lamports
+    #[tokio::test]
+    async fn test_repeated_cycles_conserve_lamports() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let vault_reserve = banks_client.get_balance(vault_account).await.unwrap();
+        let user_rent = Rent::default().minimum_balance(super::UserAccount::LEN);
+        let payer_start = banks_client.get_balance(payer.pubkey()).await.unwrap();
+
+        let funding = 100_000_000;
+        let amount = 40_000_000;
+        let mut tracked = vec![payer.pubkey(), vault_account];
+        let mut total_fees = 0;
+        for _ in 0..8 {
+            // Each cycle funds a fresh owner who then deposits and withdraws everything
+            let owner = Keypair::new();
+            let (user_data_account, _) =
+                find_user_account_address(&owner.pubkey(), 0, &program_id);
+            tracked.extend([owner.pubkey(), user_data_account]);
+
+            let transaction = Transaction::new_signed_with_payer(
+                &[
+                    solana_program::system_instruction::transfer(
+                        &payer.pubkey(),
+                        &owner.pubkey(),
+                        funding,
+                    ),
+                    initialize_instruction(program_id, owner.pubkey(), 0),
+                    transfer_instruction(
+                        program_id,
+                        owner.pubkey(),
+                        DepositInstruction::Deposit { vault_id: 0, amount },
+                    ),
+                    transfer_instruction(
+                        program_id,
+                        owner.pubkey(),
+                        DepositInstruction::Withdraw { vault_id: 0, amount },
+                    ),
+                ],
+                Some(&payer.pubkey()),
+                &[&payer, &owner],
+                recent_blockhash,
+            );
+            let fee = banks_client
+                .get_fee_for_message(transaction.message.clone())
+                .await
+                .unwrap()
+                .unwrap();
+
+            let mut before = 0;
+            for account in &tracked {
+                before += banks_client.get_balance(*account).await.unwrap();
+            }
+            banks_client.process_transaction(transaction).await.unwrap();
+            let mut after = 0;
+            for account in &tracked {
+                after += banks_client.get_balance(*account).await.unwrap();
+            }
+
+            // Only the fee leaves the system, and the owner is down exactly the account's rent
+            assert_eq!(after + fee, before);
+            assert_eq!(
+                banks_client.get_balance(owner.pubkey()).await.unwrap(),
+                funding - user_rent
+            );
+            assert_eq!(banks_client.get_balance(user_data_account).await.unwrap(), user_rent);
+            assert_eq!(banks_client.get_balance(vault_account).await.unwrap(), vault_reserve);
+            total_fees += fee;
+        }
+
+        // Across the whole run the payer paid only the funding and the fees
+        assert_eq!(
+            banks_client.get_balance(payer.pubkey()).await.unwrap(),
+            payer_start - 8 * funding - total_fees
+        );
+    }
+
+    // Test growing the user account
+    #[tokio::test]
+    async fn test_grow_account() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        // Derive user data account
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        let grow_instruction = |new_size: u32| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::GrowAccount { vault_id: 0, new_size }.try_to_vec().unwrap(),
+        };
+
+        // Initialize and deposit, then grow the account
+        let amount = 1_000_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount },
+                ),
+                grow_instruction(512),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
         );
-        let (vault_account, _) = Pubkey::find_program_address(&[b"
\ No newline at end of file
+
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Verify the new space is zeroed and the existing data is intact
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(account.data.len(), 512);
+        assert!(account.data[super::UserAccount::LEN..].iter().all(|byte| *byte == 0));
+        let user_data = UserAccount::deserialize(&mut &account.data[..]).unwrap();
+        assert_eq!(user_data.owner, payer.pubkey());
+        assert_eq!(user_data.balance, amount);
+
+        // Verify the account is still rent-exempt at its new size
+        let rent = banks_client.get_rent().await.unwrap();
+        assert!(rent.is_exempt(account.lamports, account.data.len()));
+
+        // Deposits keep working on the grown account
+        let deposit_transaction = Transaction::new_signed_with_payer(
+            &[transfer_instruction(
+                program_id,
+                payer.pubkey(),
+                DepositInstruction::Deposit { vault_id: 0, amount },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(deposit_transaction).await.unwrap();
+
+        // Shrinking and growing past the cap are rejected
+        for new_size in [64, MAX_USER_ACCOUNT_SIZE as u32 + 1] {
+            let transaction = Transaction::new_signed_with_payer(
+                &[grow_instruction(new_size)],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            );
+            assert!(banks_client.process_transaction(transaction).await.is_err());
+        }
+    }
+
+    // Test that withdrawing the full balance leaves exactly zero
+    #[tokio::test]
+    async fn test_full_withdrawal_leaves_zero_balance() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        // Derive accounts
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let vault_before = banks_client.get_balance(vault_account).await.unwrap();
+
+        // Initialize, deposit and withdraw everything
+        let amount = 2_000_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount },
+                ),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Withdraw { vault_id: 0, amount },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Verify the balance is exactly zero and the vault is back where it started
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 0);
+        assert_eq!(banks_client.get_balance(vault_account).await.unwrap(), vault_before);
+
+        // A further withdrawal of a single lamport is rejected
+        let withdraw_transaction = Transaction::new_signed_with_payer(
+            &[transfer_instruction(
+                program_id,
+                payer.pubkey(),
+                DepositInstruction::Withdraw { vault_id: 0, amount: 1 },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+
+        let err = banks_client
+            .process_transaction(withdraw_transaction)
+            .await
+            .unwrap_err()
+            .unwrap();
+        assert_eq!(
+            err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::InsufficientBalance as u32)
+            )
+        );
+    }
+
+    // Test that withdrawing down to a target leaves exactly that balance
+    #[tokio::test]
+    async fn test_withdraw_to_remaining() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        // Derive accounts
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let vault_before = banks_client.get_balance(vault_account).await.unwrap();
+
+        // Initialize, deposit and withdraw everything above the target
+        let target_balance = 1_250_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 5_000_000 },
+                ),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::WithdrawToRemaining { vault_id: 0, target_balance },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Only the target stays behind, in both the record and the vault
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, target_balance);
+        assert_eq!(user_data.lifetime_withdrawn, 5_000_000 - target_balance);
+        assert_eq!(
+            banks_client.get_balance(vault_account).await.unwrap(),
+            vault_before + target_balance
+        );
+
+        // A target above the balance is rejected
+        let transaction = Transaction::new_signed_with_payer(
+            &[transfer_instruction(
+                program_id,
+                payer.pubkey(),
+                DepositInstruction::WithdrawToRemaining {
+                    vault_id: 0,
+                    target_balance: target_balance + 1,
+                },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::InsufficientBalance as u32)
+            )
+        );
+    }
+
+    // Test that WithdrawAll drains the balance as it stands when it executes
+    #[tokio::test]
+    async fn test_withdraw_all() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        // Derive accounts
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let vault_before = banks_client.get_balance(vault_account).await.unwrap();
+
+        // A deposit landing just before the drain is taken out with the rest
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 5_000_000 },
+                ),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 1_250_000 },
+                ),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::WithdrawAll { vault_id: 0 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 0);
+        assert_eq!(user_data.lifetime_withdrawn, 6_250_000);
+        assert_eq!(banks_client.get_balance(vault_account).await.unwrap(), vault_before);
+    }
+
+    // Test that a proposed owner, and only they, can take over an account with its funds
+    #[tokio::test]
+    async fn test_transfer_ownership() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // A separate owner, so its lamports move only by what the program does
+        let owner = Keypair::new();
+        let new_owner = Keypair::new();
+        let stranger = Keypair::new();
+        for keypair in [&owner, &new_owner, &stranger] {
+            program_test.add_account(
+                keypair.pubkey(),
+                Account {
+                    lamports: 1_000_000_000,
+                    ..Account::default()
+                },
+            );
+        }
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (user_data_account, _) = find_user_account_address(&owner.pubkey(), 0, &program_id);
+        let (new_user_data_account, _) =
+            find_user_account_address(&new_owner.pubkey(), 0, &program_id);
+        let amount = 3_000_000;
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, owner.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    owner.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount },
+                ),
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(owner.pubkey(), true),
+                        AccountMeta::new(user_data_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                    ],
+                    data: DepositInstruction::TransferOwnership {
+                        vault_id: 0,
+                        new_owner: new_owner.pubkey(),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                },
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.owner, owner.pubkey());
+        assert_eq!(user_data.pending_owner, new_owner.pubkey());
+        let rent_lamports = account.lamports;
+
+        let accept_instruction = |accepter: Pubkey| {
+            let (accepter_data_account, _) = find_user_account_address(&accepter, 0, &program_id);
+            Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(accepter, true),
+                    AccountMeta::new(user_data_account, false),
+                    AccountMeta::new(accepter_data_account, false),
+                    AccountMeta::new(owner.pubkey(), false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+                data: DepositInstruction::AcceptOwnership { vault_id: 0 }.try_to_vec().unwrap(),
+            }
+        };
+
+        // Nobody but the proposed owner can accept
+        let transaction = Transaction::new_signed_with_payer(
+            &[accept_instruction(stranger.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer, &stranger],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::Unauthorized as u32)
+            )
+        );
+
+        // Accepting moves the record to the new owner's PDA and refunds the old rent
+        let owner_before = banks_client.get_balance(owner.pubkey()).await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[accept_instruction(new_owner.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer, &new_owner],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        assert!(banks_client.get_account(user_data_account).await.unwrap().is_none());
+        assert_eq!(
+            banks_client.get_balance(owner.pubkey()).await.unwrap(),
+            owner_before + rent_lamports
+        );
+        let account = banks_client.get_account(new_user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.owner, new_owner.pubkey());
+        assert_eq!(user_data.pending_owner, Pubkey::default());
+        assert_eq!(user_data.balance, amount);
+        assert_eq!(user_data.lifetime_deposited, amount);
+
+        // The new owner withdraws the funds; the old owner no longer has an account
+        let transaction = Transaction::new_signed_with_payer(
+            &[transfer_instruction(
+                program_id,
+                new_owner.pubkey(),
+                DepositInstruction::Withdraw { vault_id: 0, amount },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &new_owner],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[transfer_instruction(
+                program_id,
+                owner.pubkey(),
+                DepositInstruction::Withdraw { vault_id: 0, amount: 1 },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        );
+        assert!(banks_client.process_transaction(transaction).await.is_err());
+    }
+
+    // Test that a delegate withdraws to itself within its cap until revoked
+    #[tokio::test]
+    async fn test_delegated_withdraw() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Keys other than the payer, so their lamports move only by what the program does
+        let delegate = Keypair::new();
+        let stranger = Keypair::new();
+        for keypair in [&delegate, &stranger] {
+            program_test.add_account(
+                keypair.pubkey(),
+                Account {
+                    lamports: 1_000_000_000,
+                    ..Account::default()
+                },
+            );
+        }
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (config_account, _) = find_config_address(0, &program_id);
+        let cap = 2_000_000;
+
+        let owner_instruction = |data: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: data.try_to_vec().unwrap(),
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 5_000_000 },
+                ),
+                owner_instruction(DepositInstruction::SetDelegate {
+                    vault_id: 0,
+                    delegate: delegate.pubkey(),
+                    cap,
+                }),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // The payer covers fees, and the signer receives the withdrawal
+        let delegated_withdraw = |signer: &Keypair, amount: u64| {
+            Transaction::new_signed_with_payer(
+                &[Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(signer.pubkey(), true),
+                        AccountMeta::new(user_data_account, false),
+                        AccountMeta::new(vault_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                        AccountMeta::new_readonly(config_account, false),
+                    ],
+                    data: DepositInstruction::DelegatedWithdraw { vault_id: 0, amount }
+                        .try_to_vec()
+                        .unwrap(),
+                }],
+                Some(&payer.pubkey()),
+                &[&payer, signer],
+                recent_blockhash,
+            )
+        };
+
+        let delegate_before = banks_client.get_balance(delegate.pubkey()).await.unwrap();
+        banks_client
+            .process_transaction(delegated_withdraw(&delegate, 1_500_000))
+            .await
+            .unwrap();
+        assert_eq!(
+            banks_client.get_balance(delegate.pubkey()).await.unwrap(),
+            delegate_before + 1_500_000
+        );
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 3_500_000);
+        assert_eq!(user_data.delegated_amount, cap - 1_500_000);
+
+        // The rest of the cap is all the delegate can take, and nobody else can take any
+        assert_eq!(
+            banks_client
+                .process_transaction(delegated_withdraw(&delegate, 500_001))
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::DelegateAllowanceExceeded as u32)
+            )
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(delegated_withdraw(&stranger, 1))
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::Unauthorized as u32)
+            )
+        );
+
+        // Once revoked, the delegate can't withdraw what was left of its cap
+        let transaction = Transaction::new_signed_with_payer(
+            &[owner_instruction(DepositInstruction::RevokeDelegate { vault_id: 0 })],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(
+            banks_client
+                .process_transaction(delegated_withdraw(&delegate, 1))
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::Unauthorized as u32)
+            )
+        );
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.delegate, Pubkey::default());
+        assert_eq!(user_data.delegated_amount, 0);
+    }
+
+    // Test that only the upgrade authority can set up a config, and that deposits wait for one
+    #[tokio::test]
+    async fn test_initialize_config_requires_upgrade_authority() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+
+        // Without a config the vault takes no deposits
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 10_000_000 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(DepositError::AccountNotInitialized as u32)
+            )
+        );
+        let vault_before = banks_client.get_balance(vault_account).await.unwrap();
+
+        // Someone other than the upgrade authority, an unsigned authority and a
+        // wrong ProgramData account are all refused
+        let impostor = initialize_config_instruction(program_id, payer.pubkey(), 0);
+        let mut signed_by_payer = impostor.clone();
+        signed_by_payer.accounts[4] = AccountMeta::new_readonly(payer.pubkey(), true);
+        let mut unsigned = impostor.clone();
+        unsigned.accounts[4].is_signer = false;
+        let mut wrong_program_data = impostor;
+        wrong_program_data.accounts[3] = AccountMeta::new_readonly(Pubkey::new_unique(), false);
+        let attempts = [
+            (signed_by_payer, false, InstructionError::Custom(DepositError::Unauthorized as u32)),
+            (unsigned, false, InstructionError::MissingRequiredSignature),
+            (
+                wrong_program_data,
+                true,
+                InstructionError::Custom(DepositError::WrongProgramData as u32),
+            ),
+        ];
+        for (instruction, authority_signs, expected) in attempts {
+            let authority = upgrade_authority();
+            let signers: Vec<&Keypair> =
+                if authority_signs { vec![&payer, &authority] } else { vec![&payer] };
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &signers,
+                recent_blockhash,
+            );
+            assert_eq!(
+                banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+                TransactionError::InstructionError(0, expected)
+            );
+            assert!(banks_client.get_account(config_account).await.unwrap().is_none());
+        }
+
+        // The upgrade authority co-signs and the payer becomes admin; now deposits go through
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 10_000_000 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let account = banks_client.get_account(config_account).await.unwrap().unwrap();
+        let config = VaultConfig::try_from_slice(&account.data).unwrap();
+        assert_eq!(config.admin, payer.pubkey());
+        assert_eq!(
+            banks_client.get_balance(vault_account).await.unwrap(),
+            vault_before + 10_000_000
+        );
+    }
+
+    // Test each combination of the deposit and withdrawal pause flags
+    #[tokio::test]
+    async fn test_pause_flags() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (config_account, _) = find_config_address(0, &program_id);
+
+        // Fund the account and set up the vault's config with the payer as admin
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 10_000_000 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // A new config records the vault bump
+        let account = banks_client.get_account(config_account).await.unwrap().unwrap();
+        let config = VaultConfig::try_from_slice(&account.data).unwrap();
+        assert_eq!(config.vault_bump, find_vault_address(0, &program_id).1);
+
+        let combinations = [(false, false), (true, false), (false, true), (true, true)];
+        for (i, (deposits, withdrawals)) in combinations.into_iter().enumerate() {
+            let pause_transaction = Transaction::new_signed_with_payer(
+                &[Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(payer.pubkey(), true),
+                        AccountMeta::new(config_account, false),
+                    ],
+                    data: DepositInstruction::SetPauseFlags { vault_id: 0, deposits, withdrawals }
+                        .try_to_vec()
+                        .unwrap(),
+                }],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            );
+            banks_client.process_transaction(pause_transaction).await.unwrap();
+
+            // Vary the amount so repeated transactions stay unique
+            let amount = 1_000 + i as u64;
+            let steps = [
+                (
+                    DepositInstruction::Deposit { vault_id: 0, amount },
+                    deposits,
+                    DepositError::DepositsPaused,
+                ),
+                (
+                    DepositInstruction::Withdraw { vault_id: 0, amount },
+                    withdrawals,
+                    DepositError::WithdrawalsPaused,
+                ),
+            ];
+
+            for (step, paused, paused_error) in steps {
+                let transaction = Transaction::new_signed_with_payer(
+                    &[transfer_instruction(program_id, payer.pubkey(), step)],
+                    Some(&payer.pubkey()),
+                    &[&payer],
+                    recent_blockhash,
+                );
+                let result = banks_client.process_transaction(transaction).await;
+                if paused {
+                    assert_eq!(
+                        result.unwrap_err().unwrap(),
+                        TransactionError::InstructionError(
+                            0,
+                            InstructionError::Custom(paused_error as u32)
+                        )
+                    );
+                } else {
+                    result.unwrap();
+                }
+            }
+        }
+
+        // Only the admin can change the flags
+        let intruder = Keypair::new();
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(intruder.pubkey(), true),
+                    AccountMeta::new(config_account, false),
+                ],
+                data: DepositInstruction::SetPauseFlags {
+                    vault_id: 0,
+                    deposits: false,
+                    withdrawals: false,
+                }
+                .try_to_vec()
+                .unwrap(),
+            }],
+            Some(&payer.pubkey()),
+            &[&payer, &intruder],
+            recent_blockhash,
+        );
+        assert!(banks_client.process_transaction(transaction).await.is_err());
+    }
+
+    // Test that vested deposits unlock linearly between vest_start and vest_end
+    #[tokio::test]
+    async fn test_vesting_schedule() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+
+        // Deposit an amount vesting over the next 1000 seconds
+        let amount = 10_000_000;
+        let vest_start = clock.unix_timestamp;
+        let vest_end = vest_start + 1_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::DepositVested { vault_id: 0, amount, vest_start, vest_end },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // Warp the clock to the given timestamp and try a withdrawal
+        async fn withdraw_at(
+            context: &mut solana_program_test::ProgramTestContext,
+            program_id: Pubkey,
+            payer: &Keypair,
+            unix_timestamp: i64,
+            amount: u64,
+        ) -> Result<(), TransactionError> {
+            let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+            clock.unix_timestamp = unix_timestamp;
+            context.set_sysvar(&clock);
+
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Withdraw { vault_id: 0, amount },
+                )],
+                Some(&payer.pubkey()),
+                &[payer],
+                recent_blockhash,
+            );
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .map_err(|err| err.unwrap())
+        }
+
+        // At the midpoint exactly half is withdrawable
+        let midpoint = vest_start + 500;
+        assert_eq!(
+            withdraw_at(&mut context, program_id, &payer, midpoint, amount / 2 + 1).await,
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::AmountStillVesting as u32)
+            ))
+        );
+        withdraw_at(&mut context, program_id, &payer, midpoint, amount / 2).await.unwrap();
+
+        // After vest_end the rest is withdrawable
+        withdraw_at(&mut context, program_id, &payer, vest_end + 1, amount / 2).await.unwrap();
+
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 0);
+        assert_eq!(user_data.vest_total, amount);
+    }
+
+    // Test that a locked deposit blocks withdrawals until the unlock time
+    #[tokio::test]
+    async fn test_deposit_locked() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+
+        // Warp the clock to the given timestamp and send the instruction
+        async fn send_at(
+            context: &mut solana_program_test::ProgramTestContext,
+            program_id: Pubkey,
+            payer: &Keypair,
+            unix_timestamp: i64,
+            instruction: DepositInstruction,
+        ) -> Result<(), TransactionError> {
+            let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+            clock.unix_timestamp = unix_timestamp;
+            context.set_sysvar(&clock);
+
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[transfer_instruction(program_id, payer.pubkey(), instruction)],
+                Some(&payer.pubkey()),
+                &[payer],
+                recent_blockhash,
+            );
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .map_err(|err| err.unwrap())
+        }
+        let locked_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+
+        // Lock a deposit for the next 1000 seconds
+        let now = clock.unix_timestamp;
+        let unlock_ts = now + 1_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::DepositLocked {
+                        vault_id: 0,
+                        amount: 10_000_000,
+                        unlock_ts,
+                    },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // An unlock time that has already passed is refused
+        let past = DepositInstruction::DepositLocked { vault_id: 0, amount: 1, unlock_ts: now };
+        assert_eq!(
+            send_at(&mut context, program_id, &payer, now, past).await,
+            locked_error(DepositError::InvalidUnlockTime)
+        );
+
+        // A shorter lockup on a later deposit keeps the longer one
+        let shorter =
+            DepositInstruction::DepositLocked { vault_id: 0, amount: 1, unlock_ts: now + 10 };
+        send_at(&mut context, program_id, &payer, now, shorter).await.unwrap();
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.unlock_ts, unlock_ts);
+        assert_eq!(user_data.balance, 10_000_001);
+
+        // Nothing can be withdrawn up to the last second of the lockup...
+        let withdraw = || DepositInstruction::Withdraw { vault_id: 0, amount: 1 };
+        assert_eq!(
+            send_at(&mut context, program_id, &payer, unlock_ts - 1, withdraw()).await,
+            locked_error(DepositError::FundsLocked)
+        );
+        let withdraw_all = DepositInstruction::WithdrawAll { vault_id: 0 };
+        assert_eq!(
+            send_at(&mut context, program_id, &payer, unlock_ts - 1, withdraw_all).await,
+            locked_error(DepositError::FundsLocked)
+        );
+
+        // ...and everything once it is reached
+        send_at(&mut context, program_id, &payer, unlock_ts, withdraw()).await.unwrap();
+        let withdraw_all = DepositInstruction::WithdrawAll { vault_id: 0 };
+        send_at(&mut context, program_id, &payer, unlock_ts, withdraw_all).await.unwrap();
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 0);
+    }
+
+    // Test that tiered deposits lock their own tranche for the tier's duration
+    #[tokio::test]
+    async fn test_lockup_tiers() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let stranger = Keypair::new();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let day = 24 * 60 * 60;
+
+        // Config instructions share the admin, config and system program accounts
+        let config_instruction = |admin: Pubkey, instruction: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(admin, true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        };
+        let set_tier = |admin: Pubkey, tier: u8, days: i64, multiplier_bps: u16| {
+            config_instruction(
+                admin,
+                DepositInstruction::SetLockupTier {
+                    vault_id: 0,
+                    tier,
+                    duration: days * day,
+                    multiplier_bps,
+                },
+            )
+        };
+
+        // Offer 30, 90 and 180 day tiers paying 1.1x, 1.25x and 1.5x
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                set_tier(payer.pubkey(), 0, 30, 11_000),
+                set_tier(payer.pubkey(), 1, 90, 12_500),
+                set_tier(payer.pubkey(), 2, 180, 15_000),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+        let account = context.banks_client.get_account(config_account).await.unwrap().unwrap();
+        let config = VaultConfig::try_from_slice(&account.data).unwrap();
+        let tier = LockupTier { duration: 90 * day, multiplier_bps: 12_500 };
+        assert_eq!(config.lockup_tiers[1], tier);
+
+        // Only the admin sets tiers, which must pay at least 1x and exist
+        let tier_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+        let rejected = [
+            (&stranger, 0, 30, 11_000, DepositError::Unauthorized),
+            (&payer, 0, 30, 9_999, DepositError::InvalidLockupTier),
+            (&payer, 0, -30, 11_000, DepositError::InvalidLockupTier),
+            (&payer, 3, 30, 11_000, DepositError::UnknownLockupTier),
+        ];
+        for (admin, tier, days, multiplier_bps, error) in rejected {
+            let transaction = Transaction::new_signed_with_payer(
+                &[set_tier(admin.pubkey(), tier, days, multiplier_bps)],
+                Some(&payer.pubkey()),
+                &[&payer, admin],
+                context.last_blockhash,
+            );
+            let result = context.banks_client.process_transaction(transaction).await;
+            assert_eq!(result.map_err(|err| err.unwrap()), tier_error(error));
+        }
+
+        // Warp the clock to the given timestamp and send the instruction
+        async fn send_at(
+            context: &mut solana_program_test::ProgramTestContext,
+            program_id: Pubkey,
+            payer: &Keypair,
+            unix_timestamp: i64,
+            instruction: DepositInstruction,
+        ) -> Result<(), TransactionError> {
+            let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+            clock.unix_timestamp = unix_timestamp;
+            context.set_sysvar(&clock);
+
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[transfer_instruction(program_id, payer.pubkey(), instruction)],
+                Some(&payer.pubkey()),
+                &[payer],
+                recent_blockhash,
+            );
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .map_err(|err| err.unwrap())
+        }
+
+        // Lock 3 SOL for 30 days and 2 SOL for 180 days beside 1 SOL unrestricted
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        let now = clock.unix_timestamp;
+        let tiered = |amount: u64, tier: u8| DepositInstruction::DepositTiered {
+            vault_id: 0,
+            amount,
+            tier,
+        };
+        send_at(&mut context, program_id, &payer, now, tiered(3_000_000_000, 0)).await.unwrap();
+        send_at(&mut context, program_id, &payer, now, tiered(2_000_000_000, 2)).await.unwrap();
+        let deposit = DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000_000 };
+        send_at(&mut context, program_id, &payer, now, deposit).await.unwrap();
+
+        // Each tranche carries its own unlock time and the tier's multiplier
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = super::UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(
+            user_data.tranches[..2],
+            [
+                super::LockupTranche {
+                    amount: 3_000_000_000,
+                    unlock_ts: now + 30 * day,
+                    multiplier_bps: 11_000,
+                },
+                super::LockupTranche {
+                    amount: 2_000_000_000,
+                    unlock_ts: now + 180 * day,
+                    multiplier_bps: 15_000,
+                },
+            ]
+        );
+        assert_eq!(user_data.weighted_balance(now), 6_000_000_000 + 300_000_000 + 1_000_000_000);
+
+        // A tier past the ones the config holds is refused
+        assert_eq!(
+            send_at(&mut context, program_id, &payer, now, tiered(1, 3)).await,
+            tier_error(DepositError::UnknownLockupTier)
+        );
+
+        // Only the unrestricted part can leave before the first tranche unlocks...
+        let withdraw = |amount: u64| DepositInstruction::Withdraw { vault_id: 0, amount };
+        let before_unlock = now + 30 * day - 1;
+        assert_eq!(
+            send_at(&mut context, program_id, &payer, before_unlock, withdraw(1_000_000_001)).await,
+            tier_error(DepositError::FundsLocked)
+        );
+        send_at(&mut context, program_id, &payer, before_unlock, withdraw(1_000_000_000))
+            .await
+            .unwrap();
+
+        // ...after which the 30 day tranche is free and the 180 day one still is not
+        let unlocked = now + 30 * day;
+        send_at(&mut context, program_id, &payer, unlocked, withdraw(3_000_000_000)).await.unwrap();
+        assert_eq!(
+            send_at(&mut context, program_id, &payer, unlocked, withdraw(1)).await,
+            tier_error(DepositError::FundsLocked)
+        );
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = super::UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 2_000_000_000);
+        assert_eq!(user_data.withdrawable(unlocked), 0);
+    }
+
+    // Test that interest accrues lazily on deposits and withdrawals and through
+    // the crank, paid out of the rewards account for as long as it lasts
+    #[tokio::test]
+    async fn test_interest_accrual() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Fund the rewards account with 0.15 SOL above its rent reserve
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        let rewards_reserve = Rent::default().minimum_balance(0);
+        program_test.add_account(
+            rewards_account,
+            Account {
+                lamports: rewards_reserve + 150_000_000,
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        let config_instruction = |instruction: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        };
+        let accrue_instruction = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(find_stats_address(0, &program_id).0, false),
+                AccountMeta::new(user_data_account, false),
+            ],
+            data: DepositInstruction::AccrueInterest { vault_id: 0 }.try_to_vec().unwrap(),
+        };
+
+        // Deposits and withdrawals pass the rewards account once interest is on
+        let with_rewards = |instruction: DepositInstruction| {
+            let mut instruction = transfer_instruction(program_id, payer.pubkey(), instruction);
+            instruction.accounts.push(AccountMeta::new(rewards_account, false));
+            instruction
+        };
+
+        // Warp the clock to the given timestamp and send the instruction
+        async fn send_at(
+            context: &mut solana_program_test::ProgramTestContext,
+            payer: &Keypair,
+            unix_timestamp: i64,
+            instruction: Instruction,
+        ) -> Result<(), TransactionError> {
+            let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+            clock.unix_timestamp = unix_timestamp;
+            context.set_sysvar(&clock);
+
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[payer],
+                recent_blockhash,
+            );
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .map_err(|err| err.unwrap())
+        }
+
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        let now = clock.unix_timestamp;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // The crank has nothing to accrue until a rate is set
+        assert_eq!(
+            send_at(&mut context, &payer, now, accrue_instruction.clone()).await,
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::InterestNotEnabled as u32)
+            ))
+        );
+
+        // Pay 10% a year on 1 SOL
+        let set_rate = config_instruction(DepositInstruction::SetInterestRate {
+            vault_id: 0,
+            rate_bps: 1_000,
+        });
+        send_at(&mut context, &payer, now, set_rate).await.unwrap();
+        let deposit = DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000_000 };
+        let without_rewards = transfer_instruction(program_id, payer.pubkey(), deposit);
+        assert_eq!(
+            send_at(&mut context, &payer, now, without_rewards).await,
+            Err(TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys))
+        );
+        let deposit = DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000_000 };
+        send_at(&mut context, &payer, now, with_rewards(deposit)).await.unwrap();
+
+        // A year later the crank pays the full 0.1 SOL and records the index
+        let year = super::SECONDS_PER_YEAR;
+        send_at(&mut context, &payer, now + year, accrue_instruction).await.unwrap();
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = super::UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 1_100_000_000);
+        assert_eq!(user_data.interest_index, INTEREST_INDEX_ONE * 11 / 10);
+        let account = context.banks_client.get_account(config_account).await.unwrap().unwrap();
+        let config = VaultConfig::try_from_slice(&account.data).unwrap();
+        assert_eq!(
+            (config.interest_index, config.interest_updated_at),
+            (user_data.interest_index, now + year)
+        );
+
+        // Another year compounds to 0.11 SOL, of which the rewards account only
+        // has 0.05 SOL left; withdrawing everything takes what was paid
+        let withdraw_all = DepositInstruction::WithdrawAll { vault_id: 0 };
+        send_at(&mut context, &payer, now + 2 * year, with_rewards(withdraw_all)).await.unwrap();
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = super::UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 0);
+        assert_eq!(user_data.lifetime_withdrawn, 1_150_000_000);
+        assert_eq!(user_data.interest_index, INTEREST_INDEX_ONE * 121 / 100);
+        let rewards = context.banks_client.get_account(rewards_account).await.unwrap().unwrap();
+        assert_eq!(rewards.lamports, rewards_reserve);
+    }
+
+    // Test that a delegated withdrawal settles the owner's interest first, as Withdraw does
+    #[tokio::test]
+    async fn test_delegated_withdraw_settles_interest() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Fund the rewards account with 0.15 SOL above its rent reserve
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        let rewards_reserve = Rent::default().minimum_balance(0);
+        program_test.add_account(
+            rewards_account,
+            Account {
+                lamports: rewards_reserve + 150_000_000,
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        let delegate = Keypair::new();
+        program_test.add_account(
+            delegate.pubkey(),
+            Account {
+                lamports: 1_000_000_000,
+                ..Account::default()
+            },
+        );
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        // Pay 10% a year on 1 SOL, of which the delegate may take 0.5 SOL
+        let mut deposit = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000_000 },
+        );
+        deposit.accounts.push(AccountMeta::new(rewards_account, false));
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(payer.pubkey(), true),
+                        AccountMeta::new(config_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                    ],
+                    data: DepositInstruction::SetInterestRate { vault_id: 0, rate_bps: 1_000 }
+                        .try_to_vec()
+                        .unwrap(),
+                },
+                deposit,
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(payer.pubkey(), true),
+                        AccountMeta::new(user_data_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                    ],
+                    data: DepositInstruction::SetDelegate {
+                        vault_id: 0,
+                        delegate: delegate.pubkey(),
+                        cap: 500_000_000,
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                },
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // A year later the delegate takes 0.1 SOL; the rewards account must be passed
+        let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += super::SECONDS_PER_YEAR;
+        context.set_sysvar(&clock);
+        let mut delegated_withdraw = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(delegate.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(config_account, false),
+            ],
+            data: DepositInstruction::DelegatedWithdraw { vault_id: 0, amount: 100_000_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            std::slice::from_ref(&delegated_withdraw),
+            Some(&payer.pubkey()),
+            &[&payer, &delegate],
+            recent_blockhash,
+        );
+        assert_eq!(
+            context.banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+        );
+
+        delegated_withdraw.accounts.push(AccountMeta::new(rewards_account, false));
+        let delegate_before = context.banks_client.get_balance(delegate.pubkey()).await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[delegated_withdraw],
+            Some(&payer.pubkey()),
+            &[&payer, &delegate],
+            recent_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // The owner was paid the year's 0.1 SOL before the delegate's 0.1 SOL left
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = super::UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 1_000_000_000);
+        assert_eq!(user_data.interest_index, INTEREST_INDEX_ONE * 11 / 10);
+        assert_eq!(user_data.delegated_amount, 400_000_000);
+        assert_eq!(
+            context.banks_client.get_balance(delegate.pubkey()).await.unwrap(),
+            delegate_before + 100_000_000
+        );
+        let rewards = context.banks_client.get_account(rewards_account).await.unwrap().unwrap();
+        assert_eq!(rewards.lamports, rewards_reserve + 50_000_000);
+    }
+
+    // Test that a credit settles the interest earned so far, so it earns only from then on
+    #[tokio::test]
+    async fn test_batch_credit_settles_interest() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Fund the rewards account with 2 SOL above its rent reserve
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        let rewards_reserve = Rent::default().minimum_balance(0);
+        program_test.add_account(
+            rewards_account,
+            Account {
+                lamports: rewards_reserve + 2_000_000_000,
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        let set_rate = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::SetInterestRate { vault_id: 0, rate_bps: 1_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let mut deposit = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000_000 },
+        );
+        deposit.accounts.push(AccountMeta::new(rewards_account, false));
+        let credit = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(find_stats_address(0, &program_id).0, false),
+                AccountMeta::new(user_data_account, false),
+            ],
+            data: DepositInstruction::BatchCredit {
+                vault_id: 0,
+                credits: vec![(payer.pubkey(), 1_000_000_000)],
+            }
+            .try_to_vec()
+            .unwrap(),
+        };
+        let accrue = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(find_stats_address(0, &program_id).0, false),
+                AccountMeta::new(user_data_account, false),
+            ],
+            data: DepositInstruction::AccrueInterest { vault_id: 0 }.try_to_vec().unwrap(),
+        };
+
+        // Warp the clock to the given timestamp and send the instructions
+        async fn send_at(
+            context: &mut solana_program_test::ProgramTestContext,
+            payer: &Keypair,
+            unix_timestamp: i64,
+            instructions: &[Instruction],
+        ) {
+            let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+            clock.unix_timestamp = unix_timestamp;
+            context.set_sysvar(&clock);
+
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                instructions,
+                Some(&payer.pubkey()),
+                &[payer],
+                recent_blockhash,
+            );
+            context.banks_client.process_transaction(transaction).await.unwrap();
+        }
+        let user_data = |account: Account| UserAccount::try_from_slice(&account.data).unwrap();
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // Pay 10% a year on 1 SOL deposited now
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        let now = clock.unix_timestamp;
+        send_at(&mut context, &payer, now, &[set_rate, deposit]).await;
+
+        // A year later 1 SOL is credited; the 0.1 SOL earned is paid first and the
+        // account's index moves up with it
+        let year = super::SECONDS_PER_YEAR;
+        send_at(&mut context, &payer, now + year, &[credit]).await;
+        let account = context.banks_client.get_account(user_data_account).await.unwrap();
+        let credited = user_data(account.unwrap());
+        assert_eq!(credited.balance, 2_100_000_000);
+        assert_eq!(credited.lifetime_deposited, 2_000_000_000);
+        assert_eq!(credited.interest_index, INTEREST_INDEX_ONE * 11 / 10);
+
+        // Nothing recorded the vault's index in between, so a year on it stands at
+        // 1.2; all 2.1 SOL earn its growth since the credit, and none earn the
+        // year before it
+        send_at(&mut context, &payer, now + 2 * year, &[accrue]).await;
+        let account = context.banks_client.get_account(user_data_account).await.unwrap();
+        let accrued = user_data(account.unwrap());
+        let earned = (2_100_000_000u128 * INTEREST_INDEX_ONE / 10
+            / (INTEREST_INDEX_ONE * 11 / 10)) as u64;
+        assert_eq!(accrued.balance, 2_100_000_000 + earned);
+        let rewards = context.banks_client.get_account(rewards_account).await.unwrap().unwrap();
+        assert_eq!(rewards.lamports, rewards_reserve + 2_000_000_000 - 1_100_000_000 - earned);
+    }
+
+    // Test that the admin tunes the fee and minimum deposit, both apply, and
+    // the fees collected in the fee vault can be swept
+    #[tokio::test]
+    async fn test_set_params() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        let (fee_vault_account, _) = find_fee_vault_address(0, &program_id);
+        let fee_vault_reserve = Rent::default().minimum_balance(0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        record_events();
+        let stranger = Keypair::new();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        let config_instruction = |admin: Pubkey, instruction: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(admin, true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        };
+        let set_params = |admin: Pubkey, withdraw_fee_bps: u16| {
+            let mut instruction = config_instruction(
+                admin,
+                DepositInstruction::SetParams {
+                    vault_id: 0,
+                    interest_rate_bps: 0,
+                    withdraw_fee_bps,
+                    min_deposit: 100_000_000,
+                },
+            );
+            instruction.accounts.push(AccountMeta::new(fee_vault_account, false));
+            instruction
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Only the admin sets params, and the fee can't go above the cap
+        let params_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+        let rejected = [
+            (&stranger, 100, DepositError::Unauthorized),
+            (&payer, MAX_WITHDRAW_FEE_BPS + 1, DepositError::InvalidWithdrawFee),
+        ];
+        for (admin, withdraw_fee_bps, error) in rejected {
+            let transaction = Transaction::new_signed_with_payer(
+                &[set_params(admin.pubkey(), withdraw_fee_bps)],
+                Some(&payer.pubkey()),
+                &[&payer, admin],
+                recent_blockhash,
+            );
+            let result = banks_client.process_transaction(transaction).await;
+            assert_eq!(result.map_err(|err| err.unwrap()), params_error(error));
+        }
+
+        // The cap itself is accepted; settle on 1% on withdrawals and deposits of
+        // 0.1 SOL and up
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_params(payer.pubkey(), MAX_WITHDRAW_FEE_BPS), set_params(payer.pubkey(), 100)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let account = banks_client.get_account(config_account).await.unwrap().unwrap();
+        let config = VaultConfig::try_from_slice(&account.data).unwrap();
+        assert_eq!((config.withdraw_fee_bps, config.min_deposit), (100, 100_000_000));
+
+        // A rate left at 0 doesn't enable interest
+        assert_eq!(config.interest_index, 0);
+
+        // The admin funded the fee vault's rent reserve
+        let fee_vault = banks_client.get_account(fee_vault_account).await.unwrap().unwrap();
+        assert_eq!(fee_vault.lamports, fee_vault_reserve);
+
+        // Deposits below the minimum are refused
+        let deposit = |amount: u64| {
+            let instruction = DepositInstruction::Deposit { vault_id: 0, amount };
+            transfer_instruction(program_id, payer.pubkey(), instruction)
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit(99_999_999)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let result = banks_client.process_transaction(transaction).await;
+        assert_eq!(
+            result.map_err(|err| err.unwrap()),
+            params_error(DepositError::DepositBelowMinimum)
+        );
+
+        // A withdrawal is debited in full and its fee moves to the fee vault
+        let mut withdraw = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::Withdraw { vault_id: 0, amount: 500_000_000 },
+        );
+        withdraw.accounts.push(AccountMeta::new(rewards_account, false));
+        withdraw.accounts.push(AccountMeta::new(fee_vault_account, false));
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit(1_000_000_000), withdraw],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 500_000_000);
+        let fee_vault = banks_client.get_account(fee_vault_account).await.unwrap().unwrap();
+        assert_eq!(fee_vault.lamports, fee_vault_reserve + 5_000_000);
+        let vault = banks_client.get_account(vault_account).await.unwrap().unwrap();
+        assert_eq!(vault.lamports, Rent::default().minimum_balance(0) + 500_000_000);
+
+        // Only the admin sweeps the fees, and the fee vault keeps its reserve
+        let treasury = Pubkey::new_unique();
+        let collect_fees = |admin: Pubkey| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(admin, true),
+                AccountMeta::new_readonly(config_account, false),
+                AccountMeta::new(fee_vault_account, false),
+                AccountMeta::new(treasury, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::CollectFees { vault_id: 0 }.try_to_vec().unwrap(),
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[collect_fees(stranger.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer, &stranger],
+            recent_blockhash,
+        );
+        let result = banks_client.process_transaction(transaction).await;
+        assert_eq!(
+            result.map_err(|err| err.unwrap()),
+            params_error(DepositError::Unauthorized)
+        );
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[collect_fees(payer.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(banks_client.get_balance(treasury).await.unwrap(), 5_000_000);
+        let fee_vault = banks_client.get_account(fee_vault_account).await.unwrap().unwrap();
+        assert_eq!(fee_vault.lamports, fee_vault_reserve);
+
+        // Where the fees went is logged
+        assert_eq!(
+            take_events(|event| matches!(event, Event::Fees(fees) if fees.destination == treasury)),
+            vec![Event::Fees(FeesEvent { vault_id: 0, destination: treasury, amount: 5_000_000 })]
+        );
+    }
+
+    // Test that fee tiers are admin-only and charge by the balance withdrawn from
+    #[tokio::test]
+    async fn test_set_fee_tier() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        let (fee_vault_account, _) = find_fee_vault_address(0, &program_id);
+        let fee_vault_reserve = Rent::default().minimum_balance(0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let stranger = Keypair::new();
+        let (config_account, _) = find_config_address(0, &program_id);
+
+        let config_instruction = |admin: Pubkey, instruction: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(admin, true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(fee_vault_account, false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        };
+        let set_fee_tier = |admin: Pubkey, tier: u8, fee_bps: u16| {
+            let instruction = DepositInstruction::SetFeeTier {
+                vault_id: 0,
+                tier,
+                min_balance: 2_000_000_000,
+                fee_bps,
+            };
+            config_instruction(admin, instruction)
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Only the admin sets tiers, within the table and at most the fee cap
+        let tier_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+        let rejected = [
+            (&stranger, 0, 10, DepositError::Unauthorized),
+            (&payer, FEE_TIER_COUNT as u8, 10, DepositError::UnknownFeeTier),
+            (&payer, 0, MAX_WITHDRAW_FEE_BPS + 1, DepositError::InvalidWithdrawFee),
+        ];
+        for (admin, tier, fee_bps, error) in rejected {
+            let transaction = Transaction::new_signed_with_payer(
+                &[set_fee_tier(admin.pubkey(), tier, fee_bps)],
+                Some(&payer.pubkey()),
+                &[&payer, admin],
+                recent_blockhash,
+            );
+            let result = banks_client.process_transaction(transaction).await;
+            assert_eq!(result.map_err(|err| err.unwrap()), tier_error(error));
+        }
+
+        // The cap itself is accepted; settle on 0.1% for balances of 2 SOL and up,
+        // while others keep the base fee of 0
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                set_fee_tier(payer.pubkey(), 0, MAX_WITHDRAW_FEE_BPS),
+                set_fee_tier(payer.pubkey(), 0, 10),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let account = banks_client.get_account(config_account).await.unwrap().unwrap();
+        let config = VaultConfig::try_from_slice(&account.data).unwrap();
+        assert_eq!(config.fee_tiers[0], FeeTier { min_balance: 2_000_000_000, fee_bps: 10 });
+        let fee_vault = banks_client.get_account(fee_vault_account).await.unwrap().unwrap();
+        assert_eq!(fee_vault.lamports, fee_vault_reserve);
+
+        // The tier is picked by the balance before each withdrawal
+        let withdraw = |amount: u64| {
+            let mut instruction = transfer_instruction(
+                program_id,
+                payer.pubkey(),
+                DepositInstruction::Withdraw { vault_id: 0, amount },
+            );
+            instruction.accounts.push(AccountMeta::new(rewards_account, false));
+            instruction.accounts.push(AccountMeta::new(fee_vault_account, false));
+            instruction
+        };
+        let deposit = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::Deposit { vault_id: 0, amount: 3_000_000_000 },
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit, withdraw(2_000_000_000), withdraw(500_000_000)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let fee_vault = banks_client.get_account(fee_vault_account).await.unwrap().unwrap();
+        assert_eq!(fee_vault.lamports, fee_vault_reserve + 2_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_first_deposit_funds_vault_rent() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        // Derive accounts
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let vault_reserve = banks_client.get_rent().await.unwrap().minimum_balance(0);
+
+        let init_transaction = Transaction::new_signed_with_payer(
+            &[initialize_instruction(program_id, payer.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(init_transaction).await.unwrap();
+
+        // A first deposit too small to cover the reserve is rejected
+        let transaction = Transaction::new_signed_with_payer(
+            &[transfer_instruction(
+                program_id,
+                payer.pubkey(),
+                DepositInstruction::Deposit { vault_id: 0, amount: vault_reserve - 1 },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::DepositBelowVaultRent as u32)
+            )
+        );
+
+        // The first deposit funds the reserve and credits only the remainder,
+        // later deposits are credited in full
+        let first_amount = 2_000_000;
+        let second_amount = 1_000_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: first_amount },
+                ),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: second_amount },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, first_amount - vault_reserve + second_amount);
+
+        let vault = banks_client.get_account(vault_account).await.unwrap().unwrap();
+        assert_eq!(vault.lamports, first_amount + second_amount);
+        assert_eq!(vault.lamports, vault_reserve + user_data.balance);
+    }
+
+    // Test that InitializeVault creates a fresh vault and records its bump in an older config
+    #[tokio::test]
+    async fn test_initialize_vault() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        // A legacy config, created before the vault bump was recorded
+        let (config_account, _) = find_config_address(0, &program_id);
+        let mut data = Pubkey::new_unique().to_bytes().to_vec();
+        data.extend_from_slice(&[0, 0]);
+        program_test.add_account(
+            config_account,
+            Account {
+                lamports: Rent::default().minimum_balance(VaultConfig::LEGACY_LEN),
+                data,
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let (vault_account, vault_bump) = find_vault_address(0, &program_id);
+        let vault_reserve = context.banks_client.get_rent().await.unwrap().minimum_balance(0);
+        assert!(context.banks_client.get_account(vault_account).await.unwrap().is_none());
+
+        let initialize_vault = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::InitializeVault { vault_id: 0 }.try_to_vec().unwrap(),
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            std::slice::from_ref(&initialize_vault),
+            Some(&payer.pubkey()),
+            &[&payer],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // The vault holds exactly its reserve and the config grew to record the bump
+        let vault = context.banks_client.get_account(vault_account).await.unwrap().unwrap();
+        assert_eq!(vault.lamports, vault_reserve);
+        assert_eq!(vault.owner, system_program::id());
+        let account = context.banks_client.get_account(config_account).await.unwrap().unwrap();
+        assert_eq!(account.data.len(), VaultConfig::LEN);
+        let config = VaultConfig::try_from_slice(&account.data).unwrap();
+        assert_eq!(config.vault_bump, vault_bump);
+
+        // With nothing left to do a second call is rejected
+        let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_vault],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            context.banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(0, InstructionError::AccountAlreadyInitialized)
+        );
+
+        // The first deposit is now credited in full
+        let amount = 1_000_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, amount);
+        let vault = context.banks_client.get_account(vault_account).await.unwrap().unwrap();
+        assert_eq!(vault.lamports, vault_reserve + amount);
+    }
+
+    // Test that a valid encoding followed by junk bytes is rejected
+    #[tokio::test]
+    async fn test_rejects_trailing_instruction_bytes() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        let init_transaction = Transaction::new_signed_with_payer(
+            &[initialize_instruction(program_id, payer.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(init_transaction).await.unwrap();
+
+        // Append junk to an otherwise valid deposit
+        let mut deposit = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000 },
+        );
+        deposit.data.extend_from_slice(&[0xde, 0xad]);
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let err = banks_client.process_transaction(transaction).await.unwrap_err().unwrap();
+        assert!(matches!(
+            err,
+            TransactionError::InstructionError(0, InstructionError::BorshIoError(_))
+        ));
+
+        // Nothing was credited
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 0);
+    }
+
+    // Test that an admin batch credit moves rewards into the vault and credits each user
+    #[tokio::test]
+    async fn test_batch_credit() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Seed three user accounts with existing balances
+        let owners = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        for owner in &owners {
+            let (user_data_account, _) = find_user_account_address(owner, 0, &program_id);
+            let user_data = UserAccount {
+                owner: *owner,
+                balance: 1_000,
+                vest_start: 0,
+                vest_end: 0,
+                vest_total: 0,
+                lifetime_deposited: 0,
+                lifetime_withdrawn: 0,
+                deposit_window_start: 0,
+                deposited_in_window: 0,
+                mint: Pubkey::default(),
+                pending_owner: Pubkey::default(),
+                delegate: Pubkey::default(),
+                delegated_amount: 0,
+                unlock_ts: 0,
+                tranches: Default::default(),
+                interest_index: 0,
+                pending_withdrawal: 0,
+                withdraw_requested_at: 0,
+                withdraw_epoch: 0,
+                withdrawn_in_epoch: 0,
+                sweep_to_treasury: false,
+                closeable: false,
+            };
+            program_test.add_account(
+                user_data_account,
+                Account {
+                    lamports: Rent::default().minimum_balance(super::UserAccount::LEN),
+                    data: user_data.try_to_vec().unwrap(),
+                    owner: program_id,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+        }
+
+        // Fund the rewards account
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        let rewards = 5_000_000;
+        program_test.add_account(
+            rewards_account,
+            Account {
+                lamports: rewards,
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+
+        let init_config_transaction = Transaction::new_signed_with_payer(
+            &[initialize_config_instruction(program_id, payer.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(init_config_transaction).await.unwrap();
+
+        let batch_credit = |credits: Vec<(Pubkey, u64)>| {
+            let mut accounts = vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(find_stats_address(0, &program_id).0, false),
+            ];
+            for (owner, _) in &credits {
+                let (user_data_account, _) = find_user_account_address(owner, 0, &program_id);
+                accounts.push(AccountMeta::new(user_data_account, false));
+            }
+            Transaction::new_signed_with_payer(
+                &[Instruction {
+                    program_id,
+                    accounts,
+                    data: DepositInstruction::BatchCredit { vault_id: 0, credits }
+                        .try_to_vec()
+                        .unwrap(),
+                }],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            )
+        };
+
+        // A batch the rewards account can't cover is rejected
+        let transaction = batch_credit(vec![(owners[0], rewards), (owners[1], 1)]);
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::InsufficientRewards as u32)
+            )
+        );
+
+        // So is one that would dip into the rent reserve the rewards account keeps
+        let spare = rewards - Rent::default().minimum_balance(0);
+        let transaction = batch_credit(vec![(owners[0], spare), (owners[1], 1)]);
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::InsufficientRewards as u32)
+            )
+        );
+
+        let vault_before = banks_client.get_balance(vault_account).await.unwrap();
+        let credits = vec![(owners[0], 100_000), (owners[1], 200_000), (owners[2], 300_000)];
+        let transaction = batch_credit(credits.clone());
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        for (owner, amount) in &credits {
+            let (user_data_account, _) = find_user_account_address(owner, 0, &program_id);
+            let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+            let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+            assert_eq!(user_data.balance, 1_000 + amount);
+            assert_eq!(user_data.lifetime_deposited, *amount);
+        }
+
+        // The rewards account paid exactly what the vault received
+        assert_eq!(banks_client.get_balance(rewards_account).await.unwrap(), rewards - 600_000);
+        assert_eq!(banks_client.get_balance(vault_account).await.unwrap(), vault_before + 600_000);
+
+        // A credit to an owner who never opened an account fails the whole batch
+        let transaction = batch_credit(vec![(owners[0], 1_000), (Pubkey::new_unique(), 2_000)]);
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::AccountNotInitialized as u32)
+            )
+        );
+        assert_eq!(banks_client.get_balance(rewards_account).await.unwrap(), rewards - 600_000);
+    }
+
+    // Test that BatchCredit accepts exactly MAX_BATCH_CREDITS credits and rejects one more
+    #[tokio::test]
+    async fn test_batch_credit_size_boundary() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Seed one more user account than a batch may credit
+        let owners: Vec<Pubkey> = (0..=MAX_BATCH_CREDITS).map(|_| Pubkey::new_unique()).collect();
+        for owner in &owners {
+            let (user_data_account, _) = find_user_account_address(owner, 0, &program_id);
+            let user_data = UserAccount {
+                owner: *owner,
+                balance: 0,
+                vest_start: 0,
+                vest_end: 0,
+                vest_total: 0,
+                lifetime_deposited: 0,
+                lifetime_withdrawn: 0,
+                deposit_window_start: 0,
+                deposited_in_window: 0,
+                mint: Pubkey::default(),
+                pending_owner: Pubkey::default(),
+                delegate: Pubkey::default(),
+                delegated_amount: 0,
+                unlock_ts: 0,
+                tranches: Default::default(),
+                interest_index: 0,
+                pending_withdrawal: 0,
+                withdraw_requested_at: 0,
+                withdraw_epoch: 0,
+                withdrawn_in_epoch: 0,
+                sweep_to_treasury: false,
+                closeable: false,
+            };
+            program_test.add_account(
+                user_data_account,
+                Account {
+                    lamports: Rent::default().minimum_balance(super::UserAccount::LEN),
+                    data: user_data.try_to_vec().unwrap(),
+                    owner: program_id,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+        }
+
+        // Fund the rewards account
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        program_test.add_account(
+            rewards_account,
+            Account {
+                lamports: 5_000_000,
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+
+        let init_config_transaction = Transaction::new_signed_with_payer(
+            &[initialize_config_instruction(program_id, payer.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(init_config_transaction).await.unwrap();
+
+        let batch_credit = |owners: &[Pubkey]| {
+            let mut accounts = vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(find_stats_address(0, &program_id).0, false),
+            ];
+            for owner in owners {
+                let (user_data_account, _) = find_user_account_address(owner, 0, &program_id);
+                accounts.push(AccountMeta::new(user_data_account, false));
+            }
+            let credits = owners.iter().map(|owner| (*owner, 1_000)).collect();
+            Transaction::new_signed_with_payer(
+                &[Instruction {
+                    program_id,
+                    accounts,
+                    data: DepositInstruction::BatchCredit { vault_id: 0, credits }
+                        .try_to_vec()
+                        .unwrap(),
+                }],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            )
+        };
+
+        // One credit over the cap is rejected before any balance moves
+        let transaction = batch_credit(&owners);
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::BatchTooLarge as u32)
+            )
+        );
+        assert_eq!(banks_client.get_balance(rewards_account).await.unwrap(), 5_000_000);
+
+        // Exactly the cap succeeds
+        let transaction = batch_credit(&owners[..MAX_BATCH_CREDITS]);
+        banks_client.process_transaction(transaction).await.unwrap();
+        for owner in &owners[..MAX_BATCH_CREDITS] {
+            let (user_data_account, _) = find_user_account_address(owner, 0, &program_id);
+            let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+            assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 1_000);
+        }
+    }
+
+    // Test that each role only authorizes its own instruction, and the admin holds all of them
+    #[tokio::test]
+    async fn test_roles() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Seed a user to credit and fund the rewards account
+        let owner = Pubkey::new_unique();
+        let (user_data_account, _) = find_user_account_address(&owner, 0, &program_id);
+        let user_data = UserAccount {
+            owner,
+            balance: 0,
+            vest_start: 0,
+            vest_end: 0,
+            vest_total: 0,
+            lifetime_deposited: 0,
+            lifetime_withdrawn: 0,
+            deposit_window_start: 0,
+            deposited_in_window: 0,
+            mint: Pubkey::default(),
+            pending_owner: Pubkey::default(),
+            delegate: Pubkey::default(),
+            delegated_amount: 0,
+            unlock_ts: 0,
+            tranches: Default::default(),
+            interest_index: 0,
+            pending_withdrawal: 0,
+            withdraw_requested_at: 0,
+            withdraw_epoch: 0,
+            withdrawn_in_epoch: 0,
+            sweep_to_treasury: false,
+            closeable: false,
+        };
+        program_test.add_account(
+            user_data_account,
+            Account {
+                lamports: Rent::default().minimum_balance(super::UserAccount::LEN),
+                data: user_data.try_to_vec().unwrap(),
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        program_test.add_account(
+            rewards_account,
+            Account {
+                lamports: 10_000_000,
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let pauser = Keypair::new();
+        let distributor = Keypair::new();
+
+        let admin_instruction = |data: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: data.try_to_vec().unwrap(),
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                admin_instruction(DepositInstruction::GrantRole {
+                    vault_id: 0,
+                    role: Role::Pauser,
+                    authority: pauser.pubkey(),
+                }),
+                admin_instruction(DepositInstruction::GrantRole {
+                    vault_id: 0,
+                    role: Role::Distributor,
+                    authority: distributor.pubkey(),
+                }),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Vary the paused flag and credited amount so repeated transactions stay unique
+        let pause = |signer: &Keypair, deposits: bool| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(signer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+            ],
+            data: DepositInstruction::SetPauseFlags { vault_id: 0, deposits, withdrawals: false }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let credit = |signer: &Keypair, amount: u64| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(signer.pubkey(), true),
+                AccountMeta::new_readonly(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(find_stats_address(0, &program_id).0, false),
+                AccountMeta::new(user_data_account, false),
+            ],
+            data: DepositInstruction::BatchCredit { vault_id: 0, credits: vec![(owner, amount)] }
+                .try_to_vec()
+                .unwrap(),
+        };
+
+        let steps = [
+            (pause(&pauser, true), &pauser, true),
+            (pause(&distributor, false), &distributor, false),
+            (credit(&distributor, 1_000), &distributor, true),
+            (credit(&pauser, 2_000), &pauser, false),
+            (pause(&payer, false), &payer, true),
+            (credit(&payer, 3_000), &payer, true),
+        ];
+        for (instruction, signer, allowed) in steps {
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer, signer],
+                recent_blockhash,
+            );
+            let result = banks_client.process_transaction(transaction).await;
+            if allowed {
+                result.unwrap();
+            } else {
+                assert_eq!(
+                    result.unwrap_err().unwrap(),
+                    TransactionError::InstructionError(
+                        0,
+                        InstructionError::Custom(DepositError::Unauthorized as u32)
+                    )
+                );
+            }
+        }
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 4_000);
+
+        // Once revoked, the pauser can no longer pause
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                admin_instruction(DepositInstruction::RevokeRole {
+                    vault_id: 0,
+                    role: Role::Pauser,
+                }),
+                pause(&pauser, true),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &pauser],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                1,
+                InstructionError::Custom(DepositError::Unauthorized as u32)
+            )
+        );
+    }
+
+    // Test that a config created before roles existed still works and grows on GrantRole
+    #[tokio::test]
+    async fn test_legacy_config_grows_for_roles() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        // A legacy config holds only the admin and pause flags
+        let admin = Keypair::new();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let mut data = admin.pubkey().to_bytes().to_vec();
+        data.extend_from_slice(&[0, 0]);
+        assert_eq!(data.len(), VaultConfig::LEGACY_LEN);
+        program_test.add_account(
+            config_account,
+            Account {
+                lamports: Rent::default().minimum_balance(VaultConfig::LEGACY_LEN),
+                data,
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        program_test.add_account(
+            admin.pubkey(),
+            Account {
+                lamports: 1_000_000_000,
+                ..Account::default()
+            },
+        );
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let pauser = Pubkey::new_unique();
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(admin.pubkey(), true),
+                        AccountMeta::new(config_account, false),
+                    ],
+                    data: DepositInstruction::SetPauseFlags {
+                        vault_id: 0,
+                        deposits: true,
+                        withdrawals: false,
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                },
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(admin.pubkey(), true),
+                        AccountMeta::new(config_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                    ],
+                    data: DepositInstruction::GrantRole {
+                        vault_id: 0,
+                        role: Role::Pauser,
+                        authority: pauser,
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                },
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &admin],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // The config grew to the current layout, keeping its existing fields
+        let account = banks_client.get_account(config_account).await.unwrap().unwrap();
+        assert_eq!(account.data.len(), VaultConfig::LEN);
+        assert_eq!(account.lamports, Rent::default().minimum_balance(VaultConfig::LEN));
+        let config = VaultConfig::try_from_slice(&account.data).unwrap();
+        assert_eq!(config.admin, admin.pubkey());
+        assert!(config.deposits_paused);
+        assert_eq!(config.pauser, pauser);
+        assert_eq!(config.distributor, Pubkey::default());
+    }
+
+    // Test that every instruction variant survives a round trip through the program's types
+    #[test]
+    fn test_instruction_round_trip() {
+        let key = Pubkey::new_unique();
+        let instructions = vec![
+            DepositInstruction::InitializeAccount { vault_id: 0 },
+            DepositInstruction::InitializeAccount { vault_id: u16::MAX },
+            DepositInstruction::Deposit { vault_id: 1, amount: 0 },
+            DepositInstruction::Deposit { vault_id: 1, amount: u64::MAX },
+            DepositInstruction::Withdraw { vault_id: 2, amount: 0 },
+            DepositInstruction::Withdraw { vault_id: 2, amount: u64::MAX },
+            DepositInstruction::GrowAccount { vault_id: 3, new_size: u32::MAX },
+            DepositInstruction::InitializeConfig { vault_id: 4 },
+            DepositInstruction::SetPauseFlags { vault_id: 5, deposits: true, withdrawals: false },
+            DepositInstruction::DepositVested {
+                vault_id: 6,
+                amount: u64::MAX,
+                vest_start: i64::MIN,
+                vest_end: i64::MAX,
+            },
+            DepositInstruction::BatchCredit { vault_id: 7, credits: vec![] },
+            DepositInstruction::BatchCredit {
+                vault_id: 7,
+                credits: vec![(key, 0), (key, u64::MAX)],
+            },
+            DepositInstruction::GrantRole { vault_id: 8, role: Role::Pauser, authority: key },
+            DepositInstruction::RevokeRole { vault_id: 9, role: Role::Distributor },
+            DepositInstruction::SweepDust { vault_id: 10, threshold: u64::MAX },
+            DepositInstruction::SetDepositLimit { vault_id: 11, daily_limit: u64::MAX },
+            DepositInstruction::GetWithdrawable { vault_id: 12 },
+            DepositInstruction::RenounceAdmin { vault_id: 13 },
+            DepositInstruction::WithdrawToRemaining { vault_id: 14, target_balance: u64::MAX },
+            DepositInstruction::InitializeTokenAccount { vault_id: 15 },
+            DepositInstruction::DepositToken { vault_id: 16, amount: u64::MAX },
+            DepositInstruction::WithdrawToken { vault_id: u16::MAX, amount: 1 },
+            DepositInstruction::DepositWrapped { vault_id: 17, amount: 2 },
+            DepositInstruction::WithdrawUnwrapped { vault_id: 18, amount: 3 },
+            DepositInstruction::InitializeVault { vault_id: 19 },
+            DepositInstruction::CloseAccount { vault_id: 20 },
+            DepositInstruction::WithdrawAll { vault_id: 21 },
+            DepositInstruction::TransferOwnership { vault_id: 22, new_owner: key },
+            DepositInstruction::AcceptOwnership { vault_id: 23 },
+            DepositInstruction::SetDelegate { vault_id: 24, delegate: key, cap: u64::MAX },
+            DepositInstruction::RevokeDelegate { vault_id: 25 },
+            DepositInstruction::DelegatedWithdraw { vault_id: 26, amount: 4 },
+            DepositInstruction::DepositLocked { vault_id: 27, amount: 5, unlock_ts: i64::MIN },
+            DepositInstruction::SetLockupTier {
+                vault_id: 28,
+                tier: u8::MAX,
+                duration: i64::MAX,
+                multiplier_bps: u16::MAX,
+            },
+            DepositInstruction::DepositTiered { vault_id: 29, amount: 6, tier: 2 },
+            DepositInstruction::AccrueInterest { vault_id: 30 },
+            DepositInstruction::SetInterestRate { vault_id: 31, rate_bps: u16::MAX },
+            DepositInstruction::SetParams {
+                vault_id: 32,
+                interest_rate_bps: 1,
+                withdraw_fee_bps: u16::MAX,
+                min_deposit: u64::MAX,
+            },
+            DepositInstruction::CollectFees { vault_id: 33 },
+            DepositInstruction::SetFeeTier {
+                vault_id: 34,
+                tier: u8::MAX,
+                min_balance: u64::MAX,
+                fee_bps: u16::MAX,
+            },
+            DepositInstruction::SetMaxBalancePerUser { vault_id: 35, max_balance: u64::MAX },
+            DepositInstruction::SetTvlCap { vault_id: 36, cap: u64::MAX },
+            DepositInstruction::SetMinResidualBalance { vault_id: 37, min_balance: u64::MAX },
+            DepositInstruction::RequestWithdraw { vault_id: 38, amount: u64::MAX },
+            DepositInstruction::ClaimWithdraw { vault_id: 39 },
+            DepositInstruction::SetWithdrawCooldown { vault_id: 40, cooldown: i64::MIN },
+            DepositInstruction::SetWithdrawLimit { vault_id: 41, epoch_limit: u64::MAX },
+            DepositInstruction::DepositFor {
+                vault_id: 42,
+                amount: u64::MAX,
+                beneficiary: Pubkey::new_unique(),
+            },
+            DepositInstruction::WithdrawTo { vault_id: 43, amount: u64::MAX },
+            DepositInstruction::DepositWithMemo {
+                vault_id: 44,
+                amount: u64::MAX,
+                memo: "order-1234".to_string(),
+            },
+            DepositInstruction::WithdrawWithMemo {
+                vault_id: 45,
+                amount: u64::MAX,
+                memo: String::new(),
+            },
+            DepositInstruction::SetDustThreshold { vault_id: 46, threshold: u64::MAX },
+            DepositInstruction::SetSweepToTreasury { vault_id: 47, enabled: true },
+        ];
+
+        // The test's copy of the enum must encode exactly like the program's
+        for instruction in instructions {
+            let data = instruction.try_to_vec().unwrap();
+            let decoded = super::DepositInstruction::try_from_slice(&data).unwrap();
+            assert_eq!(format!("{:?}", decoded), format!("{:?}", instruction));
+            assert_eq!(decoded.try_to_vec().unwrap(), data);
+        }
+
+        let user_data = super::UserAccount {
+            owner: key,
+            balance: u64::MAX,
+            vest_start: i64::MIN,
+            vest_end: i64::MAX,
+            vest_total: 0,
+            lifetime_deposited: 0,
+            lifetime_withdrawn: 0,
+            deposit_window_start: 0,
+            deposited_in_window: 0,
+            mint: key,
+            pending_owner: key,
+            delegate: key,
+            delegated_amount: u64::MAX,
+            unlock_ts: i64::MAX,
+            tranches: [super::LockupTranche {
+                amount: u64::MAX,
+                unlock_ts: i64::MIN,
+                multiplier_bps: u16::MAX,
+            }; MAX_LOCKUP_TRANCHES],
+            interest_index: u128::MAX,
+            pending_withdrawal: u64::MAX,
+            withdraw_requested_at: i64::MAX,
+            withdraw_epoch: u64::MAX,
+            withdrawn_in_epoch: u64::MAX,
+            sweep_to_treasury: false,
+            closeable: false,
+        };
+        let data = user_data.try_to_vec().unwrap();
+        assert_eq!(data.len(), super::UserAccount::LEN);
+        let decoded = super::UserAccount::try_from_slice(&data).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", user_data));
+        let mirrored = UserAccount::try_from_slice(&data).unwrap();
+        assert_eq!(format!("{:?}", mirrored), format!("{:?}", user_data));
+    }
+
+    // Test that a withdraw whose transfer fails leaves the debited balance untouched
+    #[tokio::test]
+    async fn test_failed_withdraw_transfer_reverts_balance() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+
+        // The vault holds only its reserve, less than the user's recorded balance
+        add_funded_vault(&mut program_test, &program_id, 0);
+        let owner = Keypair::new();
+        let (user_data_account, _) = find_user_account_address(&owner.pubkey(), 0, &program_id);
+        let user_data = UserAccount {
+            owner: owner.pubkey(),
+            balance: 5_000_000,
+            vest_start: 0,
+            vest_end: 0,
+            vest_total: 0,
+            lifetime_deposited: 0,
+            lifetime_withdrawn: 0,
+            deposit_window_start: 0,
+            deposited_in_window: 0,
+            mint: Pubkey::default(),
+            pending_owner: Pubkey::default(),
+            delegate: Pubkey::default(),
+            delegated_amount: 0,
+            unlock_ts: 0,
+            tranches: Default::default(),
+            interest_index: 0,
+            pending_withdrawal: 0,
+            withdraw_requested_at: 0,
+            withdraw_epoch: 0,
+            withdrawn_in_epoch: 0,
+            sweep_to_treasury: false,
+            closeable: false,
+        };
+        program_test.add_account(
+            user_data_account,
+            Account {
+                lamports: Rent::default().minimum_balance(super::UserAccount::LEN),
+                data: user_data.try_to_vec().unwrap(),
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let vault_before = banks_client.get_balance(vault_account).await.unwrap();
+
+        // The balance check passes and the balance is debited, then the transfer fails
+        let transaction = Transaction::new_signed_with_payer(
+            &[transfer_instruction(
+                program_id,
+                owner.pubkey(),
+                DepositInstruction::Withdraw { vault_id: 0, amount: 1_000_000 },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        );
+        let err = banks_client.process_transaction(transaction).await.unwrap_err().unwrap();
+        assert_eq!(
+            err,
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(
+                    solana_program::system_instruction::SystemError::ResultWithNegativeLamports
+                        as u32
+                )
+            )
+        );
+
+        // The debit was rolled back with the failed transfer
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 5_000_000);
+        assert_eq!(banks_client.get_balance(vault_account).await.unwrap(), vault_before);
+    }
+
+    // Test that dust is swept back to its owner and larger balances are left alone
+    #[tokio::test]
+    async fn test_sweep_dust() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+
+        // One owner with dust and one with a real balance, both backed by the vault
+        let owners = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let balances = [500_000, 5_000_000];
+        for (owner, balance) in owners.iter().zip(balances) {
+            let (user_data_account, _) = find_user_account_address(owner, 0, &program_id);
+            let user_data = UserAccount {
+                owner: *owner,
+                balance,
+                vest_start: 0,
+                vest_end: 0,
+                vest_total: 0,
+                lifetime_deposited: 0,
+                lifetime_withdrawn: 0,
+                deposit_window_start: 0,
+                deposited_in_window: 0,
+                mint: Pubkey::default(),
+                pending_owner: Pubkey::default(),
+                delegate: Pubkey::default(),
+                delegated_amount: 0,
+                unlock_ts: 0,
+                tranches: Default::default(),
+                interest_index: 0,
+                pending_withdrawal: 0,
+                withdraw_requested_at: 0,
+                withdraw_epoch: 0,
+                withdrawn_in_epoch: 0,
+                sweep_to_treasury: false,
+                closeable: false,
+            };
+            program_test.add_account(
+                user_data_account,
+                Account {
+                    lamports: Rent::default().minimum_balance(super::UserAccount::LEN),
+                    data: user_data.try_to_vec().unwrap(),
+                    owner: program_id,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+            program_test.add_account(
+                *owner,
+                Account {
+                    lamports: 1_000_000_000,
+                    data: vec![],
+                    owner: system_program::id(),
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+        }
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        program_test.add_account(
+            vault_account,
+            Account {
+                lamports: Rent::default().minimum_balance(0) + balances.iter().sum::<u64>(),
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        let init_config_transaction = Transaction::new_signed_with_payer(
+            &[initialize_config_instruction(program_id, payer.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(init_config_transaction).await.unwrap();
+
+        let sweep_dust = |owner: Pubkey, threshold: u64| {
+            let mut sweep = sweep_dust_instruction(program_id, payer.pubkey(), owner, 0);
+            sweep.data = DepositInstruction::SweepDust { vault_id: 0, threshold }
+                .try_to_vec()
+                .unwrap();
+            Transaction::new_signed_with_payer(
+                &[sweep],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            )
+        };
+        let dust_above_threshold = TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(DepositError::DustAboveThreshold as u32),
+        );
+
+        // Until the admin sets a threshold in the config nothing is dust
+        let transaction = sweep_dust(owners[0], 1_000_000);
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            dust_above_threshold
+        );
+
+        // The config's threshold caps the one the instruction passes, and the other way round
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_dust_threshold_instruction(program_id, payer.pubkey(), 0, 400_000)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let transaction = sweep_dust(owners[0], 1_000_001);
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            dust_above_threshold
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_dust_threshold_instruction(program_id, payer.pubkey(), 0, 1_000_000)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let transaction = sweep_dust(owners[0], 500_000);
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            dust_above_threshold
+        );
+
+        // A balance above the threshold is not dust
+        let transaction = sweep_dust(owners[1], u64::MAX);
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            dust_above_threshold
+        );
+
+        let vault_before = banks_client.get_balance(vault_account).await.unwrap();
+        let owner_before = banks_client.get_balance(owners[0]).await.unwrap();
+        let transaction = sweep_dust(owners[0], u64::MAX);
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // The dust left the vault for its owner and the balance is now zero
+        let (user_data_account, _) = find_user_account_address(&owners[0], 0, &program_id);
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 0);
+        assert!(user_data.closeable);
+        assert_eq!(banks_client.get_balance(owners[0]).await.unwrap(), owner_before + 500_000);
+        assert_eq!(banks_client.get_balance(vault_account).await.unwrap(), vault_before - 500_000);
+        assert_vault_invariants(&mut banks_client, vault_account, system_program::id()).await;
+    }
+
+    // Test that sweeping pays the interest owed first and judges the dust by the result
+    #[tokio::test]
+    async fn test_sweep_dust_settles_interest_first() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        program_test.add_account(
+            rewards_account,
+            Account {
+                lamports: Rent::default().minimum_balance(0) + 1_000_000_000,
+                ..Account::default()
+            },
+        );
+        let owners = [Keypair::new(), Keypair::new()];
+        for owner in &owners {
+            program_test.add_account(
+                owner.pubkey(),
+                Account {
+                    lamports: 1_000_000_000,
+                    ..Account::default()
+                },
+            );
+        }
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let (config_account, _) = find_config_address(0, &program_id);
+
+        // Both accounts start below the threshold and earn 10% a year
+        let mut instructions = vec![
+            initialize_config_instruction(program_id, payer.pubkey(), 0),
+            Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new(config_account, false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+                data: DepositInstruction::SetInterestRate { vault_id: 0, rate_bps: 1_000 }
+                    .try_to_vec()
+                    .unwrap(),
+            },
+            set_dust_threshold_instruction(program_id, payer.pubkey(), 0, 1_000_000),
+        ];
+        for (owner, amount) in owners.iter().zip([900_000, 950_000]) {
+            let mut deposit = transfer_instruction(
+                program_id,
+                owner.pubkey(),
+                DepositInstruction::Deposit { vault_id: 0, amount },
+            );
+            deposit.accounts.push(AccountMeta::new(rewards_account, false));
+            instructions.push(initialize_instruction(program_id, owner.pubkey(), 0));
+            instructions.push(deposit);
+        }
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority(), &owners[0], &owners[1]],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += super::SECONDS_PER_YEAR;
+        context.set_sysvar(&clock);
+        let sweep = |owner: &Keypair, recent_blockhash| {
+            Transaction::new_signed_with_payer(
+                &[sweep_dust_instruction(program_id, payer.pubkey(), owner.pubkey(), 0)],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            )
+        };
+
+        // With its interest the larger account has grown past the threshold
+        let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+        assert_eq!(
+            context
+                .banks_client
+                .process_transaction(sweep(&owners[1], recent_blockhash))
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::DustAboveThreshold as u32)
+            )
+        );
+
+        // The smaller one is swept whole, interest included
+        let owner_before = context.banks_client.get_balance(owners[0].pubkey()).await.unwrap();
+        let rewards_before = context.banks_client.get_balance(rewards_account).await.unwrap();
+        let transaction = sweep(&owners[0], recent_blockhash);
+        context.banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(
+            context.banks_client.get_balance(owners[0].pubkey()).await.unwrap(),
+            owner_before + 990_000
+        );
+        assert_eq!(
+            context.banks_client.get_balance(rewards_account).await.unwrap(),
+            rewards_before - 90_000
+        );
+        let (user_data_account, _) =
+            find_user_account_address(&owners[0].pubkey(), 0, &program_id);
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 0);
+    }
+
+    // Test that dust goes to the fee vault only once its owner has opted in
+    #[tokio::test]
+    async fn test_sweep_dust_to_treasury() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+        let owner = Keypair::new();
+        program_test.add_account(
+            owner.pubkey(),
+            Account {
+                lamports: 1_000_000_000,
+                ..Account::default()
+            },
+        );
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (user_data_account, _) = find_user_account_address(&owner.pubkey(), 0, &program_id);
+        let (fee_vault_account, _) = find_fee_vault_address(0, &program_id);
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                set_dust_threshold_instruction(program_id, payer.pubkey(), 0, 1_000_000),
+                initialize_instruction(program_id, owner.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    owner.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 500_000 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority(), &owner],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let set_sweep_to_treasury = |signer: Pubkey, enabled: bool| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(signer, true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::SetSweepToTreasury { vault_id: 0, enabled }
+                .try_to_vec()
+                .unwrap(),
+        };
+
+        // Only the owner can opt in; the admin signing for them is refused
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_sweep_to_treasury(payer.pubkey(), true)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::WrongUserPda as u32)
+            )
+        );
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_sweep_to_treasury(owner.pubkey(), true)],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert!(UserAccount::try_from_slice(&account.data).unwrap().sweep_to_treasury);
+
+        // The admin tops the empty fee vault up to its rent reserve and the dust
+        // lands on top, leaving the owner's lamports alone
+        let owner_before = banks_client.get_balance(owner.pubkey()).await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[sweep_dust_instruction(program_id, payer.pubkey(), owner.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(banks_client.get_balance(owner.pubkey()).await.unwrap(), owner_before);
+        assert_eq!(
+            banks_client.get_balance(fee_vault_account).await.unwrap(),
+            Rent::default().minimum_balance(0) + 500_000
+        );
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 0);
+    }
+
+    // Test that a swept account closes without waiting out its deposit window,
+    // until a new deposit lands in it
+    #[tokio::test]
+    async fn test_sweep_dust_marks_account_closeable() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+        let owners = [Keypair::new(), Keypair::new()];
+        for owner in &owners {
+            program_test.add_account(
+                owner.pubkey(),
+                Account {
+                    lamports: 1_000_000_000,
+                    ..Account::default()
+                },
+            );
+        }
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (config_account, _) = find_config_address(0, &program_id);
+        let mut instructions = vec![
+            initialize_config_instruction(program_id, payer.pubkey(), 0),
+            Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new(config_account, false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                ],
+                data: DepositInstruction::SetDepositLimit { vault_id: 0, daily_limit: 5_000_000 }
+                    .try_to_vec()
+                    .unwrap(),
+            },
+            set_dust_threshold_instruction(program_id, payer.pubkey(), 0, 1_000_000),
+        ];
+        for owner in &owners {
+            instructions.push(initialize_instruction(program_id, owner.pubkey(), 0));
+            instructions.push(transfer_instruction(
+                program_id,
+                owner.pubkey(),
+                DepositInstruction::Deposit { vault_id: 0, amount: 500_000 },
+            ));
+        }
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority(), &owners[0], &owners[1]],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Both accounts are swept while the day's deposits still count
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                sweep_dust_instruction(program_id, payer.pubkey(), owners[0].pubkey(), 0),
+                sweep_dust_instruction(program_id, payer.pubkey(), owners[1].pubkey(), 0),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let close = |owner: &Keypair| {
+            let (user_data_account, _) =
+                find_user_account_address(&owner.pubkey(), 0, &program_id);
+            Transaction::new_signed_with_payer(
+                &[Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(owner.pubkey(), true),
+                        AccountMeta::new(user_data_account, false),
+                        AccountMeta::new_readonly(config_account, false),
+                    ],
+                    data: DepositInstruction::CloseAccount { vault_id: 0 }.try_to_vec().unwrap(),
+                }],
+                Some(&payer.pubkey()),
+                &[&payer, owner],
+                recent_blockhash,
+            )
+        };
+
+        // The swept account closes at once
+        banks_client.process_transaction(close(&owners[0])).await.unwrap();
+        let (user_data_account, _) =
+            find_user_account_address(&owners[0].pubkey(), 0, &program_id);
+        assert!(banks_client.get_account(user_data_account).await.unwrap().is_none());
+
+        // Depositing into the other one again makes it wait for the window like any other
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                transfer_instruction(
+                    program_id,
+                    owners[1].pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 600_000 },
+                ),
+                transfer_instruction(
+                    program_id,
+                    owners[1].pubkey(),
+                    DepositInstruction::Withdraw { vault_id: 0, amount: 600_000 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &owners[1]],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(
+            banks_client.process_transaction(close(&owners[1])).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::DepositWindowOpen as u32)
+            )
+        );
+    }
+
+    // Test that a deposit or withdraw without the vault account fails instead of panicking
+    #[tokio::test]
+    async fn test_missing_vault_account_is_rejected() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        for instruction in [
+            DepositInstruction::Deposit { vault_id: 0, amount: 1_000 },
+            DepositInstruction::Withdraw { vault_id: 0, amount: 1_000 },
+        ] {
+            let mut instruction = transfer_instruction(program_id, payer.pubkey(), instruction);
+            instruction.accounts.retain(|meta| meta.pubkey != vault_account);
+            assert_eq!(instruction.accounts.len(), 4);
+
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            );
+            assert_eq!(
+                banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+                TransactionError::InstructionError(0, InstructionError::NotEnoughAccountKeys)
+            );
+        }
+    }
+
+    // Test that an all-zero owner can neither initialize nor withdraw
+    #[tokio::test]
+    async fn test_rejects_default_owner() {
+        // A real transaction can't be signed by the default pubkey, so call the processor
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let owner = Pubkey::default();
+        let (user_data_key, _) = find_user_account_address(&owner, 0, &program_id);
+        let system_program_id = system_program::id();
+        let (mut owner_lamports, mut user_data_lamports, mut system_lamports) =
+            (1_000_000_000, 0, 0);
+        let (mut owner_data, mut user_data_data, mut system_data) = (vec![], vec![], vec![]);
+        let accounts = [
+            AccountInfo::new(
+                &owner,
+                true,
+                true,
+                &mut owner_lamports,
+                &mut owner_data,
+                &system_program_id,
+                false,
+                0,
+            ),
+            AccountInfo::new(
+                &user_data_key,
+                false,
+                true,
+                &mut user_data_lamports,
+                &mut user_data_data,
+                &system_program_id,
+                false,
+                0,
+            ),
+            AccountInfo::new(
+                &system_program_id,
+                false,
+                false,
+                &mut system_lamports,
+                &mut system_data,
+                &system_program_id,
+                true,
+                0,
+            ),
+        ];
+        let data = DepositInstruction::InitializeAccount { vault_id: 0 }.try_to_vec().unwrap();
+        assert_eq!(
+            process_instruction(&program_id, &accounts, &data),
+            Err(DepositError::ZeroOwner.into())
+        );
+
+        // Seed one account whose owner field was left zeroed and one that is normal
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        let vault_id = 0;
+        let (vault_account, _) = find_vault_address(vault_id, &program_id);
+        program_test.add_account(
+            vault_account,
+            Account {
+                lamports: Rent::default().minimum_balance(0) + 2_000_000,
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        let zeroed = Keypair::new();
+        let normal = Keypair::new();
+        for (user, recorded_owner) in [(&zeroed, Pubkey::default()), (&normal, normal.pubkey())] {
+            let (user_data_account, _) = find_user_account_address(&user.pubkey(), 0, &program_id);
+            let user_data = UserAccount {
+                owner: recorded_owner,
+                balance: 1_000_000,
+                vest_start: 0,
+                vest_end: 0,
+                vest_total: 0,
+                lifetime_deposited: 0,
+                lifetime_withdrawn: 0,
+                deposit_window_start: 0,
+                deposited_in_window: 0,
+                mint: Pubkey::default(),
+                pending_owner: Pubkey::default(),
+                delegate: Pubkey::default(),
+                delegated_amount: 0,
+                unlock_ts: 0,
+                tranches: Default::default(),
+                interest_index: 0,
+                pending_withdrawal: 0,
+                withdraw_requested_at: 0,
+                withdraw_epoch: 0,
+                withdrawn_in_epoch: 0,
+                sweep_to_treasury: false,
+                closeable: false,
+            };
+            program_test.add_account(
+                user_data_account,
+                Account {
+                    lamports: Rent::default().minimum_balance(super::UserAccount::LEN),
+                    data: user_data.try_to_vec().unwrap(),
+                    owner: program_id,
+                    executable: false,
+                    rent_epoch: 0,
+                },
+            );
+        }
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let withdraw = |user: &Keypair| {
+            Transaction::new_signed_with_payer(
+                &[transfer_instruction(
+                    program_id,
+                    user.pubkey(),
+                    DepositInstruction::Withdraw { vault_id, amount: 1_000_000 },
+                )],
+                Some(&payer.pubkey()),
+                &[&payer, user],
+                recent_blockhash,
+            )
+        };
+
+        assert_eq!(
+            banks_client.process_transaction(withdraw(&zeroed)).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::AccountNotInitialized as u32)
+            )
+        );
+        banks_client.process_transaction(withdraw(&normal)).await.unwrap();
+    }
+
+    // Test that deposits through the processor fill the balance up to u64::MAX and no further
+    #[tokio::test]
+    async fn test_deposit_up_to_max_balance() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // No payer holds u64::MAX lamports, so start the recorded balance near the top
+        let owner = Keypair::new();
+        let (user_data_account, _) = find_user_account_address(&owner.pubkey(), 0, &program_id);
+        let headroom = 3_000_000;
+        let user_data = UserAccount {
+            owner: owner.pubkey(),
+            balance: u64::MAX - headroom,
+            vest_start: 0,
+            vest_end: 0,
+            vest_total: 0,
+            lifetime_deposited: 0,
+            lifetime_withdrawn: 0,
+            deposit_window_start: 0,
+            deposited_in_window: 0,
+            mint: Pubkey::default(),
+            pending_owner: Pubkey::default(),
+            delegate: Pubkey::default(),
+            delegated_amount: 0,
+            unlock_ts: 0,
+            tranches: Default::default(),
+            interest_index: 0,
+            pending_withdrawal: 0,
+            withdraw_requested_at: 0,
+            withdraw_epoch: 0,
+            withdrawn_in_epoch: 0,
+            sweep_to_treasury: false,
+            closeable: false,
+        };
+        program_test.add_account(
+            user_data_account,
+            Account {
+                lamports: Rent::default().minimum_balance(super::UserAccount::LEN),
+                data: user_data.try_to_vec().unwrap(),
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let deposit = |amount: u64| {
+            Transaction::new_signed_with_payer(
+                &[transfer_instruction(
+                    program_id,
+                    owner.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount },
+                )],
+                Some(&payer.pubkey()),
+                &[&payer, &owner],
+                recent_blockhash,
+            )
+        };
+        let fund_owner = Transaction::new_signed_with_payer(
+            &[solana_program::system_instruction::transfer(
+                &payer.pubkey(),
+                &owner.pubkey(),
+                1_000_000_000,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(fund_owner).await.unwrap();
+
+        // Every step that still fits is credited, the last one landing exactly on the maximum
+        for amount in [1_000_000, 1_500_000, 500_000] {
+            banks_client.process_transaction(deposit(amount)).await.unwrap();
+        }
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, u64::MAX);
+        assert_eq!(user_data.lifetime_deposited, headroom);
+
+        // The next lamport would overflow and is rejected without changing anything
+        assert_eq!(
+            banks_client.process_transaction(deposit(1)).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::ArithmeticOverflow as u32)
+            )
+        );
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, u64::MAX);
+    }
+
+    // Test that the daily deposit limit rejects excess deposits until the window restarts
+    #[tokio::test]
+    async fn test_daily_deposit_limit() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // An account from before the window fields existed
+        let owner = Keypair::new();
+        let (user_data_account, _) = find_user_account_address(&owner.pubkey(), 0, &program_id);
+        let user_data = UserAccount {
+            owner: owner.pubkey(),
+            balance: 0,
+            vest_start: 0,
+            vest_end: 0,
+            vest_total: 0,
+            lifetime_deposited: 0,
+            lifetime_withdrawn: 0,
+            deposit_window_start: 0,
+            deposited_in_window: 0,
+            mint: Pubkey::default(),
+            pending_owner: Pubkey::default(),
+            delegate: Pubkey::default(),
+            delegated_amount: 0,
+            unlock_ts: 0,
+            tranches: Default::default(),
+            interest_index: 0,
+            pending_withdrawal: 0,
+            withdraw_requested_at: 0,
+            withdraw_epoch: 0,
+            withdrawn_in_epoch: 0,
+            sweep_to_treasury: false,
+            closeable: false,
+        };
+        let data = user_data.try_to_vec().unwrap();
+        program_test.add_account(
+            user_data_account,
+            Account {
+                lamports: Rent::default().minimum_balance(super::UserAccount::LEGACY_LEN),
+                data: data[..super::UserAccount::LEGACY_LEN].to_vec(),
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        program_test.add_account(
+            owner.pubkey(),
+            Account {
+                lamports: 1_000_000_000,
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let daily_limit = 5_000_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(payer.pubkey(), true),
+                        AccountMeta::new(config_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                    ],
+                    data: DepositInstruction::SetDepositLimit { vault_id: 0, daily_limit }
+                        .try_to_vec()
+                        .unwrap(),
+                },
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // Warp the clock to the given timestamp and try a deposit
+        async fn deposit_at(
+            context: &mut solana_program_test::ProgramTestContext,
+            program_id: Pubkey,
+            owner: &Keypair,
+            unix_timestamp: i64,
+            amount: u64,
+        ) -> Result<(), TransactionError> {
+            let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+            clock.unix_timestamp = unix_timestamp;
+            context.set_sysvar(&clock);
+
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[transfer_instruction(
+                    program_id,
+                    owner.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount },
+                )],
+                Some(&owner.pubkey()),
+                &[owner],
+                recent_blockhash,
+            );
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .map_err(|err| err.unwrap())
+        }
+
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        let start = clock.unix_timestamp;
+        let over_limit = Err(TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(DepositError::DepositLimitExceeded as u32),
+        ));
+
+        // The limit is shared by all deposits in the window
+        deposit_at(&mut context, program_id, &owner, start, 3_000_000).await.unwrap();
+        assert_eq!(
+            deposit_at(&mut context, program_id, &owner, start + 60, 3_000_000).await,
+            over_limit
+        );
+        deposit_at(&mut context, program_id, &owner, start + 60, 2_000_000).await.unwrap();
+
+        // The first deposit grew the legacy account so the window could be recorded
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(account.data.len(), super::UserAccount::LEN);
+        assert!(Rent::default().is_exempt(account.lamports, account.data.len()));
+
+        // One second short of a day the window is still full; a day later it restarts
+        let window_end = start + DEPOSIT_WINDOW_SECONDS;
+        assert_eq!(
+            deposit_at(&mut context, program_id, &owner, window_end - 1, 1).await,
+            over_limit
+        );
+        deposit_at(&mut context, program_id, &owner, window_end, daily_limit).await.unwrap();
+
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 10_000_000);
+        assert_eq!(user_data.deposit_window_start, window_end);
+        assert_eq!(user_data.deposited_in_window, daily_limit);
+    }
+
+    // Test that deposits can bring an account up to the per-user maximum but not above
+    #[tokio::test]
+    async fn test_max_balance_per_user() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let stranger = Keypair::new();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        let config_instruction = |admin: Pubkey, instruction: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(admin, true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        };
+        let set_max_balance = |admin: Pubkey| {
+            let instruction = DepositInstruction::SetMaxBalancePerUser {
+                vault_id: 0,
+                max_balance: 1_000_000_000,
+            };
+            config_instruction(admin, instruction)
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Only the admin sets the maximum
+        let custom_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_max_balance(stranger.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer, &stranger],
+            recent_blockhash,
+        );
+        let result = banks_client.process_transaction(transaction).await;
+        assert_eq!(result.map_err(|err| err.unwrap()), custom_error(DepositError::Unauthorized));
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_max_balance(payer.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // A deposit overshooting the maximum is refused, one reaching it exactly isn't
+        let deposit = |amount: u64| {
+            let instruction = DepositInstruction::Deposit { vault_id: 0, amount };
+            transfer_instruction(program_id, payer.pubkey(), instruction)
+        };
+        for (amount, expected) in [
+            (600_000_000, Ok(())),
+            (400_000_001, custom_error(DepositError::MaxBalanceExceeded)),
+            (400_000_000, Ok(())),
+        ] {
+            let transaction = Transaction::new_signed_with_payer(
+                &[deposit(amount)],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            );
+            let result = banks_client.process_transaction(transaction).await;
+            assert_eq!(result.map_err(|err| err.unwrap()), expected);
+        }
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 1_000_000_000);
+    }
+
+    // Test that a partial withdrawal can't leave dust behind, but a full one can
+    #[tokio::test]
+    async fn test_min_residual_balance() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let stranger = Keypair::new();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        let config_instruction = |admin: Pubkey, instruction: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(admin, true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        };
+        let set_min_balance = |admin: Pubkey| {
+            let instruction = DepositInstruction::SetMinResidualBalance {
+                vault_id: 0,
+                min_balance: 500_000_000,
+            };
+            config_instruction(admin, instruction)
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 1_500_000_000 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Only the admin sets the minimum
+        let custom_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_min_balance(stranger.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer, &stranger],
+            recent_blockhash,
+        );
+        let result = banks_client.process_transaction(transaction).await;
+        assert_eq!(result.map_err(|err| err.unwrap()), custom_error(DepositError::Unauthorized));
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_min_balance(payer.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Leaving less than the minimum is refused, leaving it exactly or nothing isn't
+        let withdraw = |amount: u64| {
+            let instruction = DepositInstruction::Withdraw { vault_id: 0, amount };
+            transfer_instruction(program_id, payer.pubkey(), instruction)
+        };
+        for (instruction, expected) in [
+            (withdraw(1_000_000_001), custom_error(DepositError::ResidualBelowMinimum)),
+            (withdraw(1_000_000_000), Ok(())),
+            (
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::WithdrawAll { vault_id: 0 },
+                ),
+                Ok(()),
+            ),
+        ] {
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            );
+            let result = banks_client.process_transaction(transaction).await;
+            assert_eq!(result.map_err(|err| err.unwrap()), expected);
+        }
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 0);
+    }
+
+    // Test that a payer can fund a beneficiary's account, but only one they opened
+    #[tokio::test]
+    async fn test_deposit_for() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let beneficiary = Keypair::new();
+        let (beneficiary_data_account, _) =
+            find_user_account_address(&beneficiary.pubkey(), 0, &program_id);
+        let (payer_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (config_account, _) = find_config_address(0, &program_id);
+
+        let deposit_for = |user_data_account: Pubkey, amount: u64| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(config_account, false),
+            ],
+            data: DepositInstruction::DepositFor {
+                vault_id: 0,
+                amount,
+                beneficiary: beneficiary.pubkey(),
+            }
+            .try_to_vec()
+            .unwrap(),
+        };
+        let custom_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+
+        // Nothing is credited before the beneficiary opens an account
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit_for(beneficiary_data_account, 500_000_000)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let result = banks_client.process_transaction(transaction).await;
+        assert_eq!(
+            result.map_err(|err| err.unwrap()),
+            custom_error(DepositError::AccountNotInitialized)
+        );
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                system_instruction::transfer(&payer.pubkey(), &beneficiary.pubkey(), 100_000_000),
+                initialize_instruction(program_id, beneficiary.pubkey(), 0),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &beneficiary],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // The account credited must be the beneficiary's, not the payer's
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit_for(payer_data_account, 500_000_000)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let result = banks_client.process_transaction(transaction).await;
+        assert_eq!(result.map_err(|err| err.unwrap()), custom_error(DepositError::WrongUserPda));
+
+        // The payer funds the deposit and the beneficiary is credited with it
+        let payer_before = banks_client.get_balance(payer.pubkey()).await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit_for(beneficiary_data_account, 400_000_000)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let payer_after = banks_client.get_balance(payer.pubkey()).await.unwrap();
+        assert_eq!(payer_after, payer_before - 400_000_000 - 5_000);
+
+        let account = banks_client.get_account(beneficiary_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.owner, beneficiary.pubkey());
+        assert_eq!(user_data.balance, 400_000_000);
+        assert_eq!(user_data.lifetime_deposited, 400_000_000);
+        assert!(banks_client.get_account(payer_data_account).await.unwrap().is_none());
+
+        // Only the beneficiary can withdraw what was deposited for them
+        let transaction = Transaction::new_signed_with_payer(
+            &[transfer_instruction(
+                program_id,
+                beneficiary.pubkey(),
+                DepositInstruction::WithdrawAll { vault_id: 0 },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &beneficiary],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let beneficiary_balance = banks_client.get_balance(beneficiary.pubkey()).await.unwrap();
+        assert!(beneficiary_balance > 400_000_000);
+    }
+
+    // Test that deposits into a user PDA that was never initialized are refused
+    #[tokio::test]
+    async fn test_deposit_requires_initialized_account() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let vault_before = banks_client.get_balance(vault_account).await.unwrap();
+
+        for instruction in [
+            DepositInstruction::Deposit { vault_id: 0, amount: 5_000_000 },
+            DepositInstruction::DepositLocked {
+                vault_id: 0,
+                amount: 5_000_000,
+                unlock_ts: i64::MAX,
+            },
+            DepositInstruction::DepositTiered { vault_id: 0, amount: 5_000_000, tier: 0 },
+        ] {
+            let transaction = Transaction::new_signed_with_payer(
+                &[transfer_instruction(program_id, payer.pubkey(), instruction)],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            );
+            let result = banks_client.process_transaction(transaction).await;
+            assert_eq!(
+                result.map_err(|err| err.unwrap()),
+                Err(TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(DepositError::AccountNotInitialized as u32)
+                ))
+            );
+        }
+
+        // Nothing moved into the vault and the PDA still doesn't exist
+        assert_eq!(banks_client.get_balance(vault_account).await.unwrap(), vault_before);
+        assert!(banks_client.get_account(user_data_account).await.unwrap().is_none());
+    }
+
+    // Test that the owner can withdraw to another address, and only the owner
+    #[tokio::test]
+    async fn test_withdraw_to() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let stranger = Keypair::new();
+        let destination = Pubkey::new_unique();
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000_000 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // The destination follows the accounts Withdraw takes
+        let withdraw_to = |signer: Pubkey| {
+            let instruction = DepositInstruction::WithdrawTo { vault_id: 0, amount: 300_000_000 };
+            let mut instruction = transfer_instruction(program_id, payer.pubkey(), instruction);
+            instruction.accounts[0] = AccountMeta::new(signer, true);
+            instruction.accounts.push(AccountMeta::new(destination, false));
+            instruction
+        };
+
+        // Someone else signing can't pay the owner's balance out anywhere
+        let transaction = Transaction::new_signed_with_payer(
+            &[withdraw_to(stranger.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer, &stranger],
+            recent_blockhash,
+        );
+        let result = banks_client.process_transaction(transaction).await;
+        assert_eq!(
+            result.map_err(|err| err.unwrap()),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::WrongUserPda as u32)
+            ))
+        );
+
+        // The owner signs and the destination receives the lamports
+        let payer_before = banks_client.get_balance(payer.pubkey()).await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[withdraw_to(payer.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(banks_client.get_balance(destination).await.unwrap(), 300_000_000);
+        let payer_after = banks_client.get_balance(payer.pubkey()).await.unwrap();
+        assert_eq!(payer_after, payer_before - 5_000);
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 700_000_000);
+        assert_eq!(user_data.lifetime_withdrawn, 300_000_000);
+    }
+
+    // Test that every instruction that takes SOL out of a balance logs a withdraw event
+    #[tokio::test]
+    async fn test_withdraw_events() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+        let delegate = Keypair::new();
+        program_test.add_account(
+            delegate.pubkey(),
+            Account {
+                lamports: 1_000_000_000,
+                ..Account::default()
+            },
+        );
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        record_events();
+        let owner = payer.pubkey();
+        let destination = Pubkey::new_unique();
+        let (user_data_account, _) = find_user_account_address(&owner, 0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (config_account, _) = find_config_address(0, &program_id);
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, owner, 0),
+                transfer_instruction(
+                    program_id,
+                    owner,
+                    DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000_000 },
+                ),
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(owner, true),
+                        AccountMeta::new(user_data_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                    ],
+                    data: DepositInstruction::SetDelegate {
+                        vault_id: 0,
+                        delegate: delegate.pubkey(),
+                        cap: 2_000_000,
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                },
+            ],
+            Some(&owner),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        assert!(take_withdraw_events(&owner).is_empty());
+
+        let instruction = DepositInstruction::WithdrawTo { vault_id: 0, amount: 2_000_000 };
+        let mut withdraw_to = transfer_instruction(program_id, owner, instruction);
+        withdraw_to.accounts.push(AccountMeta::new(destination, false));
+        let request_withdraw = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new_readonly(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::RequestWithdraw { vault_id: 0, amount: 4_000_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let delegated_withdraw = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(delegate.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(config_account, false),
+            ],
+            data: DepositInstruction::DelegatedWithdraw { vault_id: 0, amount: 1_500_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+
+        // Each transaction, the account it pays out to and the amount it takes
+        let withdrawals = vec![
+            (
+                vec![transfer_instruction(
+                    program_id,
+                    owner,
+                    DepositInstruction::Withdraw { vault_id: 0, amount: 1_000_000 },
+                )],
+                owner,
+                1_000_000,
+            ),
+            (vec![withdraw_to], destination, 2_000_000),
+            (
+                vec![transfer_instruction(
+                    program_id,
+                    owner,
+                    DepositInstruction::WithdrawWithMemo {
+                        vault_id: 0,
+                        amount: 3_000_000,
+                        memo: "payout".to_string(),
+                    },
+                )],
+                owner,
+                3_000_000,
+            ),
+            (
+                vec![
+                    request_withdraw,
+                    transfer_instruction(
+                        program_id,
+                        owner,
+                        DepositInstruction::ClaimWithdraw { vault_id: 0 },
+                    ),
+                ],
+                owner,
+                4_000_000,
+            ),
+            (vec![delegated_withdraw], delegate.pubkey(), 1_500_000),
+            (
+                vec![transfer_instruction(
+                    program_id,
+                    owner,
+                    DepositInstruction::WithdrawToRemaining {
+                        vault_id: 0,
+                        target_balance: 500_000_000,
+                    },
+                )],
+                owner,
+                488_500_000,
+            ),
+            (
+                vec![transfer_instruction(
+                    program_id,
+                    owner,
+                    DepositInstruction::WithdrawAll { vault_id: 0 },
+                )],
+                owner,
+                500_000_000,
+            ),
+        ];
+
+        let mut balance = 1_000_000_000;
+        for (instructions, destination, amount) in withdrawals {
+            let signers: Vec<&Keypair> = if destination == delegate.pubkey() {
+                vec![&payer, &delegate]
+            } else {
+                vec![&payer]
+            };
+            let transaction = Transaction::new_signed_with_payer(
+                &instructions,
+                Some(&owner),
+                &signers,
+                recent_blockhash,
+            );
+            banks_client.process_transaction(transaction).await.unwrap();
+            balance -= amount;
+            assert_eq!(
+                take_withdraw_events(&owner),
+                vec![WithdrawEvent { vault_id: 0, owner, destination, amount, fee: 0, balance }]
+            );
+        }
+        assert_eq!(balance, 0);
+    }
+
+    // Test that the other instructions that change a SOL balance or the vault's
+    // settings log their events; vault 5 is this test's alone, so they are its own
+    #[tokio::test]
+    async fn test_balance_events() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 5);
+        let (rewards_account, _) = find_rewards_address(5, &program_id);
+        program_test.add_account(
+            rewards_account,
+            Account {
+                lamports: Rent::default().minimum_balance(0) + 1_000_000_000,
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        record_events();
+        let payer = context.payer.insecure_clone();
+        let owner = payer.pubkey();
+        let dust_owner = Keypair::new();
+        let new_owner = Keypair::new();
+        let (config_account, _) = find_config_address(5, &program_id);
+        let (vault_account, _) = find_vault_address(5, &program_id);
+        let (stats_account, _) = find_stats_address(5, &program_id);
+        let (user_data_account, _) = find_user_account_address(&owner, 5, &program_id);
+        let (dust_data_account, _) =
+            find_user_account_address(&dust_owner.pubkey(), 5, &program_id);
+        let (new_user_data_account, _) =
+            find_user_account_address(&new_owner.pubkey(), 5, &program_id);
+        let balance_event = |owner: Pubkey, amount: u64, balance: u64| BalanceEvent {
+            vault_id: 5,
+            owner,
+            amount,
+            balance,
+        };
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_config_instruction(program_id, owner, 5),
+                system_instruction::transfer(&owner, &dust_owner.pubkey(), 10_000_000),
+                system_instruction::transfer(&owner, &new_owner.pubkey(), 10_000_000),
+                initialize_instruction(program_id, owner, 5),
+                initialize_instruction(program_id, dust_owner.pubkey(), 5),
+            ],
+            Some(&owner),
+            &[&payer, &upgrade_authority(), &dust_owner],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(
+            take_vault_events(5),
+            vec![
+                Event::Init(InitEvent { vault_id: 5, owner }),
+                Event::Init(InitEvent { vault_id: 5, owner: dust_owner.pubkey() }),
+            ]
+        );
+
+        async fn send(
+            context: &mut solana_program_test::ProgramTestContext,
+            instructions: &[Instruction],
+            signers: &[&Keypair],
+        ) {
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                instructions,
+                Some(&signers[0].pubkey()),
+                signers,
+                recent_blockhash,
+            );
+            context.banks_client.process_transaction(transaction).await.unwrap();
+        }
+
+        // Pay 10% a year on 0.5 SOL deposited with a memo; the accounts opened
+        // before the rate was set have earned nothing yet
+        let set_rate = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::SetInterestRate { vault_id: 5, rate_bps: 1_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let mut deposit = transfer_instruction(
+            program_id,
+            owner,
+            DepositInstruction::DepositWithMemo {
+                vault_id: 5,
+                amount: 500_000_000,
+                memo: "invoice 7".to_string(),
+            },
+        );
+        deposit.accounts.push(AccountMeta::new(rewards_account, false));
+        send(&mut context, &[set_rate, deposit], &[&payer]).await;
+        assert_eq!(
+            take_vault_events(5),
+            vec![
+                Event::Param(ParamEvent {
+                    vault_id: 5,
+                    name: "interest_rate_bps".to_string(),
+                    old: 0,
+                    new: 1_000,
+                }),
+                Event::Interest(balance_event(owner, 0, 0)),
+                Event::Deposit(DepositEvent {
+                    vault_id: 5,
+                    owner,
+                    payer: owner,
+                    amount: 500_000_000,
+                    balance: 500_000_000,
+                }),
+                Event::Memo(MemoEvent {
+                    vault_id: 5,
+                    signer: owner,
+                    memo: "invoice 7".to_string(),
+                }),
+            ]
+        );
+
+        // A year on, the crank pays the interest earned
+        let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += super::SECONDS_PER_YEAR;
+        context.set_sysvar(&clock);
+        let accrue = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(stats_account, false),
+                AccountMeta::new(user_data_account, false),
+            ],
+            data: DepositInstruction::AccrueInterest { vault_id: 5 }.try_to_vec().unwrap(),
+        };
+        send(&mut context, &[accrue], &[&payer]).await;
+        assert_eq!(
+            take_vault_events(5),
+            vec![Event::Interest(balance_event(owner, 50_000_000, 550_000_000))]
+        );
+
+        // Each credit is logged after the interest settled before it
+        let credits = vec![(owner, 300_000_000), (dust_owner.pubkey(), 1_000)];
+        let credit = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new_readonly(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(stats_account, false),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(dust_data_account, false),
+            ],
+            data: DepositInstruction::BatchCredit { vault_id: 5, credits }.try_to_vec().unwrap(),
+        };
+        send(&mut context, &[credit], &[&payer]).await;
+        assert_eq!(
+            take_vault_events(5),
+            vec![
+                Event::Interest(balance_event(owner, 0, 550_000_000)),
+                Event::Credit(balance_event(owner, 300_000_000, 850_000_000)),
+                Event::Interest(balance_event(dust_owner.pubkey(), 0, 0)),
+                Event::Credit(balance_event(dust_owner.pubkey(), 1_000, 1_000)),
+            ]
+        );
+
+        let set_dust_threshold = set_dust_threshold_instruction(program_id, owner, 5, 1_000_000);
+        let sweep = sweep_dust_instruction(program_id, owner, dust_owner.pubkey(), 5);
+        send(&mut context, &[set_dust_threshold, sweep], &[&payer]).await;
+        assert_eq!(
+            take_vault_events(5),
+            vec![
+                Event::Param(ParamEvent {
+                    vault_id: 5,
+                    name: "dust_threshold".to_string(),
+                    old: 0,
+                    new: 1_000_000,
+                }),
+                Event::Interest(balance_event(dust_owner.pubkey(), 0, 1_000)),
+                Event::SweepDust(balance_event(dust_owner.pubkey(), 1_000, 0)),
+            ]
+        );
+
+        // Handing the account over moves its balance to the new owner
+        let transfer_ownership = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::TransferOwnership {
+                vault_id: 5,
+                new_owner: new_owner.pubkey(),
+            }
+            .try_to_vec()
+            .unwrap(),
+        };
+        let accept_ownership = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(new_owner.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(new_user_data_account, false),
+                AccountMeta::new(owner, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::AcceptOwnership { vault_id: 5 }.try_to_vec().unwrap(),
+        };
+        send(&mut context, &[transfer_ownership, accept_ownership], &[&payer, &new_owner]).await;
+        assert_eq!(
+            take_vault_events(5),
+            vec![Event::Ownership(OwnershipEvent {
+                vault_id: 5,
+                old_owner: owner,
+                new_owner: new_owner.pubkey(),
+                balance: 850_000_000,
+            })]
+        );
+    }
+
+    // Test that memos are logged, passed to SPL Memo when asked, and bounded
+    #[tokio::test]
+    async fn test_memo() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_instruction(program_id, payer.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // The memo program may follow the deposit's accounts
+        let mut deposit = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::DepositWithMemo {
+                vault_id: 0,
+                amount: 1_000_000,
+                memo: "order-1234".to_string(),
+            },
+        );
+        deposit.accounts.push(AccountMeta::new_readonly(spl_memo::id(), false));
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let simulation = banks_client.simulate_transaction(transaction.clone()).await.unwrap();
+        simulation.result.unwrap().unwrap();
+        let logs = simulation.simulation_details.unwrap().logs;
+        assert!(logs.iter().any(|log| log.ends_with("Memo: order-1234")));
+        assert!(logs.iter().any(|log| log.ends_with("Memo (len 10): \"order-1234\"")));
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Without the memo program the memo is only logged
+        let withdraw = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::WithdrawWithMemo {
+                vault_id: 0,
+                amount: 400_000,
+                memo: "payout-5678".to_string(),
+            },
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[withdraw],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 600_000);
+
+        // A memo over MAX_MEMO_LEN bytes is refused
+        let deposit = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::DepositWithMemo {
+                vault_id: 0,
+                amount: 1_000_000,
+                memo: "x".repeat(MAX_MEMO_LEN + 1),
+            },
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let result = banks_client.process_transaction(transaction).await;
+        assert_eq!(
+            result.map_err(|err| err.unwrap()),
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::MemoTooLong as u32)
+            ))
+        );
+    }
+
+    // Test that with a cooldown SOL only leaves through a request claimed after it
+    #[tokio::test]
+    async fn test_withdraw_cooldown() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        let config_instruction = |instruction: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        };
+        let request_withdraw = |amount: u64| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new_readonly(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::RequestWithdraw { vault_id: 0, amount }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let claim_withdraw = || {
+            let instruction = DepositInstruction::ClaimWithdraw { vault_id: 0 };
+            transfer_instruction(program_id, payer.pubkey(), instruction)
+        };
+
+        // Warp the clock to the given timestamp, if any, and send the instruction
+        async fn process_at(
+            context: &mut solana_program_test::ProgramTestContext,
+            payer: &Keypair,
+            unix_timestamp: Option<i64>,
+            instruction: Instruction,
+        ) -> Result<(), TransactionError> {
+            if let Some(unix_timestamp) = unix_timestamp {
+                let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+                clock.unix_timestamp = unix_timestamp;
+                context.set_sysvar(&clock);
+            }
+
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[payer],
+                recent_blockhash,
+            );
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .map_err(|err| err.unwrap())
+        }
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000_000 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // A cooldown can't be negative or go above the cap, though the cap itself is accepted
+        let custom_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+        let set_cooldown = |cooldown: i64| {
+            config_instruction(DepositInstruction::SetWithdrawCooldown { vault_id: 0, cooldown })
+        };
+        for cooldown in [-1, MAX_WITHDRAW_COOLDOWN + 1] {
+            let result = process_at(&mut context, &payer, None, set_cooldown(cooldown)).await;
+            assert_eq!(result, custom_error(DepositError::InvalidCooldown));
+        }
+        process_at(&mut context, &payer, None, set_cooldown(MAX_WITHDRAW_COOLDOWN)).await.unwrap();
+        process_at(&mut context, &payer, None, set_cooldown(1_000)).await.unwrap();
+
+        // Withdrawing directly is refused, and so is claiming without a request
+        let withdraw = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::Withdraw { vault_id: 0, amount: 1 },
+        );
+        let result = process_at(&mut context, &payer, None, withdraw).await;
+        assert_eq!(result, custom_error(DepositError::WithdrawalRequestRequired));
+        let result = process_at(&mut context, &payer, None, claim_withdraw()).await;
+        assert_eq!(result, custom_error(DepositError::NoPendingWithdrawal));
+
+        // A request can't ask for more than the balance
+        let result = process_at(&mut context, &payer, None, request_withdraw(1_000_000_001)).await;
+        assert_eq!(result, custom_error(DepositError::InsufficientBalance));
+
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        let requested_at = clock.unix_timestamp;
+        process_at(&mut context, &payer, Some(requested_at), request_withdraw(400_000_000))
+            .await
+            .unwrap();
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.pending_withdrawal, 400_000_000);
+        assert_eq!(user_data.withdraw_requested_at, requested_at);
+
+        // The claim waits out the cooldown, then pays the requested amount once
+        let result = process_at(&mut context, &payer, Some(requested_at + 999), claim_withdraw());
+        assert_eq!(result.await, custom_error(DepositError::CooldownNotElapsed));
+        let balance_before = context.banks_client.get_balance(payer.pubkey()).await.unwrap();
+        process_at(&mut context, &payer, Some(requested_at + 1_000), claim_withdraw())
+            .await
+            .unwrap();
+        let balance_after = context.banks_client.get_balance(payer.pubkey()).await.unwrap();
+        assert_eq!(balance_after, balance_before + 400_000_000 - 5_000);
+
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 600_000_000);
+        assert_eq!(user_data.pending_withdrawal, 0);
+        assert_eq!(user_data.withdraw_requested_at, 0);
+        let result = process_at(&mut context, &payer, None, claim_withdraw()).await;
+        assert_eq!(result, custom_error(DepositError::NoPendingWithdrawal));
+
+        // A request for nothing cancels the pending one
+        process_at(&mut context, &payer, None, request_withdraw(100)).await.unwrap();
+        process_at(&mut context, &payer, None, request_withdraw(0)).await.unwrap();
+        let result = process_at(&mut context, &payer, Some(i64::MAX), claim_withdraw()).await;
+        assert_eq!(result, custom_error(DepositError::NoPendingWithdrawal));
+    }
+
+    // Test that withdrawals in one epoch are capped per account, delegate included
+    #[tokio::test]
+    async fn test_epoch_withdraw_limit() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program with a context so the epoch can be advanced
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let delegate = Keypair::new();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+
+        let config_instruction = |instruction: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        };
+        let withdraw = |amount: u64| {
+            let instruction = DepositInstruction::Withdraw { vault_id: 0, amount };
+            transfer_instruction(program_id, payer.pubkey(), instruction)
+        };
+        let delegated_withdraw = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(delegate.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(config_account, false),
+            ],
+            data: DepositInstruction::DelegatedWithdraw { vault_id: 0, amount: 1 }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 1_000_000_000 },
+                ),
+                config_instruction(DepositInstruction::SetWithdrawLimit {
+                    vault_id: 0,
+                    epoch_limit: 300_000_000,
+                }),
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(payer.pubkey(), true),
+                        AccountMeta::new(user_data_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                    ],
+                    data: DepositInstruction::SetDelegate {
+                        vault_id: 0,
+                        delegate: delegate.pubkey(),
+                        cap: 1_000_000_000,
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                },
+                system_instruction::transfer(&payer.pubkey(), &delegate.pubkey(), 1_000_000_000),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // Send each instruction in its own transaction, signed by the payer and the given signer
+        async fn process(
+            context: &mut solana_program_test::ProgramTestContext,
+            payer: &Keypair,
+            signer: &Keypair,
+            instruction: Instruction,
+        ) -> Result<(), TransactionError> {
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[payer, signer],
+                recent_blockhash,
+            );
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .map_err(|err| err.unwrap())
+        }
+
+        // The owner and delegate share the limit within the epoch
+        let custom_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+        process(&mut context, &payer, &payer, withdraw(299_999_999)).await.unwrap();
+        process(&mut context, &payer, &delegate, delegated_withdraw.clone()).await.unwrap();
+        let result = process(&mut context, &payer, &payer, withdraw(1)).await;
+        assert_eq!(result, custom_error(DepositError::WithdrawLimitExceeded));
+        let result = process(&mut context, &payer, &delegate, delegated_withdraw).await;
+        assert_eq!(result, custom_error(DepositError::WithdrawLimitExceeded));
+
+        // A new epoch starts the count over
+        let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        clock.epoch += 1;
+        context.set_sysvar(&clock);
+        let result = process(&mut context, &payer, &payer, withdraw(300_000_001)).await;
+        assert_eq!(result, custom_error(DepositError::WithdrawLimitExceeded));
+        process(&mut context, &payer, &payer, withdraw(300_000_000)).await.unwrap();
+
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 400_000_000);
+        assert_eq!(user_data.withdraw_epoch, clock.epoch);
+        assert_eq!(user_data.withdrawn_in_epoch, 300_000_000);
+    }
+
+    // Test that the TVL cap starts from the vault's holdings and tracks deposits and withdrawals
+    #[tokio::test]
+    async fn test_tvl_cap() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        let (fee_vault_account, _) = find_fee_vault_address(0, &program_id);
+        let (stats_account, _) = find_stats_address(0, &program_id);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let stranger = Keypair::new();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+
+        let deposit = |amount: u64| {
+            let instruction = DepositInstruction::Deposit { vault_id: 0, amount };
+            transfer_instruction(program_id, payer.pubkey(), instruction)
+        };
+        let set_tvl_cap = |admin: Pubkey| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(admin, true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new(stats_account, false),
+                AccountMeta::new_readonly(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::SetTvlCap { vault_id: 0, cap: 1_000_000_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                deposit(500_000_000),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Only the admin sets the cap
+        let custom_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_tvl_cap(stranger.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer, &stranger],
+            recent_blockhash,
+        );
+        let result = banks_client.process_transaction(transaction).await;
+        assert_eq!(result.map_err(|err| err.unwrap()), custom_error(DepositError::Unauthorized));
+
+        // The deposit made before the cap is counted in
+        let transaction = Transaction::new_signed_with_payer(
+            &[set_tvl_cap(payer.pubkey())],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let stats_of = |account: Account| VaultStats::try_from_slice(&account.data).unwrap();
+        let stats = banks_client.get_account(stats_account).await.unwrap().unwrap();
+        assert_eq!(stats.owner, program_id);
+        assert_eq!(stats_of(stats).total_value_locked, 500_000_000);
+
+        // Deposits now need the stats account, and can fill the cap but not exceed it
+        let capped_deposit = |amount: u64| {
+            let mut instruction = deposit(amount);
+            instruction.accounts.push(AccountMeta::new(rewards_account, false));
+            instruction.accounts.push(AccountMeta::new(stats_account, false));
+            instruction
+        };
+        for (instruction, expected) in [
+            (deposit(1), Err(InstructionError::NotEnoughAccountKeys)),
+            (
+                capped_deposit(500_000_001),
+                Err(InstructionError::Custom(DepositError::CapExceeded as u32)),
+            ),
+            (capped_deposit(500_000_000), Ok(())),
+        ] {
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            );
+            let result = banks_client.process_transaction(transaction).await;
+            let expected = expected.map_err(|err| TransactionError::InstructionError(0, err));
+            assert_eq!(result.map_err(|err| err.unwrap()), expected);
+        }
+
+        // A withdrawal frees room under the cap
+        let mut withdraw = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::Withdraw { vault_id: 0, amount: 300_000_000 },
+        );
+        withdraw.accounts.push(AccountMeta::new(rewards_account, false));
+        withdraw.accounts.push(AccountMeta::new(fee_vault_account, false));
+        withdraw.accounts.push(AccountMeta::new(stats_account, false));
+        let transaction = Transaction::new_signed_with_payer(
+            &[withdraw],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let stats = banks_client.get_account(stats_account).await.unwrap().unwrap();
+        assert_eq!(stats_of(stats).total_value_locked, 700_000_000);
+    }
+
+    // Test that credits, interest and swept dust move the TVL, and a batch credit
+    // can't exceed the cap
+    #[tokio::test]
+    async fn test_tvl_cap_counts_credits_and_interest() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Fund the rewards account with 2 SOL above its rent reserve
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        program_test.add_account(
+            rewards_account,
+            Account {
+                lamports: Rent::default().minimum_balance(0) + 2_000_000_000,
+                data: vec![],
+                owner: system_program::id(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let dust_owner = Keypair::new();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (stats_account, _) = find_stats_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        // Pay 10% a year on 0.5 SOL deposited before the cap of 1 SOL is set
+        let set_rate = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::SetInterestRate { vault_id: 0, rate_bps: 1_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let mut deposit = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::Deposit { vault_id: 0, amount: 500_000_000 },
+        );
+        deposit.accounts.push(AccountMeta::new(rewards_account, false));
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+        let set_tvl_cap = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new(stats_account, false),
+                AccountMeta::new_readonly(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: DepositInstruction::SetTvlCap { vault_id: 0, cap: 1_000_000_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                system_instruction::transfer(&payer.pubkey(), &dust_owner.pubkey(), 10_000_000),
+                initialize_instruction(program_id, dust_owner.pubkey(), 0),
+                set_rate,
+                deposit,
+                set_tvl_cap,
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &dust_owner],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        let credit = |credits: Vec<(Pubkey, u64)>| {
+            let mut accounts = vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(stats_account, false),
+            ];
+            for (owner, _) in &credits {
+                let (user_data_account, _) = find_user_account_address(owner, 0, &program_id);
+                accounts.push(AccountMeta::new(user_data_account, false));
+            }
+            Instruction {
+                program_id,
+                accounts,
+                data: DepositInstruction::BatchCredit { vault_id: 0, credits }
+                    .try_to_vec()
+                    .unwrap(),
+            }
+        };
+        let tvl = |context: &mut solana_program_test::ProgramTestContext| {
+            let mut banks_client = context.banks_client.clone();
+            async move {
+                let stats = banks_client.get_account(stats_account).await.unwrap().unwrap();
+                VaultStats::try_from_slice(&stats.data).unwrap().total_value_locked
+            }
+        };
+        async fn send(
+            context: &mut solana_program_test::ProgramTestContext,
+            payer: &Keypair,
+            instruction: Instruction,
+        ) -> Result<(), TransactionError> {
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[payer],
+                recent_blockhash,
+            );
+            let result = context.banks_client.process_transaction(transaction).await;
+            result.map_err(|err| err.unwrap())
+        }
+        assert_eq!(tvl(&mut context).await, 500_000_000);
+
+        // A batch that would go over the cap is rejected whole
+        let result = send(&mut context, &payer, credit(vec![(payer.pubkey(), 500_000_001)])).await;
+        assert_eq!(
+            result,
+            Err(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::CapExceeded as u32)
+            ))
+        );
+        let credits = vec![(payer.pubkey(), 300_000_000), (dust_owner.pubkey(), 1_000)];
+        send(&mut context, &payer, credit(credits)).await.unwrap();
+        assert_eq!(tvl(&mut context).await, 800_001_000);
+
+        // A year on, the 0.08 SOL of interest paid to the payer is counted in
+        let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        clock.unix_timestamp += super::SECONDS_PER_YEAR;
+        context.set_sysvar(&clock);
+        let accrue = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(stats_account, false),
+                AccountMeta::new(user_data_account, false),
+            ],
+            data: DepositInstruction::AccrueInterest { vault_id: 0 }.try_to_vec().unwrap(),
+        };
+        send(&mut context, &payer, accrue).await.unwrap();
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 880_000_000);
+        assert_eq!(tvl(&mut context).await, 880_001_000);
+
+        // Sweeping the dust takes it off again, along with the 100 lamports of
+        // interest it earned first
+        let set_dust_threshold =
+            set_dust_threshold_instruction(program_id, payer.pubkey(), 0, 2_000);
+        send(&mut context, &payer, set_dust_threshold).await.unwrap();
+        let sweep = sweep_dust_instruction(program_id, payer.pubkey(), dust_owner.pubkey(), 0);
+        send(&mut context, &payer, sweep).await.unwrap();
+        assert_eq!(tvl(&mut context).await, 880_000_000);
+    }
+
+    // Test that only an empty account closes, and not while its deposit window is open
+    #[tokio::test]
+    async fn test_close_account() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+        let owner = Keypair::new();
+        program_test.add_account(
+            owner.pubkey(),
+            Account {
+                lamports: 1_000_000_000,
+                ..Account::default()
+            },
+        );
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (user_data_account, _) = find_user_account_address(&owner.pubkey(), 0, &program_id);
+        let amount = 2_000_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(payer.pubkey(), true),
+                        AccountMeta::new(config_account, false),
+                        AccountMeta::new_readonly(system_program::id(), false),
+                    ],
+                    data: DepositInstruction::SetDepositLimit {
+                        vault_id: 0,
+                        daily_limit: 5_000_000,
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                },
+                initialize_instruction(program_id, owner.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    owner.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority(), &owner],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // The payer covers fees, so the owner's lamports move only by what the program does
+        async fn close_at(
+            context: &mut solana_program_test::ProgramTestContext,
+            program_id: Pubkey,
+            payer: &Keypair,
+            owner: &Keypair,
+            unix_timestamp: i64,
+        ) -> Result<(), TransactionError> {
+            let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+            clock.unix_timestamp = unix_timestamp;
+            context.set_sysvar(&clock);
+
+            let (user_data_account, _) =
+                find_user_account_address(&owner.pubkey(), 0, &program_id);
+            let (config_account, _) = find_config_address(0, &program_id);
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new(owner.pubkey(), true),
+                        AccountMeta::new(user_data_account, false),
+                        AccountMeta::new_readonly(config_account, false),
+                    ],
+                    data: DepositInstruction::CloseAccount { vault_id: 0 }.try_to_vec().unwrap(),
+                }],
+                Some(&payer.pubkey()),
+                &[payer, owner],
+                recent_blockhash,
+            );
+            context
+                .banks_client
+                .process_transaction(transaction)
+                .await
+                .map_err(|err| err.unwrap())
+        }
+
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        let start = clock.unix_timestamp;
+        let close_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+
+        // A balance keeps the account open
+        assert_eq!(
+            close_at(&mut context, program_id, &payer, &owner, start).await,
+            close_error(DepositError::AccountNotEmpty)
+        );
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[transfer_instruction(
+                program_id,
+                owner.pubkey(),
+                DepositInstruction::Withdraw { vault_id: 0, amount },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // Emptied, it still can't close while today's deposits count against the limit
+        assert_eq!(
+            close_at(&mut context, program_id, &payer, &owner, start + 60).await,
+            close_error(DepositError::DepositWindowOpen)
+        );
+
+        // Once the window is over the account closes and its rent goes back to the owner
+        let rent_lamports = context
+            .banks_client
+            .get_account(user_data_account)
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+        let owner_before = context.banks_client.get_balance(owner.pubkey()).await.unwrap();
+        close_at(&mut context, program_id, &payer, &owner, start + DEPOSIT_WINDOW_SECONDS)
+            .await
+            .unwrap();
+        assert!(context.banks_client.get_account(user_data_account).await.unwrap().is_none());
+        assert_eq!(
+            context.banks_client.get_balance(owner.pubkey()).await.unwrap(),
+            owner_before + rent_lamports
+        );
+
+        // The address can be initialized again from scratch
+        let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_instruction(program_id, owner.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+        let account = context.banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.lifetime_deposited, 0);
+        assert_eq!(user_data.deposited_in_window, 0);
+    }
+
+    // Test that GetWithdrawable returns what Withdraw would allow right now
+    #[tokio::test]
+    async fn test_get_withdrawable() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program with a context so the clock can be warped
+        let mut context = program_test.start_with_context().await;
+        let payer = context.payer.insecure_clone();
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+
+        // 4 SOL plain plus 10 SOL vesting over 1000 seconds
+        let vest_start = clock.unix_timestamp;
+        let vest_end = vest_start + 1_000;
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_instruction(program_id, payer.pubkey(), 0),
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::Deposit { vault_id: 0, amount: 4_000_000_000 },
+                ),
+                transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    DepositInstruction::DepositVested {
+                        vault_id: 0,
+                        amount: 10_000_000_000,
+                        vest_start,
+                        vest_end,
+                    },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            context.last_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+
+        // Warp the clock and simulate GetWithdrawable, which needs no signature from the owner
+        async fn withdrawable_at(
+            context: &mut solana_program_test::ProgramTestContext,
+            program_id: Pubkey,
+            owner: Pubkey,
+            unix_timestamp: i64,
+        ) -> u64 {
+            let mut clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+            clock.unix_timestamp = unix_timestamp;
+            context.set_sysvar(&clock);
+
+            let (user_data_account, _) = find_user_account_address(&owner, 0, &program_id);
+            let (config_account, _) = find_config_address(0, &program_id);
+            let payer = context.payer.insecure_clone();
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[Instruction {
+                    program_id,
+                    accounts: vec![
+                        AccountMeta::new_readonly(owner, false),
+                        AccountMeta::new_readonly(user_data_account, false),
+                        AccountMeta::new_readonly(config_account, false),
+                    ],
+                    data: DepositInstruction::GetWithdrawable { vault_id: 0 }
+                        .try_to_vec()
+                        .unwrap(),
+                }],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            );
+            let simulation = context.banks_client.simulate_transaction(transaction).await.unwrap();
+            simulation.result.unwrap().unwrap();
+            let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+            assert_eq!(return_data.program_id, program_id);
+            u64::from_le_bytes(return_data.data.try_into().unwrap())
+        }
+
+        // A quarter of the way through, the plain deposit plus a quarter of the schedule
+        let owner = payer.pubkey();
+        assert_eq!(
+            withdrawable_at(&mut context, program_id, owner, vest_start + 250).await,
+            4_000_000_000 + 2_500_000_000
+        );
+        assert_eq!(
+            withdrawable_at(&mut context, program_id, owner, vest_end).await,
+            14_000_000_000
+        );
+
+        // What is left of the epoch limit caps it once the limit binds
+        let (config_account, _) = find_config_address(0, &program_id);
+        let config_instruction = |instruction: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        };
+        let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                config_instruction(DepositInstruction::SetWithdrawLimit {
+                    vault_id: 0,
+                    epoch_limit: 3_000_000_000,
+                }),
+                transfer_instruction(
+                    program_id,
+                    owner,
+                    DepositInstruction::Withdraw { vault_id: 0, amount: 1_000_000_000 },
+                ),
+            ],
+            Some(&owner),
+            &[&payer],
+            recent_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(
+            withdrawable_at(&mut context, program_id, owner, vest_end).await,
+            2_000_000_000
+        );
+
+        // The residual minimum binds next, and a cooldown leaves only a request to claim
+        for (instruction, expected) in [
+            (
+                DepositInstruction::SetMinResidualBalance {
+                    vault_id: 0,
+                    min_balance: 11_500_000_000,
+                },
+                1_500_000_000,
+            ),
+            (DepositInstruction::SetWithdrawCooldown { vault_id: 0, cooldown: 1_000 }, 0),
+        ] {
+            let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+            let transaction = Transaction::new_signed_with_payer(
+                &[config_instruction(instruction)],
+                Some(&owner),
+                &[&payer],
+                recent_blockhash,
+            );
+            context.banks_client.process_transaction(transaction).await.unwrap();
+            assert_eq!(
+                withdrawable_at(&mut context, program_id, owner, vest_end).await,
+                expected
+            );
+        }
+
+        // Pausing withdrawals makes nothing withdrawable, whatever has vested
+        let recent_blockhash = context.get_new_latest_blockhash().await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new(config_account, false),
+                ],
+                data: DepositInstruction::SetPauseFlags {
+                    vault_id: 0,
+                    deposits: false,
+                    withdrawals: true,
+                }
+                .try_to_vec()
+                .unwrap(),
+            }],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        context.banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(withdrawable_at(&mut context, program_id, owner, vest_end).await, 0);
+    }
+
+    // Test that once the admin is renounced no admin-gated instruction succeeds again
+    #[tokio::test]
+    async fn test_renounce_admin() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Seed a user holding dust, so sweeping would otherwise succeed
+        let owner = Pubkey::new_unique();
+        let (user_data_account, _) = find_user_account_address(&owner, 0, &program_id);
+        let user_data = UserAccount {
+            owner,
+            balance: 1_000,
+            vest_start: 0,
+            vest_end: 0,
+            vest_total: 0,
+            lifetime_deposited: 0,
+            lifetime_withdrawn: 0,
+            deposit_window_start: 0,
+            deposited_in_window: 0,
+            mint: Pubkey::default(),
+            pending_owner: Pubkey::default(),
+            delegate: Pubkey::default(),
+            delegated_amount: 0,
+            unlock_ts: 0,
+            tranches: Default::default(),
+            interest_index: 0,
+            pending_withdrawal: 0,
+            withdraw_requested_at: 0,
+            withdraw_epoch: 0,
+            withdrawn_in_epoch: 0,
+            sweep_to_treasury: false,
+            closeable: false,
+        };
+        program_test.add_account(
+            user_data_account,
+            Account {
+                lamports: Rent::default().minimum_balance(super::UserAccount::LEN),
+                data: user_data.try_to_vec().unwrap(),
+                owner: program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        record_events();
+        let (config_account, _) = find_config_address(0, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (rewards_account, _) = find_rewards_address(0, &program_id);
+        let pauser = Keypair::new();
+
+        let admin_instruction = |signer: &Keypair, data: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(signer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: data.try_to_vec().unwrap(),
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                admin_instruction(
+                    &payer,
+                    DepositInstruction::GrantRole {
+                        vault_id: 0,
+                        role: Role::Pauser,
+                        authority: pauser.pubkey(),
+                    },
+                ),
+                admin_instruction(&payer, DepositInstruction::RenounceAdmin { vault_id: 0 }),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let account = banks_client.get_account(config_account).await.unwrap().unwrap();
+        let config = super::VaultConfig::try_from_slice(&account.data).unwrap();
+        assert_eq!(config.admin, Pubkey::default());
+        assert_eq!(config.pauser, Pubkey::default());
+
+        // The renunciation is logged like any other admin change
+        let is_admin_change =
+            |event: &Event| matches!(event, Event::Admin(admin) if admin.old == payer.pubkey());
+        assert_eq!(
+            take_events(is_admin_change),
+            vec![Event::Admin(AdminEvent {
+                vault_id: 0,
+                old: payer.pubkey(),
+                new: Pubkey::default(),
+            })]
+        );
+
+        let sweep = sweep_dust_instruction(program_id, payer.pubkey(), owner, 0);
+        let credit = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new_readonly(config_account, false),
+                AccountMeta::new(rewards_account, false),
+                AccountMeta::new(vault_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new(find_stats_address(0, &program_id).0, false),
+                AccountMeta::new(user_data_account, false),
+            ],
+            data: DepositInstruction::BatchCredit { vault_id: 0, credits: vec![(owner, 1)] }
+                .try_to_vec()
+                .unwrap(),
+        };
+
+        // Neither the former admin nor a formerly delegated role holder gets through
+        let steps = [
+            (
+                admin_instruction(
+                    &payer,
+                    DepositInstruction::SetPauseFlags {
+                        vault_id: 0,
+                        deposits: true,
+                        withdrawals: true,
+                    },
+                ),
+                &payer,
+            ),
+            (
+                admin_instruction(
+                    &pauser,
+                    DepositInstruction::SetPauseFlags {
+                        vault_id: 0,
+                        deposits: true,
+                        withdrawals: false,
+                    },
+                ),
+                &pauser,
+            ),
+            (
+                admin_instruction(
+                    &payer,
+                    DepositInstruction::GrantRole {
+                        vault_id: 0,
+                        role: Role::Pauser,
+                        authority: payer.pubkey(),
+                    },
+                ),
+                &payer,
+            ),
+            (
+                admin_instruction(
+                    &payer,
+                    DepositInstruction::RevokeRole { vault_id: 0, role: Role::Distributor },
+                ),
+                &payer,
+            ),
+            (
+                admin_instruction(
+                    &payer,
+                    DepositInstruction::SetDepositLimit { vault_id: 0, daily_limit: 1 },
+                ),
+                &payer,
+            ),
+            (sweep, &payer),
+            (credit, &payer),
+            (admin_instruction(&payer, DepositInstruction::RenounceAdmin { vault_id: 0 }), &payer),
+        ];
+        for (instruction, signer) in steps {
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer, signer],
+                recent_blockhash,
+            );
+            assert_eq!(
+                banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+                TransactionError::InstructionError(
+                    0,
+                    InstructionError::Custom(DepositError::Unauthorized as u32)
+                )
+            );
+        }
+
+        // The config can't be re-initialized to install a new admin either
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_config_instruction(program_id, payer.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        assert!(banks_client.process_transaction(transaction).await.is_err());
+        let account = banks_client.get_account(config_account).await.unwrap().unwrap();
+        let config = super::VaultConfig::try_from_slice(&account.data).unwrap();
+        assert_eq!(config.admin, Pubkey::default());
+        assert!(!config.deposits_paused);
+    }
+
+    // A caller program that forwards its instruction data to the program in
+    // its first account, then returns whatever that program returned
+    fn forwarding_process_instruction(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        data: &[u8],
+    ) -> ProgramResult {
+        let (callee, forwarded) =
+            accounts.split_first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let instruction = Instruction {
+            program_id: *callee.key,
+            accounts: forwarded
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: *account.key,
+                    is_signer: account.is_signer,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: data.to_vec(),
+        };
+        invoke(&instruction, accounts)?;
+
+        match get_return_data() {
+            Some((returned_by, returned)) if returned_by == *callee.key => {
+                set_return_data(&returned);
+                Ok(())
+            }
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+
+    // Test that a program calling deposit through CPI reads the result, and it is also logged
+    #[tokio::test]
+    async fn test_deposit_result_visible_to_cpi_caller() {
+        // Create program test with a caller program alongside
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let caller_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        program_test.add_program(
+            "forwarding_caller",
+            caller_id,
+            processor!(forwarding_process_instruction),
+        );
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program and initialize the account directly
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_instruction(program_id, payer.pubkey(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // Deposit through the caller, which re-exposes what the deposit returned
+        let amount = 3_000_000;
+        let deposit = transfer_instruction(
+            program_id,
+            payer.pubkey(),
+            DepositInstruction::Deposit { vault_id: 0, amount },
+        );
+        let mut accounts = vec![AccountMeta::new_readonly(program_id, false)];
+        accounts.extend(deposit.accounts);
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction {
+                program_id: caller_id,
+                accounts,
+                data: deposit.data,
+            }],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        let simulation = banks_client.simulate_transaction(transaction.clone()).await.unwrap();
+        simulation.result.unwrap().unwrap();
+        let details = simulation.simulation_details.unwrap();
+
+        // The caller saw the credited amount and the new balance
+        let return_data = details.return_data.unwrap();
+        assert_eq!(return_data.program_id, caller_id);
+        assert_eq!(return_data.data[..8], amount.to_le_bytes());
+        assert_eq!(return_data.data[8..], amount.to_le_bytes());
+
+        // The deposit ran nested under the caller. Its `Program data:` event
+        // only reaches the logs under the BPF runtime; processor! routes
+        // sol_log_data to a stub that prints to stdout.
+        let nested = format!("Program {} invoke [2]", program_id);
+        assert!(details.logs.contains(&nested));
+
+        banks_client.process_transaction(transaction).await.unwrap();
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, amount);
+    }
+
+    // Build the instruction that initializes an owner's balance of `mint`
+    fn initialize_token_instruction(
+        program_id: Pubkey,
+        owner: Pubkey,
+        mint: Pubkey,
+        token_program_id: Pubkey,
+        vault_id: u16,
+    ) -> Instruction {
+        let vault_token_account =
+            find_vault_token_address(vault_id, &mint, &token_program_id, &program_id);
+        let (user_data_account, _) =
+            find_token_user_account_address(&owner, vault_id, &mint, &program_id);
+        let (vault_account, _) = find_vault_address(vault_id, &program_id);
+        Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(owner, true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(vault_account, false),
+                AccountMeta::new(vault_token_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(token_program_id, false),
+                AccountMeta::new_readonly(spl_associated_token_account::id(), false),
+            ],
+            data: DepositInstruction::InitializeTokenAccount { vault_id }.try_to_vec().unwrap(),
+        }
+    }
+
+    // Build a DepositToken from `source`, or a WithdrawToken to it, passing
+    // `hook_accounts` on for the mint's transfer hook
+    #[allow(clippy::too_many_arguments)]
+    fn token_transfer_instruction(
+        program_id: Pubkey,
+        owner: Pubkey,
+        mint: Pubkey,
+        token_program_id: Pubkey,
+        source: Pubkey,
+        vault_token_account: Pubkey,
+        hook_accounts: &[AccountMeta],
+        instruction: DepositInstruction,
+    ) -> Instruction {
+        let vault_id = 0;
+        let (user_data_account, _) =
+            find_token_user_account_address(&owner, vault_id, &mint, &program_id);
+        let (vault_account, _) = find_vault_address(vault_id, &program_id);
+        let (config_account, _) = find_config_address(vault_id, &program_id);
+        let mut accounts = vec![
+            AccountMeta::new(owner, true),
+            AccountMeta::new(user_data_account, false),
+            AccountMeta::new(source, false),
+            AccountMeta::new(vault_token_account, false),
+        ];
+        if let DepositInstruction::WithdrawToken { .. } = instruction {
+            accounts.push(AccountMeta::new_readonly(vault_account, false));
+        }
+        accounts.extend([
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(token_program_id, false),
+            AccountMeta::new_readonly(config_account, false),
+        ]);
+        accounts.extend_from_slice(hook_accounts);
+        Instruction {
+            program_id,
+            accounts,
+            data: instruction.try_to_vec().unwrap(),
+        }
+    }
+
+    async fn token_balance(banks_client: &mut BanksClient, token_account: Pubkey) -> u64 {
+        let account = banks_client.get_account(token_account).await.unwrap().unwrap();
+        StateWithExtensions::<spl_token_2022::state::Account>::unpack(&account.data)
+            .unwrap()
+            .base
+            .amount
+    }
+
+    // Test that SPL tokens move through the vault's token account and are tracked per mint
+    #[tokio::test]
+    async fn test_token_deposit_and_withdraw() {
+        // Create program test; the SPL Token and ATA programs are loaded by default
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        record_events();
+
+        // Create a mint and fund the payer's token account
+        let mint = Keypair::new();
+        let payer_tokens = spl_associated_token_account::get_associated_token_address(
+            &payer.pubkey(),
+            &mint.pubkey(),
+        );
+        let setup_transaction = Transaction::new_signed_with_payer(
+            &[
+                system_instruction::create_account(
+                    &payer.pubkey(),
+                    &mint.pubkey(),
+                    Rent::default().minimum_balance(spl_token::state::Mint::LEN),
+                    spl_token::state::Mint::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_mint(
+                    &spl_token::id(),
+                    &mint.pubkey(),
+                    &payer.pubkey(),
+                    None,
+                    6,
+                )
+                .unwrap(),
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &payer.pubkey(),
+                    &payer.pubkey(),
+                    &mint.pubkey(),
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::mint_to(
+                    &spl_token::id(),
+                    &mint.pubkey(),
+                    &payer_tokens,
+                    &payer.pubkey(),
+                    &[],
+                    1_000_000,
+                )
+                .unwrap(),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &mint],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(setup_transaction).await.unwrap();
+
+        // A second owner of the same mint reuses the vault's token account
+        let other = Keypair::new();
+        let fund_other_transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &other.pubkey(), 100_000_000)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(fund_other_transaction).await.unwrap();
+
+        for owner in [&payer, &other] {
+            let transaction = Transaction::new_signed_with_payer(
+                &[initialize_token_instruction(
+                    program_id,
+                    owner.pubkey(),
+                    mint.pubkey(),
+                    spl_token::id(),
+                    0,
+                )],
+                Some(&owner.pubkey()),
+                &[owner],
+                recent_blockhash,
+            );
+            banks_client.process_transaction(transaction).await.unwrap();
+        }
+
+        // The vault PDA owns the token account, and the mint is recorded per user
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let vault_tokens =
+            find_vault_token_address(0, &mint.pubkey(), &spl_token::id(), &program_id);
+        let account = banks_client.get_account(vault_tokens).await.unwrap().unwrap();
+        let token_account = spl_token::state::Account::unpack(&account.data).unwrap();
+        assert_eq!(token_account.owner, vault_account);
+        assert_eq!(token_account.mint, mint.pubkey());
+
+        let (user_data_account, _) =
+            find_token_user_account_address(&payer.pubkey(), 0, &mint.pubkey(), &program_id);
+        let user_data_of = |account: Account| UserAccount::try_from_slice(&account.data).unwrap();
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(user_data_of(account).mint, mint.pubkey());
+
+        // Deposits must go to the vault's token account, not one the caller picks
+        let transaction = Transaction::new_signed_with_payer(
+            &[token_transfer_instruction(
+                program_id,
+                payer.pubkey(),
+                mint.pubkey(),
+                spl_token::id(),
+                payer_tokens,
+                payer_tokens,
+                &[],
+                DepositInstruction::DepositToken { vault_id: 0, amount: 1 },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::WrongTokenAccount as u32)
+            )
+        );
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[token_transfer_instruction(
+                program_id,
+                payer.pubkey(),
+                mint.pubkey(),
+                spl_token::id(),
+                payer_tokens,
+                vault_tokens,
+                &[],
+                DepositInstruction::DepositToken { vault_id: 0, amount: 400_000 },
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(token_balance(&mut banks_client, payer_tokens).await, 600_000);
+        assert_eq!(token_balance(&mut banks_client, vault_tokens).await, 400_000);
+
+        // Withdrawing more than the tracked balance fails, even though the vault holds it
+        let withdraw = |amount| {
+            Transaction::new_signed_with_payer(
+                &[token_transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    mint.pubkey(),
+                    spl_token::id(),
+                    payer_tokens,
+                    vault_tokens,
+                    &[],
+                    DepositInstruction::WithdrawToken { vault_id: 0, amount },
+                )],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            )
+        };
+        assert_eq!(
+            banks_client.process_transaction(withdraw(400_001)).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::InsufficientBalance as u32)
+            )
+        );
+
+        banks_client.process_transaction(withdraw(150_000)).await.unwrap();
+        assert_eq!(token_balance(&mut banks_client, payer_tokens).await, 750_000);
+        assert_eq!(token_balance(&mut banks_client, vault_tokens).await, 250_000);
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = user_data_of(account);
+        assert_eq!(user_data.balance, 250_000);
+        assert_eq!(user_data.lifetime_deposited, 400_000);
+        assert_eq!(user_data.lifetime_withdrawn, 150_000);
+        let balance_event = |amount: u64, balance: u64| BalanceEvent {
+            vault_id: 0,
+            owner: payer.pubkey(),
+            amount,
+            balance,
+        };
+        assert_eq!(
+            take_events(|event| match event {
+                Event::DepositToken(event) | Event::WithdrawToken(event) => {
+                    event.owner == payer.pubkey()
+                }
+                _ => false,
+            }),
+            vec![
+                Event::DepositToken(balance_event(400_000, 400_000)),
+                Event::WithdrawToken(balance_event(150_000, 250_000)),
+            ]
+        );
+
+        // The SOL account is separate and was never created
+        let (sol_user_data_account, _) =
+            find_user_account_address(&payer.pubkey(), 0, &program_id);
+        assert!(banks_client.get_account(sol_user_data_account).await.unwrap().is_none());
+    }
+
+    // Create a Token-2022 mint whose `extensions` are set up by `extension_instructions`,
+    // and mint `supply` to the payer's token account, which is returned
+    async fn create_token_2022_mint(
+        banks_client: &mut BanksClient,
+        payer: &Keypair,
+        recent_blockhash: solana_sdk::hash::Hash,
+        mint: &Keypair,
+        extensions: &[ExtensionType],
+        extension_instructions: Vec<Instruction>,
+        supply: u64,
+    ) -> Pubkey {
+        let token_program_id = spl_token_2022::id();
+        let mint_len =
+            ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions)
+                .unwrap();
+        let payer_tokens =
+            spl_associated_token_account::get_associated_token_address_with_program_id(
+                &payer.pubkey(),
+                &mint.pubkey(),
+                &token_program_id,
+            );
+
+        let mut instructions = vec![system_instruction::create_account(
+            &payer.pubkey(),
+            &mint.pubkey(),
+            Rent::default().minimum_balance(mint_len),
+            mint_len as u64,
+            &token_program_id,
+        )];
+        instructions.extend(extension_instructions);
+        instructions.extend([
+            spl_token_2022::instruction::initialize_mint(
+                &token_program_id,
+                &mint.pubkey(),
+                &payer.pubkey(),
+                None,
+                6,
+            )
+            .unwrap(),
+            spl_associated_token_account::instruction::create_associated_token_account(
+                &payer.pubkey(),
+                &payer.pubkey(),
+                &mint.pubkey(),
+                &token_program_id,
+            ),
+            spl_token_2022::instruction::mint_to(
+                &token_program_id,
+                &mint.pubkey(),
+                &payer_tokens,
+                &payer.pubkey(),
+                &[],
+                supply,
+            )
+            .unwrap(),
+        ]);
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &[payer, mint],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_token_instruction(
+                Pubkey::from_str(PROGRAM_ID).unwrap(),
+                payer.pubkey(),
+                mint.pubkey(),
+                token_program_id,
+                0,
+            )],
+            Some(&payer.pubkey()),
+            &[payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        payer_tokens
+    }
+
+    // Test that a Token-2022 transfer fee is charged to the depositor and the
+    // withdrawer instead of leaving the vault short
+    #[tokio::test]
+    async fn test_token_2022_transfer_fee() {
+        // Create program test; Token-2022 is loaded by default
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        // A 1% fee on every transfer
+        let token_program_id = spl_token_2022::id();
+        let mint = Keypair::new();
+        let payer_tokens = create_token_2022_mint(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            &mint,
+            &[ExtensionType::TransferFeeConfig],
+            vec![initialize_transfer_fee_config(
+                &token_program_id,
+                &mint.pubkey(),
+                None,
+                None,
+                100,
+                u64::MAX,
+            )
+            .unwrap()],
+            1_000_000,
+        )
+        .await;
+        let vault_tokens =
+            find_vault_token_address(0, &mint.pubkey(), &token_program_id, &program_id);
+        let token_instruction = |instruction| {
+            Transaction::new_signed_with_payer(
+                &[token_transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    mint.pubkey(),
+                    token_program_id,
+                    payer_tokens,
+                    vault_tokens,
+                    &[],
+                    instruction,
+                )],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            )
+        };
+
+        // Only the 99_000 the vault received is credited for a 100_000 deposit
+        let deposit = DepositInstruction::DepositToken { vault_id: 0, amount: 100_000 };
+        banks_client.process_transaction(token_instruction(deposit)).await.unwrap();
+        assert_eq!(token_balance(&mut banks_client, vault_tokens).await, 99_000);
+
+        let (user_data_account, _) =
+            find_token_user_account_address(&payer.pubkey(), 0, &mint.pubkey(), &program_id);
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 99_000);
+
+        // A withdraw debits the full amount; the fee comes out of what arrives
+        let withdraw = DepositInstruction::WithdrawToken { vault_id: 0, amount: 50_000 };
+        banks_client.process_transaction(token_instruction(withdraw)).await.unwrap();
+        assert_eq!(token_balance(&mut banks_client, vault_tokens).await, 49_000);
+        assert_eq!(token_balance(&mut banks_client, payer_tokens).await, 949_500);
+
+        // The vault still backs the whole tracked balance
+        let withdraw = DepositInstruction::WithdrawToken { vault_id: 0, amount: 49_001 };
+        let result = banks_client.process_transaction(token_instruction(withdraw)).await;
+        assert_eq!(
+            result.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::InsufficientBalance as u32)
+            )
+        );
+        let withdraw = DepositInstruction::WithdrawToken { vault_id: 0, amount: 49_000 };
+        banks_client.process_transaction(token_instruction(withdraw)).await.unwrap();
+        assert_eq!(token_balance(&mut banks_client, vault_tokens).await, 0);
+    }
+
+    // A transfer hook that counts its Execute calls in the one extra account it requires
+    fn counting_transfer_hook(
+        _program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        data: &[u8],
+    ) -> ProgramResult {
+        match TransferHookInstruction::unpack(data)? {
+            TransferHookInstruction::Execute { .. } => {
+                let counter = accounts.get(5).ok_or(ProgramError::NotEnoughAccountKeys)?;
+                counter.data.borrow_mut()[0] += 1;
+                Ok(())
+            }
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+
+    // Test that deposits and withdrawals of a transfer-hook mint run the hook with
+    // the extra accounts passed after the fixed ones
+    #[tokio::test]
+    async fn test_token_2022_transfer_hook() {
+        // Create program test with the hook program, its counter and its account list
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let hook_program_id = Pubkey::new_unique();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        program_test.add_program(
+            "counting_transfer_hook",
+            hook_program_id,
+            processor!(counting_transfer_hook),
+        );
+
+        let mint = Keypair::new();
+        let counter = Pubkey::new_unique();
+        program_test.add_account(
+            counter,
+            Account {
+                lamports: Rent::default().minimum_balance(1),
+                data: vec![0],
+                owner: hook_program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        let validation_account = get_extra_account_metas_address(&mint.pubkey(), &hook_program_id);
+        let extra_metas = [ExtraAccountMeta::new_with_pubkey(&counter, false, true).unwrap()];
+        let validation_len = ExtraAccountMetaList::size_of(extra_metas.len()).unwrap();
+        let mut validation_data = vec![0; validation_len];
+        ExtraAccountMetaList::init::<ExecuteInstruction>(&mut validation_data, &extra_metas)
+            .unwrap();
+        program_test.add_account(
+            validation_account,
+            Account {
+                lamports: Rent::default().minimum_balance(validation_data.len()),
+                data: validation_data,
+                owner: hook_program_id,
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        let token_program_id = spl_token_2022::id();
+        let payer_tokens = create_token_2022_mint(
+            &mut banks_client,
+            &payer,
+            recent_blockhash,
+            &mint,
+            &[ExtensionType::TransferHook],
+            vec![transfer_hook::instruction::initialize(
+                &token_program_id,
+                &mint.pubkey(),
+                None,
+                Some(hook_program_id),
+            )
+            .unwrap()],
+            1_000_000,
+        )
+        .await;
+        let vault_tokens =
+            find_vault_token_address(0, &mint.pubkey(), &token_program_id, &program_id);
+        let hook_accounts = [
+            AccountMeta::new_readonly(hook_program_id, false),
+            AccountMeta::new_readonly(validation_account, false),
+            AccountMeta::new(counter, false),
+        ];
+        let token_instruction = |hook_accounts: &[AccountMeta], instruction| {
+            Transaction::new_signed_with_payer(
+                &[token_transfer_instruction(
+                    program_id,
+                    payer.pubkey(),
+                    mint.pubkey(),
+                    token_program_id,
+                    payer_tokens,
+                    vault_tokens,
+                    hook_accounts,
+                    instruction,
+                )],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            )
+        };
+
+        // Without the hook's accounts the transfer can't run the hook and fails
+        let deposit = DepositInstruction::DepositToken { vault_id: 0, amount: 1_000 };
+        assert!(banks_client.process_transaction(token_instruction(&[], deposit)).await.is_err());
+
+        let deposit = DepositInstruction::DepositToken { vault_id: 0, amount: 1_000 };
+        banks_client
+            .process_transaction(token_instruction(&hook_accounts, deposit))
+            .await
+            .unwrap();
+        let withdraw = DepositInstruction::WithdrawToken { vault_id: 0, amount: 400 };
+        banks_client
+            .process_transaction(token_instruction(&hook_accounts, withdraw))
+            .await
+            .unwrap();
+
+        // The hook ran for both directions
+        let account = banks_client.get_account(counter).await.unwrap().unwrap();
+        assert_eq!(account.data[0], 2);
+        assert_eq!(token_balance(&mut banks_client, vault_tokens).await, 600);
+    }
+
+    // Test that native SOL deposited through DepositWrapped is tracked as wSOL and
+    // comes back as native SOL, with the unwrap account's rent refunded
+    #[tokio::test]
+    async fn test_wrapped_sol_round_trip() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        record_events();
+
+        // A separate owner, so its lamports move only by what the program does
+        let owner = Keypair::new();
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &owner.pubkey(), 5_000_000_000)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let mint = spl_token::native_mint::id();
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_token_instruction(program_id, owner.pubkey(), mint, spl_token::id(), 0)],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let (user_data_account, _) =
+            find_token_user_account_address(&owner.pubkey(), 0, &mint, &program_id);
+        let (vault_account, _) = find_vault_address(0, &program_id);
+        let (config_account, _) = find_config_address(0, &program_id);
+        let vault_tokens = find_vault_token_address(0, &mint, &spl_token::id(), &program_id);
+        let (unwrap_account, _) = find_unwrap_address(&owner.pubkey(), 0, &program_id);
+
+        let deposit_wrapped = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(owner.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(vault_tokens, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(config_account, false),
+            ],
+            data: DepositInstruction::DepositWrapped { vault_id: 0, amount: 2_000_000_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let owner_before = banks_client.get_balance(owner.pubkey()).await.unwrap();
+        let transaction = Transaction::new_signed_with_payer(
+            &[deposit_wrapped],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // The lamports became the vault's wSOL and the owner's native-mint balance
+        let owner_after_deposit = banks_client.get_balance(owner.pubkey()).await.unwrap();
+        assert_eq!(owner_before - owner_after_deposit, 2_000_000_000);
+        assert_eq!(token_balance(&mut banks_client, vault_tokens).await, 2_000_000_000);
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 2_000_000_000);
+
+        let withdraw_unwrapped = |amount| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(owner.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(vault_tokens, false),
+                AccountMeta::new_readonly(vault_account, false),
+                AccountMeta::new(unwrap_account, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(config_account, false),
+            ],
+            data: DepositInstruction::WithdrawUnwrapped { vault_id: 0, amount }
+                .try_to_vec()
+                .unwrap(),
+        };
+
+        // More than the tracked balance is refused
+        let transaction = Transaction::new_signed_with_payer(
+            &[withdraw_unwrapped(2_000_000_001)],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client.process_transaction(transaction).await.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(DepositError::InsufficientBalance as u32)
+            )
+        );
+
+        // Lamports sent to the unwrap address ahead of time don't block the unwrap
+        let dust = Rent::default().minimum_balance(0);
+        let transaction = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &unwrap_account, dust)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        // The owner receives exactly the amount and those lamports, with the unwrap rent
+        // back and the account gone
+        let transaction = Transaction::new_signed_with_payer(
+            &[withdraw_unwrapped(500_000_000)],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        let owner_after_withdraw = banks_client.get_balance(owner.pubkey()).await.unwrap();
+        assert_eq!(owner_after_withdraw - owner_after_deposit, 500_000_000 + dust);
+        assert_eq!(token_balance(&mut banks_client, vault_tokens).await, 1_500_000_000);
+        assert!(banks_client.get_account(unwrap_account).await.unwrap().is_none());
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        assert_eq!(UserAccount::try_from_slice(&account.data).unwrap().balance, 1_500_000_000);
+
+        // The same balance is reachable as plain wSOL through WithdrawToken
+        let wsol_account = spl_associated_token_account::get_associated_token_address(
+            &owner.pubkey(),
+            &mint,
+        );
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &payer.pubkey(),
+                    &owner.pubkey(),
+                    &mint,
+                    &spl_token::id(),
+                ),
+                token_transfer_instruction(
+                    program_id,
+                    owner.pubkey(),
+                    mint,
+                    spl_token::id(),
+                    wsol_account,
+                    vault_tokens,
+                    &[],
+                    DepositInstruction::WithdrawToken { vault_id: 0, amount: 1_500_000_000 },
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+        assert_eq!(token_balance(&mut banks_client, wsol_account).await, 1_500_000_000);
+        assert_eq!(token_balance(&mut banks_client, vault_tokens).await, 0);
+
+        // Each way in and out logs its own event
+        let balance_event = |amount: u64, balance: u64| BalanceEvent {
+            vault_id: 0,
+            owner: owner.pubkey(),
+            amount,
+            balance,
+        };
+        assert_eq!(
+            take_events(|event| match event {
+                Event::DepositWrapped(event)
+                | Event::WithdrawUnwrapped(event)
+                | Event::WithdrawToken(event) => event.owner == owner.pubkey(),
+                _ => false,
+            }),
+            vec![
+                Event::DepositWrapped(balance_event(2_000_000_000, 2_000_000_000)),
+                Event::WithdrawUnwrapped(balance_event(500_000_000, 1_500_000_000)),
+                Event::WithdrawToken(balance_event(1_500_000_000, 0)),
+            ]
+        );
+    }
+
+    // Deposit 2 SOL as the payer's wSOL balance, then check that the withdrawal
+    // `withdraw` builds from the owner and an amount is held to the vault's
+    // cooldown, residual minimum and epoch limit like Withdraw
+    async fn check_wrapped_withdraw_limits(withdraw: fn(Pubkey, Pubkey, u64) -> Instruction) {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_program_data(&mut program_test, &program_id);
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+        let mint = spl_token::native_mint::id();
+        let (user_data_account, _) =
+            find_token_user_account_address(&payer.pubkey(), 0, &mint, &program_id);
+        let (config_account, _) = find_config_address(0, &program_id);
+        let vault_tokens = find_vault_token_address(0, &mint, &spl_token::id(), &program_id);
+        let deposit_wrapped = Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(user_data_account, false),
+                AccountMeta::new(vault_tokens, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+                AccountMeta::new_readonly(spl_token::id(), false),
+                AccountMeta::new_readonly(config_account, false),
+            ],
+            data: DepositInstruction::DepositWrapped { vault_id: 0, amount: 2_000_000_000 }
+                .try_to_vec()
+                .unwrap(),
+        };
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                initialize_config_instruction(program_id, payer.pubkey(), 0),
+                initialize_token_instruction(program_id, payer.pubkey(), mint, spl_token::id(), 0),
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &payer.pubkey(),
+                    &payer.pubkey(),
+                    &mint,
+                    &spl_token::id(),
+                ),
+                deposit_wrapped,
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &upgrade_authority()],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let config_instruction = |instruction: DepositInstruction| Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(payer.pubkey(), true),
+                AccountMeta::new(config_account, false),
+                AccountMeta::new_readonly(system_program::id(), false),
+            ],
+            data: instruction.try_to_vec().unwrap(),
+        };
+        let process = |instruction: Instruction| {
+            let mut banks_client = banks_client.clone();
+            let transaction = Transaction::new_signed_with_payer(
+                &[instruction],
+                Some(&payer.pubkey()),
+                &[&payer],
+                recent_blockhash,
+            );
+            async move {
+                let result = banks_client.process_transaction(transaction).await;
+                result.map_err(|err| err.unwrap())
+            }
+        };
+        let custom_error = |error: DepositError| {
+            Err(TransactionError::InstructionError(0, InstructionError::Custom(error as u32)))
+        };
+        let withdraw = |amount: u64| withdraw(program_id, payer.pubkey(), amount);
+
+        // Nothing leaves while a cooldown is set, since only SOL can be requested
+        let set_cooldown = |cooldown: i64| {
+            config_instruction(DepositInstruction::SetWithdrawCooldown { vault_id: 0, cooldown })
+        };
+        process(set_cooldown(60)).await.unwrap();
+        let result = process(withdraw(1)).await;
+        assert_eq!(result, custom_error(DepositError::WithdrawalRequestRequired));
+        process(set_cooldown(0)).await.unwrap();
+
+        // A partial withdrawal can't leave less than the residual minimum
+        let instruction =
+            DepositInstruction::SetMinResidualBalance { vault_id: 0, min_balance: 100_000_000 };
+        process(config_instruction(instruction)).await.unwrap();
+        let result = process(withdraw(1_950_000_000)).await;
+        assert_eq!(result, custom_error(DepositError::ResidualBelowMinimum));
+
+        // Withdrawals in an epoch add up towards its limit
+        let instruction =
+            DepositInstruction::SetWithdrawLimit { vault_id: 0, epoch_limit: 300_000_000 };
+        process(config_instruction(instruction)).await.unwrap();
+        process(withdraw(200_000_000)).await.unwrap();
+        let result = process(withdraw(150_000_000)).await;
+        assert_eq!(result, custom_error(DepositError::WithdrawLimitExceeded));
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.balance, 1_800_000_000);
+        assert_eq!(user_data.withdrawn_in_epoch, 200_000_000);
+    }
+
+    // Test that WithdrawToken is held to the same limits as Withdraw
+    #[tokio::test]
+    async fn test_withdraw_token_limits() {
+        check_wrapped_withdraw_limits(|program_id, owner, amount| {
+            let mint = spl_token::native_mint::id();
+            token_transfer_instruction(
+                program_id,
+                owner,
+                mint,
+                spl_token::id(),
+                spl_associated_token_account::get_associated_token_address(&owner, &mint),
+                find_vault_token_address(0, &mint, &spl_token::id(), &program_id),
+                &[],
+                DepositInstruction::WithdrawToken { vault_id: 0, amount },
+            )
+        })
+        .await;
+    }
+
+    // Test that WithdrawUnwrapped is held to the same limits as Withdraw
+    #[tokio::test]
+    async fn test_withdraw_unwrapped_limits() {
+        check_wrapped_withdraw_limits(|program_id, owner, amount| {
+            let mint = spl_token::native_mint::id();
+            let (user_data_account, _) =
+                find_token_user_account_address(&owner, 0, &mint, &program_id);
+            let (vault_account, _) = find_vault_address(0, &program_id);
+            let (unwrap_account, _) = find_unwrap_address(&owner, 0, &program_id);
+            let (config_account, _) = find_config_address(0, &program_id);
+            Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(owner, true),
+                    AccountMeta::new(user_data_account, false),
+                    AccountMeta::new(
+                        find_vault_token_address(0, &mint, &spl_token::id(), &program_id),
+                        false,
+                    ),
+                    AccountMeta::new_readonly(vault_account, false),
+                    AccountMeta::new(unwrap_account, false),
+                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new_readonly(system_program::id(), false),
+                    AccountMeta::new_readonly(spl_token::id(), false),
+                    AccountMeta::new_readonly(config_account, false),
+                ],
+                data: DepositInstruction::WithdrawUnwrapped { vault_id: 0, amount }
+                    .try_to_vec()
+                    .unwrap(),
+            }
+        })
+        .await;
+    }
+
+    // Test that lifetime totals accumulate while the balance tracks the net amount
+    #[tokio::test]
+    async fn test_lifetime_totals() {
+        // Create program test
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let mut program_test = ProgramTest::new(
+            "solana_deposit_program",
+            program_id,
+            processor!(process_instruction),
+        );
+        add_config(&mut program_test, &program_id, 0);
+        add_funded_vault(&mut program_test, &program_id, 0);
+
+        // Start program
+        let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+        let (user_data_account, _) = find_user_account_address(&payer.pubkey(), 0, &program_id);
+
+        let mut instructions = vec![initialize_instruction(program_id, payer.pubkey(), 0)];
+        for amount in [3_000_000, 2_000_000, 1_000_000] {
+            instructions.push(transfer_instruction(
+                program_id,
+                payer.pubkey(),
+                DepositInstruction::Deposit { vault_id: 0, amount },
+            ));
+        }
+        for amount in [1_500_000, 500_000] {
+            instructions.push(transfer_instruction(
+                program_id,
+                payer.pubkey(),
+                DepositInstruction::Withdraw { vault_id: 0, amount },
+            ));
+        }
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let account = banks_client.get_account(user_data_account).await.unwrap().unwrap();
+        let user_data = UserAccount::try_from_slice(&account.data).unwrap();
+        assert_eq!(user_data.lifetime_deposited, 6_000_000);
+        assert_eq!(user_data.lifetime_withdrawn, 2_000_000);
+        assert_eq!(user_data.balance, 4_000_000);
+    }
+
+    // Test that an account created before lifetime totals existed keeps working
+    #[test]
+    fn test_legacy_user_account_layout() {
+        let user_data = pure_account(100);
+        let data = user_data.try_to_vec().unwrap();
+        let legacy = super::UserAccount::unpack(&data[..super::UserAccount::LEGACY_LEN]).unwrap();
+        assert_eq!(legacy.balance, 100);
+        assert_eq!((legacy.lifetime_deposited, legacy.lifetime_withdrawn), (0, 0));
+
+        // Trailing space reserved by GrowAccount is ignored, just as before
+        let mut grown = data.clone();
+        grown.resize(super::MAX_USER_ACCOUNT_SIZE, 0);
+        assert_eq!(super::UserAccount::unpack(&grown).unwrap().balance, 100);
+    }
+
+    // Test that signing uses the config's recorded vault bump, and that configs
+    // without one still find the vault
+    #[test]
+    fn test_vault_address_uses_recorded_bump() {
+        let program_id = Pubkey::from_str(PROGRAM_ID).unwrap();
+        let expected = find_vault_address(3, &program_id);
+        let mut config = super::VaultConfig::default();
+        assert_eq!(config.vault_address(3, &program_id), expected);
+
+        config.vault_bump = expected.1;
+        assert_eq!(config.vault_address(3, &program_id), expected);
+
+        // A recorded bump is taken as is rather than searched for again
+        let other_bump = (1..expected.1)
+            .rev()
+            .find(|bump| {
+                let seeds: &[&[u8]] = &[b"vault", &3u16.to_le_bytes(), &[*bump]];
+                Pubkey::create_program_address(seeds, &program_id).is_ok()
+            })
+            .unwrap();
+        config.vault_bump = other_bump;
+        assert_eq!(config.vault_address(3, &program_id).1, other_bump);
+    }
+
+    // Plain account state for the pure state transition tests
+    fn pure_account(balance: u64) -> super::UserAccount {
+        super::UserAccount {
+            owner: Pubkey::new_unique(),
+            balance,
+            vest_start: 0,
+            vest_end: 0,
+            vest_total: 0,
+            lifetime_deposited: 0,
+            lifetime_withdrawn: 0,
+            deposit_window_start: 0,
+            deposited_in_window: 0,
+            mint: Pubkey::default(),
+            pending_owner: Pubkey::default(),
+            delegate: Pubkey::default(),
+            delegated_amount: 0,
+            unlock_ts: 0,
+            tranches: Default::default(),
+            interest_index: 0,
+            pending_withdrawal: 0,
+            withdraw_requested_at: 0,
+            withdraw_epoch: 0,
+            withdrawn_in_epoch: 0,
+            sweep_to_treasury: false,
+            closeable: false,
+        }
+    }
+
+    // Test the deposit state transition without a bank
+    #[test]
+    fn test_apply_deposit() {
+        let user_data = pure_account(100);
+
+        // A zero deposit leaves the balance as it was
+        assert_eq!(apply_deposit(&user_data, 0, None, 0).unwrap().balance, 100);
+
+        let deposited = apply_deposit(&user_data, 50, None, 0).unwrap();
+        assert_eq!(deposited.owner, user_data.owner);
+        assert_eq!(deposited.balance, 150);
+        assert_eq!(deposited.lifetime_deposited, 50);
+        assert_eq!(deposited.vest_total, 0);
+
+        // Overflowing the balance is an error rather than a wrap
+        assert_eq!(
+            apply_deposit(&pure_account(u64::MAX), 1, None, 0).unwrap_err(),
+            DepositError::ArithmeticOverflow.into()
+        );
+
+        // A vested deposit starts a schedule over the credited amount only
+        let vested = apply_deposit(&user_data, 50, Some((10, 20)), 0).unwrap();
+        assert_eq!((vested.vest_start, vested.vest_end, vested.vest_total), (10, 20, 50));
+        assert_eq!(
+            apply_deposit(&vested, 1, Some((30, 40)), 15).unwrap_err(),
+            DepositError::VestingInProgress.into()
+        );
+        assert_eq!(apply_deposit(&vested, 1, Some((30, 40)), 20).unwrap().vest_total, 1);
+
+        // A plain deposit keeps a running schedule
+        assert_eq!(apply_deposit(&vested, 1, None, 15).unwrap().vest_total, 50);
+
+        // Depositing into an account SweepDust emptied makes it an ordinary account again
+        let swept = super::UserAccount { closeable: true, ..pure_account(0) };
+        assert!(!apply_deposit(&swept, 1, None, 0).unwrap().closeable);
+
+        assert_eq!(
+            apply_deposit(&user_data, 50, Some((20, 20)), 0).unwrap_err(),
+            DepositError::InvalidVestingSchedule.into()
+        );
+    }
+
+    // Test the deposit limit window without a bank
+    #[test]
+    fn test_apply_deposit_limit() {
+        let user_data = pure_account(0);
+        let now = 1_700_000_000;
+
+        // The first deposit opens a window at `now`
+        let first = apply_deposit_limit(&user_data, 60, 100, now).unwrap();
+        assert_eq!((first.deposit_window_start, first.deposited_in_window), (now, 60));
+
+        // Exactly the limit is accepted, one lamport more is not
+        let full = apply_deposit_limit(&first, 40, 100, now + 1).unwrap();
+        assert_eq!(full.deposited_in_window, 100);
+        assert_eq!(
+            apply_deposit_limit(&first, 41, 100, now + 1).unwrap_err(),
+            DepositError::DepositLimitExceeded.into()
+        );
+
+        // Once the window has passed the count starts over
+        let reset = apply_deposit_limit(&first, 100, 100, now + DEPOSIT_WINDOW_SECONDS).unwrap();
+        assert_eq!(reset.deposit_window_start, now + DEPOSIT_WINDOW_SECONDS);
+        assert_eq!(reset.deposited_in_window, 100);
+    }
+
+    // Test the withdraw state transition without a bank
+    #[test]
+    fn test_apply_withdraw() {
+        let user_data = pure_account(100);
+
+        assert_eq!(apply_withdraw(&user_data, 0, 0).unwrap().balance, 100);
+        assert_eq!(apply_withdraw(&user_data, 40, 0).unwrap().balance, 60);
+        assert_eq!(apply_withdraw(&user_data, 40, 0).unwrap().lifetime_withdrawn, 40);
+
+        // The exact balance can be withdrawn, one lamport more cannot
+        assert_eq!(apply_withdraw(&user_data, 100, 0).unwrap().balance, 0);
+        assert_eq!(
+            apply_withdraw(&user_data, 101, 0).unwrap_err(),
+            DepositError::InsufficientBalance.into()
+        );
+
+        // Half of a 100 lamport schedule has vested at its midpoint
+        let vesting = apply_deposit(&pure_account(0), 100, Some((0, 10)), 0).unwrap();
+        assert_eq!(apply_withdraw(&vesting, 50, 5).unwrap().balance, 50);
+        assert_eq!(
+            apply_withdraw(&vesting, 51, 5).unwrap_err(),
+            DepositError::AmountStillVesting.into()
+        );
+    }
+
+    // Test the epoch withdrawal limit and its reset when the epoch advances
+    #[test]
+    fn test_apply_withdraw_limit() {
+        let user_data = pure_account(1_000);
+
+        let first = apply_withdraw_limit(&user_data, 60, 100, 7).unwrap();
+        assert_eq!(first.withdraw_epoch, 7);
+        assert_eq!(first.withdrawn_in_epoch, 60);
+
+        // Up to the limit is allowed within the epoch, one lamport more is not
+        let full = apply_withdraw_limit(&first, 40, 100, 7).unwrap();
+        assert_eq!(full.withdrawn_in_epoch, 100);
+        assert_eq!(
+            apply_withdraw_limit(&first, 41, 100, 7).unwrap_err(),
+            DepositError::WithdrawLimitExceeded.into()
+        );
+
+        // The next epoch starts the count over
+        let reset = apply_withdraw_limit(&full, 100, 100, 8).unwrap();
+        assert_eq!(reset.withdraw_epoch, 8);
+        assert_eq!(reset.withdrawn_in_epoch, 100);
+    }
+
+    // Test that a lockup blocks every withdrawal until it expires and only ever extends
+    #[test]
+    fn test_apply_lockup() {
+        let locked = apply_lockup(&pure_account(100), 10, 0).unwrap();
+        assert_eq!(locked.unlock_ts, 10);
+        assert_eq!(locked.withdrawable(9), 0);
+        assert_eq!(apply_withdraw(&locked, 1, 9).unwrap_err(), DepositError::FundsLocked.into());
+        assert_eq!(apply_withdraw(&locked, 100, 10).unwrap().balance, 0);
+
+        // An earlier unlock time keeps the later one, a later one extends it
+        assert_eq!(apply_lockup(&locked, 5, 0).unwrap().unlock_ts, 10);
+        assert_eq!(apply_lockup(&locked, 20, 0).unwrap().unlock_ts, 20);
+
+        // The unlock time must lie in the future
+        for unlock_ts in [0, -1] {
+            assert_eq!(
+                apply_lockup(&locked, unlock_ts, 0).unwrap_err(),
+                DepositError::InvalidUnlockTime.into()
+            );
+        }
+    }
+
+    // Test the interest index and what an account has earned against it
+    #[test]
+    fn test_interest_index() {
+        let year = super::SECONDS_PER_YEAR;
+        let config = VaultConfig {
+            interest_rate_bps: 500,
+            interest_index: INTEREST_INDEX_ONE,
+            interest_updated_at: 100,
+            ..VaultConfig::default()
+        };
+
+        // The index grows linearly at the rate since it was last updated
+        assert_eq!(config.interest_index_at(100).unwrap(), INTEREST_INDEX_ONE);
+        assert_eq!(config.interest_index_at(50).unwrap(), INTEREST_INDEX_ONE);
+        let half_year = config.interest_index_at(100 + year / 2).unwrap();
+        assert_eq!(half_year, INTEREST_INDEX_ONE * 1_025 / 1_000);
+
+        // Without interest enabled the index stays at 0
+        assert_eq!(VaultConfig::default().interest_index_at(year).unwrap(), 0);
+
+        // An account's first settlement only records the index
+        let user_data = pure_account(1_000);
+        assert_eq!(user_data.accrued_interest(half_year, 0), 0);
+        let settled = apply_interest(&user_data, INTEREST_INDEX_ONE, 0).unwrap();
+        assert_eq!(settled.interest_index, INTEREST_INDEX_ONE);
+
+        // After that it earns on its balance, locked tranches at their multiplier
+        assert_eq!(settled.accrued_interest(half_year, 0), 25);
+        let tier = LockupTier { duration: 10, multiplier_bps: 20_000 };
+        let tiered = apply_tranche(&settled, &tier, 1_000, 0).unwrap();
+        assert_eq!(tiered.accrued_interest(half_year, 0), 50);
+        let paid = apply_interest(&tiered, half_year, 25).unwrap();
+        assert_eq!((paid.balance, paid.interest_index), (1_025, half_year));
+        assert_eq!(paid.accrued_interest(half_year, 0), 0);
+    }
+
+    // Test that the withdraw fee rounds down and can take the whole amount
+    #[test]
+    fn test_withdraw_fee() {
+        let config = |withdraw_fee_bps: u16| VaultConfig {
+            withdraw_fee_bps,
+            ..VaultConfig::default()
+        };
+        assert_eq!(config(0).withdraw_fee(u64::MAX, u64::MAX), 0);
+        assert_eq!(config(100).withdraw_fee(1_000, 1_000), 10);
+        assert_eq!(config(100).withdraw_fee(99, 99), 0);
+        assert_eq!(config(10_000).withdraw_fee(u64::MAX, u64::MAX), u64::MAX);
+        assert!(!config(0).charges_withdraw_fee());
+    }
+
+    // Test that only a non-zero balance below the minimum is dust
+    #[test]
+    fn test_check_residual_balance() {
+        let config = VaultConfig { min_residual_balance: 100, ..VaultConfig::default() };
+        assert_eq!(config.check_residual_balance(0), Ok(()));
+        assert_eq!(config.check_residual_balance(100), Ok(()));
+        assert_eq!(
+            config.check_residual_balance(99),
+            Err(DepositError::ResidualBelowMinimum.into())
+        );
+        assert_eq!(VaultConfig::default().check_residual_balance(1), Ok(()));
+    }
+
+    // Test that a pending withdrawal becomes claimable once the cooldown has passed
+    #[test]
+    fn test_claimable_withdrawal() {
+        let user_data = super::UserAccount {
+            pending_withdrawal: 50,
+            withdraw_requested_at: 1_000,
+            ..pure_account(100)
+        };
+        assert_eq!(
+            user_data.claimable_withdrawal(10, 1_009),
+            Err(DepositError::CooldownNotElapsed.into())
+        );
+        assert_eq!(user_data.claimable_withdrawal(10, 1_010), Ok(50));
+        assert_eq!(user_data.claimable_withdrawal(0, 1_000), Ok(50));
+
+        // A cooldown too long to reach never elapses rather than overflowing
+        assert_eq!(
+            user_data.claimable_withdrawal(i64::MAX, i64::MAX - 1),
+            Err(DepositError::CooldownNotElapsed.into())
+        );
+        assert_eq!(
+            pure_account(100).claimable_withdrawal(0, 1_000),
+            Err(DepositError::NoPendingWithdrawal.into())
+        );
+    }
+
+    // Test that the enabled tier with the highest minimum reached sets the fee
+    #[test]
+    fn test_fee_tiers() {
+        let mut config = VaultConfig { withdraw_fee_bps: 100, ..VaultConfig::default() };
+        config.fee_tiers = [
+            FeeTier { min_balance: 1_000_000, fee_bps: 10 },
+            FeeTier::default(),
+            FeeTier { min_balance: 10_000, fee_bps: 50 },
+        ];
+        assert_eq!(config.withdraw_fee_bps_for(9_999), 100);
+        assert_eq!(config.withdraw_fee_bps_for(10_000), 50);
+        assert_eq!(config.withdraw_fee_bps_for(999_999), 50);
+        assert_eq!(config.withdraw_fee_bps_for(u64::MAX), 10);
+        assert_eq!(config.withdraw_fee(1_000, 1_000_000), 1);
+
+        // A tier alone makes the vault charge fees, even a disabled base fee
+        config.withdraw_fee_bps = 0;
+        assert_eq!(config.withdraw_fee_bps_for(0), 0);
+        assert!(config.charges_withdraw_fee());
+        config.fee_tiers = [FeeTier::default(); FEE_TIER_COUNT];
+        assert!(!config.charges_withdraw_fee());
+    }
+
+    // Test that tranches fill free or expired slots and weigh in while locked
+    #[test]
+    fn test_apply_tranche() {
+        let tier = LockupTier { duration: 10, multiplier_bps: 15_000 };
+        let mut user_data = pure_account(400);
+        for _ in 0..MAX_LOCKUP_TRANCHES {
+            user_data = apply_tranche(&user_data, &tier, 100, 0).unwrap();
+        }
+        assert_eq!(user_data.tranche_locked(9), 400);
+        assert_eq!(user_data.withdrawable(9), 0);
+        assert_eq!(user_data.weighted_balance(9), 600);
+        assert_eq!(
+            apply_tranche(&user_data, &tier, 100, 9).unwrap_err(),
+            DepositError::TooManyTranches.into()
+        );
+
+        // Once the lockups end the balance is free and the slots are reused
+        assert_eq!(user_data.withdrawable(10), 400);
+        assert_eq!(user_data.weighted_balance(10), 400);
+        let reused = apply_tranche(&user_data, &tier, 50, 10).unwrap();
+        assert_eq!(reused.tranches[0].unlock_ts, 20);
+        assert_eq!(reused.tranche_locked(10), 50);
+
+        // The unlock time can't overflow
+        let tier = LockupTier { duration: i64::MAX, multiplier_bps: 10_000 };
+        assert_eq!(
+            apply_tranche(&pure_account(0), &tier, 1, 1).unwrap_err(),
+            DepositError::ArithmeticOverflow.into()
+        );
+    }
+
+    // Test that a delegate spends its allowance and is held to the owner's limits
+    #[test]
+    fn test_apply_delegated_withdraw() {
+        let user_data = super::UserAccount { delegated_amount: 60, ..pure_account(100) };
+
+        let after = apply_delegated_withdraw(&user_data, 40, 0).unwrap();
+        assert_eq!(after.balance, 60);
+        assert_eq!(after.delegated_amount, 20);
+        assert_eq!(after.lifetime_withdrawn, 40);
+
+        // The whole allowance can be spent, one lamport more cannot
+        assert_eq!(apply_delegated_withdraw(&user_data, 60, 0).unwrap().delegated_amount, 0);
+        assert_eq!(
+            apply_delegated_withdraw(&user_data, 61, 0).unwrap_err(),
+            DepositError::DelegateAllowanceExceeded.into()
+        );
+
+        // An allowance above the balance doesn't let the delegate overdraw
+        let user_data = super::UserAccount { delegated_amount: 500, ..pure_account(100) };
+        assert_eq!(
+            apply_delegated_withdraw(&user_data, 101, 0).unwrap_err(),
+            DepositError::InsufficientBalance.into()
+        );
+    }
+}